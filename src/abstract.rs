@@ -42,12 +42,99 @@ pub trait AbstractTree {
     /// Will return `Err` if an IO error occurs.
     fn major_compact(&self, target_size: u64, seqno_threshold: SeqNo) -> crate::Result<()>;
 
+    /// Removes all items inside the (inclusive) key range from disk, blocking the caller until it's done.
+    ///
+    /// Segments that are fully contained in the key range are dropped without being rewritten,
+    /// which is much cheaper than writing a tombstone for every key.
+    /// Segments that only partially overlap the key range are rewritten without the key range.
+    ///
+    /// Memtables are not affected, so flush the active memtable first,
+    /// if its items should be dropped as well.
+    ///
+    /// The operation is not atomic, so concurrent reads may still see some items of the key range.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # let folder = tempfile::tempdir()?;
+    /// use lsm_tree::{AbstractTree, Config, KeyRange, Tree};
+    ///
+    /// let tree = Config::new(folder).open()?;
+    ///
+    /// tree.insert("tenant1/a", "abc", 0);
+    /// tree.insert("tenant2/a", "abc", 1);
+    /// tree.flush_active_memtable(0)?;
+    ///
+    /// tree.drop_range(KeyRange::new(("tenant1/".into(), "tenant1/~".into())))?;
+    ///
+    /// assert!(!tree.contains_key("tenant1/a", None)?);
+    /// assert!(tree.contains_key("tenant2/a", None)?);
+    /// #
+    /// # Ok::<(), lsm_tree::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    fn drop_range(&self, key_range: crate::KeyRange) -> crate::Result<()>;
+
+    /// Compacts all segments overlapping the (inclusive) key range down to `target_level`,
+    /// blocking the caller until it's done.
+    ///
+    /// Starting at L0, the overlapping segments of each level are merged into the next level,
+    /// until the target level is reached.
+    /// Compacting into the last level allows evicting tombstones of the key range,
+    /// e.g. after bulk-deleting it.
+    ///
+    /// Can run alongside other compactions; if overlapping segments are currently being compacted,
+    /// it waits until they are released.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # let folder = tempfile::tempdir()?;
+    /// use lsm_tree::{AbstractTree, Config, KeyRange, Tree};
+    ///
+    /// let tree = Config::new(folder).open()?;
+    ///
+    /// tree.insert("a", "abc", 0);
+    /// tree.flush_active_memtable(0)?;
+    /// tree.remove("a", 1);
+    /// tree.flush_active_memtable(0)?;
+    ///
+    /// tree.compact_range(KeyRange::new(("a".into(), "a".into())), 6, 2)?;
+    /// // NOTE: The tombstone was evicted, together with the value it deleted
+    /// assert_eq!(0, tree.segment_count());
+    /// #
+    /// # Ok::<(), lsm_tree::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs, or `Err(Error::InvalidLevel)`
+    /// if `target_level` does not exist.
+    fn compact_range(
+        &self,
+        key_range: crate::KeyRange,
+        target_level: u8,
+        seqno_threshold: SeqNo,
+    ) -> crate::Result<()>;
+
     /// Gets the memory usage of all bloom filters in the tree.
     fn bloom_filter_size(&self) -> usize;
 
-    // TODO:?
-    /* #[doc(hidden)]
-    fn verify(&self) -> crate::Result<usize>; */
+    /// Reads every block of every segment (and every blob of every blob file)
+    /// from disk, checking its checksum.
+    ///
+    /// Returns the amount of items read.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err(Error::Corrupted)`, listing the file and offset
+    /// of every block that failed verification.
+    ///
+    /// Will return `Err` if an IO error occurs.
+    fn verify(&self) -> crate::Result<usize>;
 
     /// Synchronously flushes a memtable to a disk segment.
     ///
@@ -102,6 +189,24 @@ pub trait AbstractTree {
         seqno_threshold: SeqNo,
     ) -> crate::Result<()>;
 
+    /// Performs compaction on the tree's levels, blocking the caller until it's done.
+    ///
+    /// `snapshots` are the seqnos of all snapshots that are still open.
+    /// Of the versions of a key between two snapshots, only the newest one
+    /// is kept, because it is the only one any snapshot can see.
+    /// This reduces space amplification if long-lived snapshots
+    /// hold back the `seqno_threshold`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    fn compact_with_snapshots(
+        &self,
+        strategy: Arc<dyn CompactionStrategy>,
+        seqno_threshold: SeqNo,
+        snapshots: &[SeqNo],
+    ) -> crate::Result<()>;
+
     /// Returns the next segment's ID.
     fn get_next_segment_id(&self) -> SegmentId;
 
@@ -139,6 +244,26 @@ pub trait AbstractTree {
     /// Can be used to determine whether to write stall.
     fn l0_run_count(&self) -> usize;
 
+    /// Returns handles to the compactions that are currently running.
+    ///
+    /// The handles report the progress of each compaction,
+    /// and can be used to cancel a single compaction.
+    fn active_compactions(&self) -> Vec<crate::compaction::CompactionHandle>;
+
+    /// Returns the cumulative compaction statistics of the tree.
+    ///
+    /// The statistics are kept in memory only, so they start
+    /// from zero when the tree is reopened.
+    /// To keep them across restarts, encode and persist them, and restore
+    /// them using [`AbstractTree::set_compaction_stats`].
+    fn compaction_stats(&self) -> crate::compaction::CompactionStats;
+
+    /// Replaces the cumulative compaction statistics of the tree.
+    ///
+    /// Can be used to restore persisted statistics,
+    /// or to reset them by passing [`Default::default`].
+    fn set_compaction_stats(&self, stats: crate::compaction::CompactionStats);
+
     /// Returns the amount of blob files currently in the tree.
     fn blob_file_count(&self) -> usize {
         0
@@ -528,7 +653,9 @@ pub trait AbstractTree {
     ///
     /// If the key already exists, the item will be overwritten.
     ///
-    /// Returns the added item's size and new size of the memtable.
+    /// Returns the added item's size, the new size of the memtable, and `true` if
+    /// the tree's [`crate::WriteBufferManager`] is over budget, in which case
+    /// the caller should call [`crate::WriteBufferManager::enforce`].
     ///
     /// # Examples
     ///
@@ -550,11 +677,47 @@ pub trait AbstractTree {
         key: K,
         value: V,
         seqno: SeqNo,
-    ) -> (u64, u64);
+    ) -> (u64, u64, bool);
+
+    /// Inserts a key-value pair into the tree that expires at the given unix timestamp (in seconds).
+    ///
+    /// Once expired, the item is hidden from reads, as if it was deleted,
+    /// and is eventually dropped physically by flushes and compactions.
+    ///
+    /// If the key already exists, the item will be overwritten.
+    ///
+    /// Returns the added item's size, the new size of the memtable, and `true` if
+    /// the tree's [`crate::WriteBufferManager`] is over budget, in which case
+    /// the caller should call [`crate::WriteBufferManager::enforce`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # let folder = tempfile::tempdir()?;
+    /// use lsm_tree::{AbstractTree, Config, Tree};
+    ///
+    /// let tree = Config::new(folder).open()?;
+    /// tree.insert_with_expiry("a", "abc", /* far in the future */ u64::MAX, 0);
+    /// tree.insert_with_expiry("b", "abc", /* long ago */ 1, 1);
+    ///
+    /// assert!(tree.contains_key("a", None)?);
+    /// assert!(!tree.contains_key("b", None)?);
+    /// #
+    /// # Ok::<(), lsm_tree::Error>(())
+    /// ```
+    fn insert_with_expiry<K: Into<UserKey>, V: Into<UserValue>>(
+        &self,
+        key: K,
+        value: V,
+        expires_at: u64,
+        seqno: SeqNo,
+    ) -> (u64, u64, bool);
 
     /// Removes an item from the tree.
     ///
-    /// Returns the added item's size and new size of the memtable.
+    /// Returns the added item's size, the new size of the memtable, and `true` if
+    /// the tree's [`crate::WriteBufferManager`] is over budget, in which case
+    /// the caller should call [`crate::WriteBufferManager::enforce`].
     ///
     /// # Examples
     ///
@@ -579,7 +742,7 @@ pub trait AbstractTree {
     /// # Errors
     ///
     /// Will return `Err` if an IO error occurs.
-    fn remove<K: Into<UserKey>>(&self, key: K, seqno: SeqNo) -> (u64, u64);
+    fn remove<K: Into<UserKey>>(&self, key: K, seqno: SeqNo) -> (u64, u64, bool);
 
     /// Removes an item from the tree.
     ///
@@ -588,7 +751,9 @@ pub trait AbstractTree {
     /// This may cause older versions of the value to be resurrected, so it should
     /// only be used and preferred in scenarios where a key is only ever written once.
     ///
-    /// Returns the added item's size and new size of the memtable.
+    /// Returns the added item's size, the new size of the memtable, and `true` if
+    /// the tree's [`crate::WriteBufferManager`] is over budget, in which case
+    /// the caller should call [`crate::WriteBufferManager::enforce`].
     ///
     /// # Examples
     ///
@@ -613,5 +778,5 @@ pub trait AbstractTree {
     /// # Errors
     ///
     /// Will return `Err` if an IO error occurs.
-    fn remove_weak<K: Into<UserKey>>(&self, key: K, seqno: SeqNo) -> (u64, u64);
+    fn remove_weak<K: Into<UserKey>>(&self, key: K, seqno: SeqNo) -> (u64, u64, bool);
 }
@@ -21,7 +21,7 @@ impl<'a> GcReader<'a> {
         let Some(item) = self
             .tree
             .get_internal_entry_with_memtable(self.memtable, key, None)?
-            .map(|x| x.value)
+            .map(|x| x.into_user_value().value)
         else {
             return Ok(None);
         };
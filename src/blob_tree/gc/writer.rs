@@ -6,19 +6,22 @@ use crate::{
     blob_tree::value::MaybeInlineValue, coding::Encode, value::InternalValue, Memtable, SeqNo,
     UserKey,
 };
+use std::io::Error as IoError;
 use value_log::ValueHandle;
 
 #[allow(clippy::module_name_repetitions)]
 pub struct GcWriter<'a> {
     seqno: SeqNo,
     buffer: Vec<(UserKey, ValueHandle, u32)>,
+    tree: &'a crate::Tree,
     memtable: &'a Memtable,
 }
 
 impl<'a> GcWriter<'a> {
-    pub fn new(seqno: SeqNo, memtable: &'a Memtable) -> Self {
+    pub fn new(seqno: SeqNo, tree: &'a crate::Tree, memtable: &'a Memtable) -> Self {
         Self {
             seqno,
+            tree,
             memtable,
             buffer: Vec::with_capacity(100),
         }
@@ -41,14 +44,26 @@ impl<'a> value_log::IndexWriter for GcWriter<'a> {
 
         #[allow(clippy::significant_drop_in_scrutinee)]
         for (key, vhandle, size) in self.buffer.drain(..) {
+            // NOTE: The memtable is locked during GC, so the index entry
+            // is still the one the GC reader saw
+            let Some(item) = self
+                .tree
+                .get_internal_entry_with_memtable(self.memtable, &key, None)
+                .map_err(|e| IoError::other(e.to_string()))?
+            else {
+                // NOTE: The value has expired in the meantime, so don't revive it
+                continue;
+            };
+
             let buf = MaybeInlineValue::Indirect { vhandle, size }.encode_into_vec();
 
-            self.memtable.insert(InternalValue::from_components(
-                key,
-                buf,
-                self.seqno,
-                crate::ValueType::Value,
-            ));
+            // NOTE: Carry over the expiry timestamp, so relocated values still expire
+            self.memtable.insert(match item.expires_at() {
+                Some(expires_at) => InternalValue::new_expiring(key, buf, expires_at, self.seqno),
+                None => {
+                    InternalValue::from_components(key, buf, self.seqno, crate::ValueType::Value)
+                }
+            });
         }
 
         Ok(())
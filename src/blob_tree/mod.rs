@@ -2,10 +2,11 @@
 // This source code is licensed under both the Apache 2.0 and MIT License
 // (found in the LICENSE-* files in the repository)
 
-mod cache;
-mod compression;
+pub(crate) mod cache;
+pub(crate) mod compression;
 mod gc;
 pub mod index;
+pub(crate) mod relocation;
 pub mod value;
 
 use crate::{
@@ -16,7 +17,8 @@ use crate::{
     segment::Segment,
     tree::inner::MemtableId,
     value::InternalValue,
-    Config, KvPair, Memtable, SegmentId, SeqNo, Snapshot, UserKey, UserValue,
+    Checksum, Config, CorruptedBlock, KvPair, Memtable, SegmentId, SeqNo, Snapshot, UserKey,
+    UserValue,
 };
 use cache::MyBlobCache;
 use compression::MyCompressor;
@@ -30,6 +32,15 @@ use std::{
 use value::MaybeInlineValue;
 use value_log::ValueLog;
 
+/// Size of a blob's magic bytes and checksum in a blob file
+const BLOB_HEADER_LEN: u64 = 8 + std::mem::size_of::<u64>() as u64;
+
+/// Size of a blob's key length in a blob file
+const KEY_LEN_SIZE: u64 = std::mem::size_of::<u16>() as u64;
+
+/// Size of a blob's value length in a blob file
+const VALUE_LEN_SIZE: u64 = std::mem::size_of::<u32>() as u64;
+
 fn resolve_value_handle(vlog: &ValueLog<MyBlobCache, MyCompressor>, item: RangeItem) -> RangeItem {
     use MaybeInlineValue::{Indirect, Inline};
 
@@ -88,17 +99,38 @@ impl BlobTree {
                 .compression(match config.blob_compression {
                     crate::CompressionType::None => None,
 
-                    #[cfg(any(feature = "lz4", feature = "miniz"))]
+                    #[cfg(any(feature = "lz4", feature = "miniz", feature = "zstd"))]
                     c => Some(MyCompressor(c)),
                 });
 
-        let index: IndexTree = config.open()?.into();
-
-        Ok(Self {
+        // NOTE: The blob tree registers itself in the write buffer manager, see below
+        let index: IndexTree = crate::Tree::open(config)?.into();
+        let blobs = ValueLog::open(vlog_path, vlog_cfg)?;
+        let pending_segments = Arc::new(AtomicUsize::new(0));
+
+        // NOTE: Compactions of the index tree may relocate blobs
+        let is_new = index
+            .value_log
+            .set(relocation::ValueLogHandle {
+                blobs: blobs.clone(),
+                pending_segments: pending_segments.clone(),
+                active_memtable: index.active_memtable.clone(),
+                last_gc_scan: Arc::default(),
+            })
+            .is_ok();
+        assert!(is_new, "index tree should not have a value log yet");
+
+        let tree = Self {
             index,
-            blobs: ValueLog::open(vlog_path, vlog_cfg)?,
-            pending_segments: Arc::new(AtomicUsize::new(0)),
-        })
+            blobs,
+            pending_segments,
+        };
+
+        if let Some(manager) = &tree.index.config.write_buffer_manager {
+            manager.register_blob_tree(&tree);
+        }
+
+        Ok(tree)
     }
 
     /// Scans the index tree, collecting statistics about
@@ -133,6 +165,16 @@ impl BlobTree {
             // to the tree
         }
 
+        // IMPORTANT: Replace the last scan before scanning, so a compaction
+        // that commits during the scan cannot add its dropped blobs on top of the scan's count
+        if let Some(value_log) = self.index.value_log.get() {
+            let gc_scan = relocation::GcScan {
+                seqno,
+                blob_file_ids: self.blobs.manifest.list_segment_ids().into_iter().collect(),
+            };
+            *value_log.last_gc_scan.lock().expect("lock is poisoned") = Arc::new(gc_scan);
+        }
+
         let iter = self
             .index
             .create_internal_range::<&[u8], RangeFull>(&.., Some(seqno), None);
@@ -205,13 +247,74 @@ impl BlobTree {
         self.blobs.apply_gc_strategy(
             strategy,
             &GcReader::new(&self.index, &memtable_lock),
-            GcWriter::new(seqno, &memtable_lock),
+            GcWriter::new(seqno, &self.index, &memtable_lock),
         )?;
 
         // NOTE: We still have the memtable lock, can't use gc_drop_stale because recursive locking
         self.blobs.drop_stale_segments().map_err(Into::into)
     }
 
+    /// Verifies all blob files, adding corrupted blobs to `corrupted`.
+    ///
+    /// Returns the amount of intact blobs.
+    fn verify_blob_files(&self, corrupted: &mut Vec<CorruptedBlock>) -> crate::Result<usize> {
+        let mut item_count = 0;
+
+        for blob_file in self.blobs.manifest.list_segments() {
+            let mut offset = 0;
+
+            for item in blob_file.scan()? {
+                // NOTE: The blob's values are not decompressed by the scanner,
+                // which is what the checksum is calculated over
+                let (key, value, expected) = match item {
+                    Ok(item) => item,
+                    Err(e) => {
+                        // NOTE: We cannot find the start of the next blob, so skip the rest of the file
+                        log::error!(
+                            "Corrupted blob at offset {offset} in blob file {}: {e:?}",
+                            blob_file.path.display(),
+                        );
+
+                        corrupted.push(CorruptedBlock {
+                            path: blob_file.path.clone(),
+                            offset,
+                            source: Box::new(e.into()),
+                        });
+                        break;
+                    }
+                };
+
+                let mut hasher = xxhash_rust::xxh3::Xxh3::new();
+                hasher.update(&key);
+                hasher.update(&value);
+                let checksum = hasher.digest();
+
+                if checksum == expected {
+                    item_count += 1;
+                } else {
+                    log::error!(
+                        "Corrupted blob at offset {offset} in blob file {}: checksum mismatch",
+                        blob_file.path.display(),
+                    );
+
+                    corrupted.push(CorruptedBlock {
+                        path: blob_file.path.clone(),
+                        offset,
+                        source: Box::new(crate::Error::InvalidChecksum((
+                            Checksum::from_raw(checksum),
+                            Checksum::from_raw(expected),
+                        ))),
+                    });
+                }
+
+                offset += BLOB_HEADER_LEN + KEY_LEN_SIZE + key.len() as u64;
+                offset += VALUE_LEN_SIZE + value.len() as u64;
+            }
+        }
+
+        Ok(item_count)
+    }
+
     /// Drops all stale blob segment files
     #[doc(hidden)]
     pub fn gc_drop_stale(&self) -> crate::Result<u64> {
@@ -229,6 +332,7 @@ impl BlobTree {
 
         let Some(segment) = self.flush_memtable(segment_id, &yanked_memtable, eviction_seqno)?
         else {
+            self.index.release_sealed_memtable(segment_id);
             return Ok(None);
         };
         self.register_segments(&[segment.clone()])?;
@@ -305,6 +409,21 @@ impl AbstractTree for BlobTree {
         self.index.major_compact(target_size, seqno_threshold)
     }
 
+    fn compact_range(
+        &self,
+        key_range: crate::KeyRange,
+        target_level: u8,
+        seqno_threshold: SeqNo,
+    ) -> crate::Result<()> {
+        self.index
+            .compact_range(key_range, target_level, seqno_threshold)
+    }
+
+    fn drop_range(&self, key_range: crate::KeyRange) -> crate::Result<()> {
+        // NOTE: Blobs of dropped items become stale, and are cleaned up by blob GC
+        self.index.drop_range(key_range)
+    }
+
     fn clear_active_memtable(&self) {
         self.index.clear_active_memtable();
     }
@@ -313,6 +432,18 @@ impl AbstractTree for BlobTree {
         self.index.l0_run_count()
     }
 
+    fn active_compactions(&self) -> Vec<crate::compaction::CompactionHandle> {
+        self.index.active_compactions()
+    }
+
+    fn compaction_stats(&self) -> crate::compaction::CompactionStats {
+        self.index.compaction_stats()
+    }
+
+    fn set_compaction_stats(&self, stats: crate::compaction::CompactionStats) {
+        self.index.set_compaction_stats(stats);
+    }
+
     fn blob_file_count(&self) -> usize {
         self.blobs.segment_count()
     }
@@ -339,12 +470,18 @@ impl AbstractTree for BlobTree {
         self.index.sealed_memtable_count()
     }
 
-    /*  #[doc(hidden)]
     fn verify(&self) -> crate::Result<usize> {
-        let index_tree_sum = self.index.verify()?;
-        let vlog_sum = self.blobs.verify()?;
-        Ok(index_tree_sum + vlog_sum)
-    } */
+        let mut corrupted = vec![];
+
+        let index_tree_sum = self.index.verify_segments(&mut corrupted)?;
+        let vlog_sum = self.verify_blob_files(&mut corrupted)?;
+
+        if corrupted.is_empty() {
+            Ok(index_tree_sum + vlog_sum)
+        } else {
+            Err(crate::Error::Corrupted(corrupted))
+        }
+    }
 
     fn keys(
         &self,
@@ -391,11 +528,15 @@ impl AbstractTree for BlobTree {
                 folder: lsm_segment_folder,
             } */
         )?
-        .use_compression(self.index.config.compression);
-
-        /* segment_writer = segment_writer.use_bloom_policy(
-            crate::segment::writer::BloomConstructionPolicy::FpRate(0.0001),
-        ); */
+        .use_compression(self.index.config.level_compression(0))
+        .use_dictionary_size(self.index.config.zstd_dictionary_size)
+        .use_data_block_size(self.index.config.level_data_block_size(0))
+        .use_index_block_size(self.index.config.level_index_block_size(0))
+        .use_rate_limiter(
+            self.index.config.rate_limiter.clone(),
+            crate::IoPriority::High,
+        )
+        .use_bloom_policy(self.index.config.level_bloom_policy(0));
 
         let mut blob_writer = self.blobs.get_writer()?;
 
@@ -413,6 +554,18 @@ impl AbstractTree for BlobTree {
                 continue;
             }
 
+            // NOTE: Expiring values carry their expiry timestamp in front of the encoded value,
+            // so strip it here, and put it back in front of whatever we write into the index tree
+            let expires_at = item.expires_at();
+            let item = item.into_user_value();
+
+            let index_value = |key: &crate::key::InternalKey, bytes: Vec<u8>| match expires_at {
+                Some(expires_at) => {
+                    InternalValue::new_expiring(key.user_key.clone(), bytes, expires_at, key.seqno)
+                }
+                None => InternalValue::new(key.clone(), bytes),
+            };
+
             let mut cursor = Cursor::new(item.value);
 
             let value = MaybeInlineValue::decode_from(&mut cursor)?;
@@ -425,8 +578,7 @@ impl AbstractTree for BlobTree {
                     let mut serialized_indirection = vec![];
                     indirection.encode_into(&mut serialized_indirection)?;
 
-                    segment_writer
-                        .write(InternalValue::new(item.key.clone(), serialized_indirection))?;
+                    segment_writer.write(index_value(&item.key, serialized_indirection))?;
 
                     continue;
                 }
@@ -447,15 +599,14 @@ impl AbstractTree for BlobTree {
                 let mut serialized_indirection = vec![];
                 indirection.encode_into(&mut serialized_indirection)?;
 
-                segment_writer
-                    .write(InternalValue::new(item.key.clone(), serialized_indirection))?;
+                segment_writer.write(index_value(&item.key, serialized_indirection))?;
 
                 blob_writer.write(&item.key.user_key, value)?;
             } else {
                 // TODO: use Slice::with_size
                 let direct = MaybeInlineValue::Inline(value);
                 let serialized_direct = direct.encode_into_vec();
-                segment_writer.write(InternalValue::new(item.key, serialized_direct))?;
+                segment_writer.write(index_value(&item.key, serialized_direct))?;
             }
         }
 
@@ -468,7 +619,11 @@ impl AbstractTree for BlobTree {
         let segment = self.index.consume_writer(segment_id, segment_writer)?;
 
         // TODO: this can probably solved in a nicer way
-        if segment.is_some() {
+        if let Some(segment) = &segment {
+            self.index
+                .compaction_stats
+                .record_flush(segment.metadata.file_size);
+
             // IMPORTANT: Increment the pending count
             // so there cannot be a GC scan now, until the segment is registered
             self.pending_segments
@@ -516,6 +671,16 @@ impl AbstractTree for BlobTree {
         self.index.compact(strategy, seqno_threshold)
     }
 
+    fn compact_with_snapshots(
+        &self,
+        strategy: Arc<dyn crate::compaction::CompactionStrategy>,
+        seqno_threshold: SeqNo,
+        snapshots: &[SeqNo],
+    ) -> crate::Result<()> {
+        self.index
+            .compact_with_snapshots(strategy, seqno_threshold, snapshots)
+    }
+
     fn get_next_segment_id(&self) -> SegmentId {
         self.index.get_next_segment_id()
     }
@@ -617,7 +782,7 @@ impl AbstractTree for BlobTree {
         key: K,
         value: V,
         seqno: SeqNo,
-    ) -> (u64, u64) {
+    ) -> (u64, u64, bool) {
         use value::MaybeInlineValue;
 
         // NOTE: Initially, we always write an inline value
@@ -657,11 +822,29 @@ impl AbstractTree for BlobTree {
         }
     }
 
-    fn remove<K: Into<UserKey>>(&self, key: K, seqno: SeqNo) -> (u64, u64) {
+    fn insert_with_expiry<K: Into<UserKey>, V: Into<UserValue>>(
+        &self,
+        key: K,
+        value: V,
+        expires_at: u64,
+        seqno: SeqNo,
+    ) -> (u64, u64, bool) {
+        use value::MaybeInlineValue;
+
+        // NOTE: The expiry timestamp is prepended to the (inline) value,
+        // so the index tree can hide expired items on its own
+        let item = MaybeInlineValue::Inline(value.into());
+
+        let value = item.encode_into_vec();
+
+        self.index.insert_with_expiry(key, value, expires_at, seqno)
+    }
+
+    fn remove<K: Into<UserKey>>(&self, key: K, seqno: SeqNo) -> (u64, u64, bool) {
         self.index.remove(key, seqno)
     }
 
-    fn remove_weak<K: Into<UserKey>>(&self, key: K, seqno: SeqNo) -> (u64, u64) {
+    fn remove_weak<K: Into<UserKey>>(&self, key: K, seqno: SeqNo) -> (u64, u64, bool) {
         self.index.remove_weak(key, seqno)
     }
 }
@@ -0,0 +1,391 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use super::{cache::MyBlobCache, compression::MyCompressor, value::MaybeInlineValue};
+use crate::{
+    coding::Encode, compaction::stream::DropObserver, memtable::Memtable, HashMap, HashSet,
+    InternalValue, SegmentId, SeqNo,
+};
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, RwLock,
+    },
+};
+use value_log::{SegmentWriter, ValueLog};
+
+/// Value log of a blob tree, as seen by compactions of its index tree
+#[derive(Clone)]
+pub struct ValueLogHandle {
+    pub(crate) blobs: ValueLog<MyBlobCache, MyCompressor>,
+
+    /// Blob tree's count of segments in-flight, which blocks GC scans
+    pub(crate) pending_segments: Arc<AtomicUsize>,
+
+    /// Active memtable of the index tree, which GC scans lock
+    pub(crate) active_memtable: Arc<RwLock<Arc<Memtable>>>,
+
+    /// Last GC scan, replaced by every new scan
+    pub(crate) last_gc_scan: Arc<Mutex<Arc<GcScan>>>,
+}
+
+/// Blob files whose GC stats were computed by a GC scan
+///
+/// A scan counts every version that is not the newest one visible at the scan seqno
+/// as stale, so compactions must not add the versions they drop on top.
+#[derive(Default)]
+pub struct GcScan {
+    /// Snapshot seqno the scan read the index tree at
+    pub(crate) seqno: SeqNo,
+
+    /// Blob files that existed when the scan started
+    pub(crate) blob_file_ids: HashSet<SegmentId>,
+}
+
+impl GcScan {
+    /// Returns `true` if the scan already counted a dropped blob as stale,
+    /// because the next newer version of its key was visible to the scan.
+    fn has_counted(&self, blob_file_id: SegmentId, newer_seqno: Option<SeqNo>) -> bool {
+        self.blob_file_ids.contains(&blob_file_id)
+            && newer_seqno.is_some_and(|newer_seqno| newer_seqno < self.seqno)
+    }
+}
+
+/// Decrements the pending segment count when dropped
+pub struct PendingGuard(Arc<AtomicUsize>);
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Release);
+    }
+}
+
+/// Relocates blobs out of fragmented blob files during a compaction,
+/// and keeps track of the blobs that the compaction made stale
+pub struct BlobRelocation {
+    handle: ValueLogHandle,
+
+    /// Last GC scan when the compaction started
+    gc_scan: Arc<GcScan>,
+
+    /// Blob files that are fragmented enough to move their blobs out of
+    blob_file_ids: HashSet<SegmentId>,
+
+    /// Stale items and bytes per blob file, caused by this compaction
+    garbage: Mutex<HashMap<SegmentId, (u64, u64)>>,
+
+    /// Finished writers of relocated blobs
+    writers: Mutex<Vec<SegmentWriter<MyCompressor>>>,
+
+    /// Blob files created by the writers, so they can be deleted if the compaction fails
+    blob_file_paths: Mutex<HashSet<PathBuf>>,
+}
+
+impl BlobRelocation {
+    /// Selects the blob files with a stale ratio of at least `threshold` for relocation.
+    pub fn new(handle: ValueLogHandle, threshold: f32) -> Self {
+        let blob_file_ids = handle
+            .blobs
+            .manifest
+            .list_segments()
+            .into_iter()
+            .filter(|blob_file| !blob_file.is_stale() && blob_file.stale_ratio() >= threshold)
+            .map(|blob_file| blob_file.id)
+            .collect();
+
+        let gc_scan = handle
+            .last_gc_scan
+            .lock()
+            .expect("lock is poisoned")
+            .clone();
+
+        Self {
+            handle,
+            gc_scan,
+            blob_file_ids,
+            garbage: Mutex::default(),
+            writers: Mutex::default(),
+            blob_file_paths: Mutex::default(),
+        }
+    }
+
+    /// Remembers the blob file the writer currently writes into.
+    fn track_blob_file(&self, writer: &SegmentWriter<MyCompressor>) {
+        let path = &writer.get_active_writer().path;
+        let mut blob_file_paths = self.blob_file_paths.lock().expect("lock is poisoned");
+
+        if !blob_file_paths.contains(path) {
+            blob_file_paths.insert(path.clone());
+        }
+    }
+
+    fn add_garbage(&self, blob_file_id: SegmentId, bytes: u32) {
+        let mut garbage = self.garbage.lock().expect("lock is poisoned");
+        let (items, total_bytes) = garbage.entry(blob_file_id).or_default();
+        *items += 1;
+        *total_bytes += u64::from(bytes);
+        drop(garbage);
+    }
+
+    /// Copies the blob of an item into `writer`, if the blob is stored in a fragmented blob file.
+    ///
+    /// Returns the item, pointing to the new location of its blob.
+    pub fn relocate(
+        &self,
+        writer: &mut Option<SegmentWriter<MyCompressor>>,
+        item: InternalValue,
+    ) -> crate::Result<InternalValue> {
+        if self.blob_file_ids.is_empty() || item.is_tombstone() {
+            return Ok(item);
+        }
+
+        let expires_at = item.expires_at();
+        let user_value = item.clone().into_user_value().value;
+
+        let MaybeInlineValue::Indirect { vhandle, size } =
+            MaybeInlineValue::from_slice(&user_value)?
+        else {
+            return Ok(item);
+        };
+
+        if !self.blob_file_ids.contains(&vhandle.segment_id) {
+            return Ok(item);
+        }
+
+        let Some(value) = self.handle.blobs.get(&vhandle)? else {
+            log::warn!("Value handle {vhandle:?} did not match any blob, not relocating it");
+            return Ok(item);
+        };
+
+        let writer = match writer {
+            Some(writer) => writer,
+            None => writer.insert(self.handle.blobs.get_writer()?),
+        };
+        self.track_blob_file(writer);
+
+        let new_vhandle = writer.get_next_value_handle();
+        writer.write(&item.key.user_key, &value)?;
+
+        // NOTE: The writer may have rotated to a new blob file
+        self.track_blob_file(writer);
+
+        self.add_garbage(vhandle.segment_id, size);
+
+        let indirection = MaybeInlineValue::Indirect {
+            vhandle: new_vhandle,
+            size,
+        }
+        .encode_into_vec();
+
+        Ok(match expires_at {
+            Some(expires_at) => InternalValue::new_expiring(
+                item.key.user_key,
+                indirection,
+                expires_at,
+                item.key.seqno,
+            ),
+            None => InternalValue::new(item.key, indirection),
+        })
+    }
+
+    /// Keeps the writer of a finished run, until the compaction is committed.
+    pub fn add_writer(&self, writer: SegmentWriter<MyCompressor>) {
+        self.writers.lock().expect("lock is poisoned").push(writer);
+    }
+
+    /// Deletes the blob files of relocated blobs that have not been registered
+    /// in the value log, after the compaction was stopped or failed.
+    ///
+    /// The writers of all runs need to be dropped (or added) before.
+    pub fn abort(&self) {
+        self.writers.lock().expect("lock is poisoned").clear();
+
+        let blob_file_paths =
+            std::mem::take(&mut *self.blob_file_paths.lock().expect("lock is poisoned"));
+
+        for path in blob_file_paths {
+            let is_registered = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.parse::<SegmentId>().ok())
+                .is_some_and(|id| self.handle.blobs.manifest.get_segment(id).is_some());
+
+            // NOTE: Registered blob files are owned by the value log (and cleaned up by GC)
+            if is_registered {
+                continue;
+            }
+
+            if let Err(e) = std::fs::remove_file(&path) {
+                log::warn!("Failed to cleanup blob file at {}: {e:?}", path.display());
+            }
+        }
+    }
+
+    /// Registers the blob files of relocated blobs in the value log.
+    ///
+    /// The returned guard blocks GC scans until the index segments
+    /// pointing to the new blob files are committed.
+    pub fn register_blob_files(&self) -> crate::Result<Option<PendingGuard>> {
+        let writers = std::mem::take(&mut *self.writers.lock().expect("lock is poisoned"));
+
+        if writers.is_empty() {
+            return Ok(None);
+        }
+
+        // IMPORTANT: Lock memtable, so a running GC scan
+        // does not see blob files that are not referenced yet
+        let _memtable_lock = self
+            .handle
+            .active_memtable
+            .write()
+            .expect("lock is poisoned");
+
+        self.handle.pending_segments.fetch_add(1, Ordering::Release);
+        let guard = PendingGuard(self.handle.pending_segments.clone());
+
+        for writer in writers {
+            self.handle.blobs.register_writer(writer)?;
+        }
+
+        Ok(Some(guard))
+    }
+
+    /// Adds the blobs made stale by this compaction to the GC stats of their blob files.
+    ///
+    /// If a GC scan ran while the compaction was running, the blob files of that scan
+    /// are skipped, because the scan may have counted the same blobs already;
+    /// the next scan picks up the rest.
+    ///
+    /// Needs to be called while holding the levels manifest write lock,
+    /// right after committing the compaction, so concurrent compactions
+    /// and GC scans do not lose updates.
+    pub fn apply_garbage(&self) {
+        let garbage = std::mem::take(&mut *self.garbage.lock().expect("lock is poisoned"));
+
+        let last_gc_scan = self
+            .handle
+            .last_gc_scan
+            .lock()
+            .expect("lock is poisoned")
+            .clone();
+
+        let is_rescanned = !Arc::ptr_eq(&last_gc_scan, &self.gc_scan);
+
+        for (blob_file_id, (items, bytes)) in garbage {
+            if is_rescanned && last_gc_scan.blob_file_ids.contains(&blob_file_id) {
+                continue;
+            }
+
+            // NOTE: Blob file may have been dropped by GC in the meantime
+            let Some(blob_file) = self.handle.blobs.manifest.get_segment(blob_file_id) else {
+                continue;
+            };
+
+            let gc_stats = &blob_file.gc_stats;
+
+            gc_stats
+                .set_stale_items((gc_stats.stale_items() + items).min(blob_file.meta.item_count));
+            gc_stats.set_stale_bytes(
+                (gc_stats.stale_bytes() + bytes).min(blob_file.meta.total_uncompressed_bytes),
+            );
+        }
+    }
+}
+
+impl DropObserver for BlobRelocation {
+    fn on_drop(&self, item: &InternalValue, newer_seqno: Option<SeqNo>) {
+        if item.is_tombstone() {
+            return;
+        }
+
+        let user_value = item.clone().into_user_value().value;
+
+        if let Ok(MaybeInlineValue::Indirect { vhandle, size }) =
+            MaybeInlineValue::from_slice(&user_value)
+        {
+            if !self.gc_scan.has_counted(vhandle.segment_id, newer_seqno) {
+                self.add_garbage(vhandle.segment_id, size);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AbstractTree, Config, SequenceNumberCounter};
+    use test_log::test;
+
+    #[test]
+    fn relocation_after_gc_scan() -> crate::Result<()> {
+        let folder = tempfile::tempdir()?;
+
+        let big_value = b"neptune!".repeat(128_000);
+        let new_value = b"saturn!".repeat(128_000);
+
+        let tree = Config::new(&folder)
+            .blob_file_relocation_threshold(0.9)
+            .open_as_blob_tree()?;
+
+        let seqno = SequenceNumberCounter::default();
+
+        for key in ["a", "b", "c", "d"] {
+            tree.insert(key, &big_value, seqno.next());
+        }
+        tree.flush_active_memtable(0)?;
+
+        for key in ["a", "b"] {
+            tree.insert(key, &new_value, seqno.next());
+        }
+        tree.flush_active_memtable(0)?;
+
+        // NOTE: Simulate a GC scan, which counts the old versions of "a" and "b" as stale
+        let blob_file_ids = tree.blobs.manifest.list_segment_ids();
+        let first_blob_file = tree
+            .blobs
+            .manifest
+            .get_segment(blob_file_ids[0])
+            .expect("should exist");
+        first_blob_file.gc_stats.set_stale_items(2);
+        first_blob_file
+            .gc_stats
+            .set_stale_bytes(2 * big_value.len() as u64);
+
+        *tree
+            .index
+            .value_log
+            .get()
+            .expect("should exist")
+            .last_gc_scan
+            .lock()
+            .expect("lock is poisoned") = Arc::new(GcScan {
+            seqno: seqno.get(),
+            blob_file_ids: blob_file_ids.into_iter().collect(),
+        });
+
+        // NOTE: Dropping the old versions must not count them again
+        tree.major_compact(u64::MAX, SeqNo::MAX)?;
+        assert_eq!(2, first_blob_file.gc_stats.stale_items());
+        assert_eq!(0, tree.gc_drop_stale()?);
+        assert_eq!(2, tree.blob_file_count());
+
+        // NOTE: Versions that are dropped because of writes after the scan still count
+        for key in ["c", "d"] {
+            tree.insert(key, &new_value, seqno.next());
+        }
+        tree.flush_active_memtable(0)?;
+
+        tree.major_compact(u64::MAX, SeqNo::MAX)?;
+        assert_eq!(4, first_blob_file.gc_stats.stale_items());
+        assert!(tree.gc_drop_stale()? > 0);
+        assert_eq!(2, tree.blob_file_count());
+
+        for key in ["a", "b", "c", "d"] {
+            assert_eq!(&*tree.get(key, None)?.expect("should exist"), new_value);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,216 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use super::{Choice, CompactionStrategy, Input as CompactionInput};
+use crate::{
+    config::Config, level_manifest::LevelManifest, segment::Segment, HashSet, KeyRange, SegmentId,
+};
+
+/// Compacts all segments overlapping a key range down to a target level
+///
+/// Each call of `choose` pushes the overlapping segments of the highest level
+/// (above the target level) into the next level.
+pub struct Strategy {
+    key_range: KeyRange,
+    target_level: u8,
+
+    /// The target segment size as disk (possibly compressed).
+    ///
+    /// Default = 64 MiB
+    pub target_size: u64,
+}
+
+impl Strategy {
+    /// Configures a new `CompactRange` compaction strategy.
+    ///
+    /// Both bounds of the key range are inclusive.
+    #[must_use]
+    pub fn new(key_range: KeyRange, target_level: u8) -> Self {
+        Self {
+            key_range,
+            target_level,
+            target_size: /* 64 Mib */ 64 * 1_024 * 1_024,
+        }
+    }
+
+    /// Returns the level the key range is compacted into.
+    pub fn target_level(&self) -> u8 {
+        self.target_level
+    }
+
+    /// Collects the segments that need to be merged to push the key range
+    /// from `level_idx` into the next level.
+    ///
+    /// Merging segments may widen the key range, so all segments of both levels
+    /// overlapping the widened key range are pulled in as well, until the key range is stable.
+    /// Otherwise an older version of a key could be left above its newer version.
+    ///
+    /// Returns an empty set if the level does not overlap the key range.
+    pub fn pick(&self, levels: &LevelManifest, level_idx: u8) -> HashSet<SegmentId> {
+        let (Some(curr_level), Some(next_level)) = (
+            levels.levels.get(level_idx as usize),
+            levels.levels.get(level_idx as usize + 1),
+        ) else {
+            return HashSet::default();
+        };
+
+        if curr_level
+            .overlapping_segments(&self.key_range)
+            .next()
+            .is_none()
+        {
+            return HashSet::default();
+        }
+
+        let mut key_range = self.key_range.clone();
+
+        loop {
+            let segments: Vec<&Segment> = curr_level
+                .overlapping_segments(&key_range)
+                .chain(next_level.overlapping_segments(&key_range))
+                .collect();
+
+            let next_key_range = KeyRange::aggregate(
+                std::iter::once(&key_range).chain(segments.iter().map(|x| &x.metadata.key_range)),
+            );
+
+            if next_key_range == key_range {
+                return segments.into_iter().map(Segment::id).collect();
+            }
+
+            key_range = next_key_range;
+        }
+    }
+
+    /// Builds the choice to push the picked segments from `level_idx` into the next level.
+    ///
+    /// If the segments do not overlap the next level, they are trivially moved,
+    /// unless the next level is the target level, where the key range is always rewritten.
+    pub fn choice_for_level(
+        &self,
+        levels: &LevelManifest,
+        level_idx: u8,
+        segment_ids: HashSet<SegmentId>,
+    ) -> Choice {
+        let dest_level = level_idx + 1;
+
+        let can_trivial_move = dest_level < self.target_level
+            && levels.levels.get(level_idx as usize).is_some_and(|level| {
+                let segments: Vec<_> = level
+                    .segments
+                    .iter()
+                    .filter(|x| segment_ids.contains(&x.id()))
+                    .collect();
+
+                segments.len() == segment_ids.len()
+                    && KeyRange::is_disjoint(
+                        &segments
+                            .iter()
+                            .map(|x| &x.metadata.key_range)
+                            .collect::<Vec<_>>(),
+                    )
+            });
+
+        let input = CompactionInput {
+            segment_ids,
+            dest_level,
+            target_size: self.target_size,
+        };
+
+        if can_trivial_move {
+            Choice::Move(input)
+        } else {
+            Choice::Merge(input)
+        }
+    }
+}
+
+impl CompactionStrategy for Strategy {
+    fn get_name(&self) -> &'static str {
+        "CompactRangeCompaction"
+    }
+
+    fn choose(&self, levels: &LevelManifest, _: &Config) -> Choice {
+        for level_idx in 0..self.target_level {
+            let segment_ids = self.pick(levels, level_idx);
+
+            if segment_ids.is_empty() {
+                continue;
+            }
+
+            if levels.hidden_set().is_blocked(segment_ids.iter().copied()) {
+                // IMPORTANT: Compaction is blocked because of other
+                // on-going compaction
+                return Choice::DoNothing;
+            }
+
+            return self.choice_for_level(levels, level_idx, segment_ids);
+        }
+
+        Choice::DoNothing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AbstractTree, Config, SeqNo};
+    use std::sync::Arc;
+    use test_log::test;
+
+    fn key_range(min: &str, max: &str) -> KeyRange {
+        KeyRange::new((min.as_bytes().into(), max.as_bytes().into()))
+    }
+
+    #[test]
+    fn compact_range_pick() -> crate::Result<()> {
+        let folder = tempfile::tempdir()?;
+        let tree = Config::new(&folder).open()?;
+
+        tree.insert("c", "c", 0);
+        tree.insert("f", "f", 0);
+        tree.flush_active_memtable(0)?;
+        tree.compact(Arc::new(crate::compaction::MoveDown(0, 1)), SeqNo::MAX)?;
+
+        tree.insert("a", "a", 1);
+        tree.insert("b", "b", 1);
+        tree.flush_active_memtable(0)?;
+
+        tree.insert("b", "b", 2);
+        tree.insert("d", "d", 2);
+        tree.flush_active_memtable(0)?;
+
+        tree.insert("x", "x", 3);
+        tree.insert("y", "y", 3);
+        tree.flush_active_memtable(0)?;
+
+        let levels = tree.levels.read().expect("lock is poisoned");
+
+        // NOTE: Segment 1 overlaps the key range, which pulls in segment 2 (because of "b"),
+        // which pulls in segment 0 in L1 (because of "d")
+        let strategy = Strategy::new(key_range("a", "a"), 1);
+        assert_eq!(
+            [0, 1, 2].into_iter().collect::<HashSet<_>>(),
+            strategy.pick(&levels, 0),
+        );
+        assert_eq!(
+            Choice::Merge(CompactionInput {
+                segment_ids: [0, 1, 2].into_iter().collect(),
+                dest_level: 1,
+                target_size: 64 * 1_024 * 1_024,
+            }),
+            strategy.choose(&levels, &Config::default()),
+        );
+
+        // NOTE: L0 does not overlap, so there is nothing to push down
+        let strategy = Strategy::new(key_range("e", "f"), 1);
+        assert!(strategy.pick(&levels, 0).is_empty());
+        assert_eq!(
+            Choice::DoNothing,
+            strategy.choose(&levels, &Config::default()),
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use super::{Choice, CompactionStrategy};
+use crate::{
+    config::Config,
+    level_manifest::{level::Level, LevelManifest},
+    segment::Segment,
+    HashSet, KeyRange, SegmentId,
+};
+
+/// Drops segments that are fully contained in a key range
+///
+/// Segments that only partially overlap the key range are not touched,
+/// those need to be rewritten by the compactor.
+pub struct Strategy {
+    key_range: KeyRange,
+}
+
+impl Strategy {
+    /// Configures a new `DropRange` compaction strategy.
+    ///
+    /// Both bounds of the key range are inclusive.
+    #[must_use]
+    pub fn new(key_range: KeyRange) -> Self {
+        Self { key_range }
+    }
+
+    /// Returns the key range to drop.
+    pub fn key_range(&self) -> &KeyRange {
+        &self.key_range
+    }
+
+    /// Returns the segments of a level that overlap the key range,
+    /// but are not fully contained in it.
+    pub fn partially_overlapping(&self, level: &Level) -> Vec<SegmentId> {
+        level
+            .overlapping_segments(&self.key_range)
+            .filter(|x| !self.key_range.contains_range(&x.metadata.key_range))
+            .map(Segment::id)
+            .collect()
+    }
+
+    /// Chooses the segments of a single level that can be dropped.
+    pub fn choose_level(&self, levels: &LevelManifest, level_idx: u8) -> Choice {
+        let Some(level) = levels.levels.get(level_idx as usize) else {
+            return Choice::DoNothing;
+        };
+
+        Self::choose_segments(levels, level.contained_segments(&self.key_range))
+    }
+
+    fn choose_segments<'a>(
+        levels: &LevelManifest,
+        segments: impl Iterator<Item = &'a Segment>,
+    ) -> Choice {
+        let segment_ids: HashSet<_> = segments.map(Segment::id).collect();
+
+        // NOTE: This should generally not occur because of the
+        // tree-level major compaction lock
+        // But just as a fail-safe...
+        let some_hidden = segment_ids
+            .iter()
+            .any(|&id| levels.hidden_set().is_hidden(id));
+
+        if some_hidden || segment_ids.is_empty() {
+            Choice::DoNothing
+        } else {
+            Choice::Drop(segment_ids)
+        }
+    }
+}
+
+impl CompactionStrategy for Strategy {
+    fn get_name(&self) -> &'static str {
+        "DropRangeCompaction"
+    }
+
+    fn choose(&self, levels: &LevelManifest, _: &Config) -> Choice {
+        Self::choose_segments(
+            levels,
+            levels
+                .levels
+                .iter()
+                .flat_map(|level| level.contained_segments(&self.key_range)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AbstractTree, Config};
+    use test_log::test;
+
+    fn key_range(min: &str, max: &str) -> KeyRange {
+        KeyRange::new((min.as_bytes().into(), max.as_bytes().into()))
+    }
+
+    #[test]
+    fn drop_range_choose_contained() -> crate::Result<()> {
+        let folder = tempfile::tempdir()?;
+        let tree = Config::new(&folder).open()?;
+
+        tree.insert("a", "a", 0);
+        tree.insert("b", "b", 0);
+        tree.flush_active_memtable(0)?;
+
+        tree.insert("c", "c", 1);
+        tree.insert("d", "d", 1);
+        tree.flush_active_memtable(0)?;
+
+        tree.insert("d", "d", 2);
+        tree.insert("z", "z", 2);
+        tree.flush_active_memtable(0)?;
+
+        let levels = tree.levels.read().expect("lock is poisoned");
+
+        let strategy = Strategy::new(key_range("a", "d"));
+        assert_eq!(
+            Choice::Drop([0, 1].into_iter().collect()),
+            strategy.choose(&levels, &Config::default()),
+        );
+        assert_eq!(
+            vec![2],
+            strategy.partially_overlapping(levels.levels.first().expect("should exist")),
+        );
+
+        let strategy = Strategy::new(key_range("e", "y"));
+        assert_eq!(
+            Choice::DoNothing,
+            strategy.choose(&levels, &Config::default())
+        );
+
+        Ok(())
+    }
+}
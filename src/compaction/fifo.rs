@@ -55,13 +55,15 @@ impl CompactionStrategy for Strategy {
 
         if let Some(ttl_seconds) = self.ttl_seconds {
             if ttl_seconds > 0 {
-                let now = unix_timestamp().as_micros();
+                let now = unix_timestamp().as_nanos();
+                let ttl_nanos = u128::from(ttl_seconds) * 1_000_000_000;
 
                 for segment in resolved_view.iter().flat_map(|lvl| &lvl.segments) {
-                    let lifetime_us: u128 = /* now - segment.metadata.created_at */ todo!();
-                    let lifetime_sec = lifetime_us / 1000 / 1000;
+                    // NOTE: Use the age of the newest data instead of the file's creation time,
+                    // so segments rewritten by compaction do not get a fresh lease
+                    let lifetime_nanos = now.saturating_sub(*segment.metadata.data_created_at);
 
-                    if lifetime_sec > ttl_seconds.into() {
+                    if lifetime_nanos > ttl_nanos {
                         log::warn!("segment is older than configured TTL: {:?}", segment.id(),);
                         segment_ids_to_delete.insert(segment.id());
                     }
@@ -0,0 +1,56 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use crate::UserValue;
+
+/// Decision of a [`CompactionFilter`] for a single item
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Verdict {
+    /// Keeps the item as-is.
+    Keep,
+
+    /// Removes the item.
+    ///
+    /// The item is replaced by a tombstone, so older versions
+    /// of the key cannot be resurrected.
+    Remove,
+
+    /// Replaces the item's value.
+    ChangeValue(UserValue),
+}
+
+/// User-defined hook to drop or rewrite items during compaction
+///
+/// The filter is invoked for every value that survives garbage collection
+/// while segments are compacted (not when memtables are flushed).
+///
+/// To never alter what a snapshot can read, the filter is only invoked
+/// for items that are older than the MVCC eviction seqno (the oldest snapshot).
+/// Tombstones are never passed into the filter.
+///
+/// The filter is not applied in key-value separated trees ([`crate::BlobTree`]).
+///
+/// # Examples
+///
+/// ```
+/// use lsm_tree::compaction::{CompactionFilter, FilterVerdict};
+///
+/// /// Removes all items of deleted tenants
+/// struct DropTenant(Vec<u8>);
+///
+/// impl CompactionFilter for DropTenant {
+///     fn filter(&self, key: &[u8], _value: &[u8]) -> FilterVerdict {
+///         if key.starts_with(&self.0) {
+///             FilterVerdict::Remove
+///         } else {
+///             FilterVerdict::Keep
+///         }
+///     }
+/// }
+/// ```
+#[allow(clippy::module_name_repetitions)]
+pub trait CompactionFilter: Send + Sync {
+    /// Decides what to do with an item.
+    fn filter(&self, key: &[u8], value: &[u8]) -> Verdict;
+}
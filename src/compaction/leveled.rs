@@ -7,6 +7,7 @@ use crate::{
     config::Config,
     level_manifest::{hidden_set::HiddenSet, level::Level, LevelManifest},
     segment::Segment,
+    time::unix_timestamp,
     windows::{GrowingWindowsExt, ShrinkingWindowsExt},
     HashSet, KeyRange, SegmentId,
 };
@@ -174,6 +175,29 @@ pub struct Strategy {
     /// A level target size is: max_memtable_size * level_ratio.pow(#level + 1).
     #[allow(clippy::doc_markdown)]
     pub level_ratio: u8,
+
+    /// If `true`, level target sizes are computed backwards from the size of the last level,
+    /// instead of growing from a fixed base size.
+    ///
+    /// The level L0 is merged into (base level) is the highest level whose target size
+    /// does not exceed the base size; levels above the base level stay empty.
+    /// This keeps the space amplification low, even when the tree is small.
+    ///
+    /// Default = false
+    ///
+    /// Same as `level_compaction_dynamic_level_bytes` in `RocksDB`.
+    pub dynamic_level_bytes: bool,
+
+    /// Segments (in L1+) that are older than this are compacted, even if no level
+    /// is over its target size, so stale versions and expired items in cold key ranges
+    /// are eventually garbage-collected, will be disabled if 0 or None.
+    ///
+    /// Segments are pushed into the next level, segments in the last level are rewritten in-place.
+    ///
+    /// Default = None
+    ///
+    /// Same as `periodic_compaction_seconds` in `RocksDB`.
+    pub periodic_compaction_seconds: Option<u64>,
 }
 
 impl Default for Strategy {
@@ -182,6 +206,8 @@ impl Default for Strategy {
             l0_threshold: 4,
             target_size:/* 64 Mib */ 64 * 1_024 * 1_024,
             level_ratio: 10,
+            dynamic_level_bytes: false,
+            periodic_compaction_seconds: None,
         }
     }
 }
@@ -206,6 +232,195 @@ impl Strategy {
     fn level_base_size(&self) -> u64 {
         u64::from(self.target_size) * u64::from(self.l0_threshold)
     }
+
+    /// Calculates the base level (the level L0 is merged into),
+    /// and the target size of every level.
+    ///
+    /// Levels above the base level have no target size, and are skipped.
+    fn level_targets(&self, levels: &LevelManifest) -> (u8, Vec<u64>) {
+        let last_level_index = levels.last_level_index();
+
+        if !self.dynamic_level_bytes {
+            let targets = (0..=last_level_index)
+                .map(|idx| {
+                    if idx == 0 {
+                        0
+                    } else {
+                        self.level_target_size(idx)
+                    }
+                })
+                .collect();
+
+            return (1, targets);
+        }
+
+        let level_base_size = self.level_base_size();
+
+        // NOTE: Use the largest level, so data in upper levels (e.g. after
+        // switching from static level sizes) is not unexpectedly small
+        let max_level_size = levels
+            .levels
+            .iter()
+            .skip(1)
+            .map(|level| level.size())
+            .max()
+            .unwrap_or_default();
+
+        // IMPORTANT: The base level cannot be below a non-empty level,
+        // otherwise data in that level would never be compacted
+        //
+        // NOTE: Level count is 255 max
+        #[allow(clippy::cast_possible_truncation)]
+        let first_non_empty_level = levels
+            .levels
+            .iter()
+            .enumerate()
+            .skip(1)
+            .find(|(_, level)| !level.is_empty())
+            .map_or(last_level_index, |(idx, _)| idx as u8);
+
+        let mut targets = vec![0; levels.levels.len()];
+        let mut target = max_level_size.max(level_base_size);
+        let mut base_level = last_level_index;
+
+        if let Some(x) = targets.get_mut(base_level as usize) {
+            *x = target;
+        }
+
+        while base_level > 1 && (target > level_base_size || base_level > first_non_empty_level) {
+            target /= u64::from(self.level_ratio);
+            base_level -= 1;
+
+            if let Some(x) = targets.get_mut(base_level as usize) {
+                *x = target;
+            }
+        }
+
+        (base_level.max(1), targets)
+    }
+
+    /// Picks a group of overlapping segments in a non-disjoint level (L1+)
+    /// and rewrites it in-place, so the level becomes disjoint again.
+    ///
+    /// L1+ should always be disjoint, but crashes or bad ingests may leave
+    /// overlapping segments behind, which disables the binary search in point reads.
+    fn choose_repair(&self, levels: &LevelManifest) -> Option<Choice> {
+        for (level_idx, level) in levels.levels.iter().enumerate().skip(1) {
+            if level.is_disjoint {
+                continue;
+            }
+
+            let mut segments = level.segments.clone();
+            segments.sort_by(|a, b| a.metadata.key_range.min().cmp(b.metadata.key_range.min()));
+
+            // NOTE: Split the level into runs of transitively overlapping segments,
+            // every run can be repaired independently
+            let mut runs: Vec<Vec<Segment>> = vec![];
+
+            for segment in segments {
+                match runs.last_mut() {
+                    Some(run)
+                        if run.iter().any(|x| {
+                            x.metadata.key_range.max() >= segment.metadata.key_range.min()
+                        }) =>
+                    {
+                        run.push(segment);
+                    }
+                    _ => runs.push(vec![segment]),
+                }
+            }
+
+            for run in runs.into_iter().filter(|x| x.len() > 1) {
+                if levels.hidden_set().is_blocked(run.iter().map(Segment::id)) {
+                    // IMPORTANT: Compaction is blocked because of other
+                    // on-going compaction
+                    continue;
+                }
+
+                let segment_ids: HashSet<_> = run.iter().map(Segment::id).collect();
+
+                log::debug!("L{level_idx} is not disjoint, repairing by rewriting {segment_ids:?}");
+
+                // NOTE: Level count is 255 max
+                #[allow(clippy::cast_possible_truncation)]
+                let dest_level = level_idx as u8;
+
+                return Some(Choice::Merge(CompactionInput {
+                    segment_ids,
+                    dest_level,
+                    target_size: u64::from(self.target_size),
+                }));
+            }
+        }
+
+        None
+    }
+
+    /// Picks the oldest segment (in L1+) that has exceeded the periodic compaction age.
+    fn choose_periodic(&self, levels: &LevelManifest) -> Choice {
+        let Some(age_seconds) = self.periodic_compaction_seconds.filter(|&x| x > 0) else {
+            return Choice::DoNothing;
+        };
+
+        let now = unix_timestamp().as_nanos();
+        let age_nanos = u128::from(age_seconds) * 1_000_000_000;
+        let last_level_index = levels.last_level_index();
+
+        // NOTE: L0 is compacted by the normal triggers anyway
+        //
+        // NOTE: Level count is 255 max
+        #[allow(clippy::cast_possible_truncation)]
+        let Some((level_idx, segment)) = levels
+            .levels
+            .iter()
+            .enumerate()
+            .skip(1)
+            // NOTE: In non-disjoint levels, we cannot know which segments
+            // need to be compacted together to not reorder versions
+            .filter(|(_, level)| level.is_disjoint)
+            .flat_map(|(idx, level)| level.segments.iter().map(move |x| (idx as u8, x)))
+            .filter(|(_, x)| !levels.hidden_set().is_hidden(x.id()))
+            .filter(|(_, x)| now.saturating_sub(*x.metadata.created_at) > age_nanos)
+            .min_by_key(|(_, x)| *x.metadata.created_at)
+        else {
+            return Choice::DoNothing;
+        };
+
+        let mut segment_ids: HashSet<_> = std::iter::once(segment.id()).collect();
+
+        let dest_level = if level_idx == last_level_index {
+            level_idx
+        } else {
+            let next_level_index = level_idx + 1;
+
+            if let Some(next_level) = levels.levels.get(next_level_index as usize) {
+                segment_ids.extend(
+                    next_level
+                        .overlapping_segments(&segment.metadata.key_range)
+                        .map(Segment::id),
+                );
+            }
+
+            next_level_index
+        };
+
+        if levels.hidden_set().is_blocked(segment_ids.iter().copied()) {
+            // IMPORTANT: Compaction is blocked because of other
+            // on-going compaction
+            return Choice::DoNothing;
+        }
+
+        log::debug!(
+            "Segment {} in L{level_idx} exceeded periodic compaction age, compacting {segment_ids:?} into L{dest_level}",
+            segment.id(),
+        );
+
+        Choice::Merge(CompactionInput {
+            segment_ids,
+            dest_level,
+            target_size: u64::from(self.target_size),
+        })
+    }
 }
 
 impl CompactionStrategy for Strategy {
@@ -217,11 +432,12 @@ impl CompactionStrategy for Strategy {
     fn choose(&self, levels: &LevelManifest, _: &Config) -> Choice {
         let view = &levels.levels;
 
-        // TODO: look at L1+, if not disjoint
-        // TODO: try to repairing level by rewriting
-        // TODO: abort if any segment is hidden
-        // TODO: then make sure, non-disjoint levels cannot be used in subsequent code below
-        // TODO: add tests
+        let (base_level, level_targets) = self.level_targets(levels);
+
+        // Repair non-disjoint levels
+        if let Some(choice) = self.choose_repair(levels) {
+            return choice;
+        }
 
         // L1+ compactions
         for (curr_level_index, level) in view.iter().enumerate().skip(1).take(view.len() - 2).rev()
@@ -232,7 +448,8 @@ impl CompactionStrategy for Strategy {
 
             let next_level_index = curr_level_index + 1;
 
-            if level.is_empty() {
+            // NOTE: Levels above the base level are skipped
+            if level.is_empty() || curr_level_index < base_level {
                 continue;
             }
 
@@ -245,7 +462,10 @@ impl CompactionStrategy for Strategy {
                 .map(|x| x.metadata.file_size)
                 .sum();
 
-            let desired_bytes = self.level_target_size(curr_level_index);
+            let desired_bytes = level_targets
+                .get(curr_level_index as usize)
+                .copied()
+                .unwrap_or_default();
 
             let overshoot = level_size.saturating_sub(desired_bytes);
 
@@ -337,11 +557,11 @@ impl CompactionStrategy for Strategy {
                     });
                 }
 
-                if !busy_levels.contains(&1) {
+                if !busy_levels.contains(&base_level) {
                     let mut level = (**first_level).clone();
                     level.sort_by_key_range();
 
-                    let Some(next_level) = &view.get(1) else {
+                    let Some(next_level) = &view.get(base_level as usize) else {
                         return Choice::DoNothing;
                     };
 
@@ -360,7 +580,7 @@ impl CompactionStrategy for Strategy {
 
                     let choice = CompactionInput {
                         segment_ids,
-                        dest_level: 1,
+                        dest_level: base_level,
                         target_size: u64::from(self.target_size),
                     };
 
@@ -372,7 +592,8 @@ impl CompactionStrategy for Strategy {
             }
         }
 
-        Choice::DoNothing
+        // Periodic compactions
+        self.choose_periodic(levels)
     }
 }
 /*
@@ -4,19 +4,35 @@
 
 //! Contains compaction strategies
 
+pub(crate) mod compact_range;
+pub(crate) mod drop_range;
 pub(crate) mod fifo;
+pub(crate) mod filter;
 pub(crate) mod leveled;
 pub(crate) mod maintenance;
 pub(crate) mod major;
 pub(crate) mod movedown;
+pub(crate) mod partitioner;
+pub(crate) mod progress;
 pub(crate) mod pulldown;
+pub(crate) mod stats;
 pub(crate) mod stream;
 pub(crate) mod tiered;
+pub(crate) mod time_window;
+pub(crate) mod tombstone_density;
+pub(crate) mod universal;
 pub(crate) mod worker;
 
 pub use fifo::Strategy as Fifo;
+pub use filter::{CompactionFilter, Verdict as FilterVerdict};
 pub use leveled::Strategy as Leveled;
+pub use partitioner::SegmentPartitioner;
+pub use progress::CompactionHandle;
+pub use stats::{CompactionStats, LevelStats};
 pub use tiered::Strategy as SizeTiered;
+pub use time_window::{Strategy as TimeWindow, TimestampExtractor};
+pub use tombstone_density::Strategy as TombstoneDensity;
+pub use universal::Strategy as Universal;
 
 use crate::{config::Config, level_manifest::LevelManifest, HashSet, SegmentId};
 
@@ -0,0 +1,38 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+/// User-defined hook to cut compaction output into segments at key boundaries
+///
+/// Between every two consecutive keys, compactions (and bulk ingestion) consult the partitioner,
+/// and start a new segment if it asks for a boundary, no matter how
+/// small the current segment is.
+/// Segments are still rotated once they reach their target size.
+///
+/// Segments written by memtable flushes are not partitioned.
+///
+/// # Examples
+///
+/// ```
+/// use lsm_tree::compaction::SegmentPartitioner;
+///
+/// /// Keeps every tenant (`tenant/...`) in its own segments
+/// struct TenantPartitioner;
+///
+/// fn tenant(key: &[u8]) -> &[u8] {
+///     let end = key.iter().position(|&x| x == b'/').unwrap_or(key.len());
+///     &key[..end]
+/// }
+///
+/// impl SegmentPartitioner for TenantPartitioner {
+///     fn should_partition(&self, prev: &[u8], next: &[u8]) -> bool {
+///         tenant(prev) != tenant(next)
+///     }
+/// }
+/// ```
+pub trait SegmentPartitioner: Send + Sync {
+    /// Returns `true` if `prev` and `next` should be written into different segments.
+    ///
+    /// `prev` is always smaller than `next`.
+    fn should_partition(&self, prev: &[u8], next: &[u8]) -> bool;
+}
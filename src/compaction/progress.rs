@@ -0,0 +1,216 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use crate::{HashSet, SegmentId};
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+struct Inner {
+    segment_ids: HashSet<SegmentId>,
+    dest_level: u8,
+    input_bytes: u64,
+    input_items: u64,
+    items_processed: AtomicU64,
+    segments_written: AtomicUsize,
+    tombstones_dropped: AtomicU64,
+    versions_dropped: AtomicU64,
+    start: Instant,
+    cancelled: AtomicBool,
+}
+
+/// Handle to a running compaction
+///
+/// Reports the progress of the compaction, and can be used to cancel it.
+///
+/// Cancelling a compaction only stops this compaction, the tree
+/// (and other compactions) keep running.
+/// The input segments are kept and are available for compaction again.
+#[derive(Clone)]
+#[allow(clippy::module_name_repetitions)]
+pub struct CompactionHandle(Arc<Inner>);
+
+impl std::fmt::Debug for CompactionHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CompactionHandle")
+            .field("segment_ids", &self.0.segment_ids)
+            .field("dest_level", &self.0.dest_level)
+            .field("input_bytes", &self.0.input_bytes)
+            .field("bytes_processed", &self.bytes_processed())
+            .field("segments_written", &self.segments_written())
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+impl CompactionHandle {
+    pub(crate) fn new(
+        segment_ids: HashSet<SegmentId>,
+        dest_level: u8,
+        input_bytes: u64,
+        input_items: u64,
+    ) -> Self {
+        Self(Arc::new(Inner {
+            segment_ids,
+            dest_level,
+            input_bytes,
+            input_items,
+            items_processed: AtomicU64::default(),
+            segments_written: AtomicUsize::default(),
+            tombstones_dropped: AtomicU64::default(),
+            versions_dropped: AtomicU64::default(),
+            start: Instant::now(),
+            cancelled: AtomicBool::default(),
+        }))
+    }
+
+    pub(crate) fn add_items_processed(&self, n: u64) {
+        self.0.items_processed.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_segments_written(&self, n: usize) {
+        self.0.segments_written.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_dropped(&self, tombstones: u64, versions: u64) {
+        self.0
+            .tombstones_dropped
+            .fetch_add(tombstones, Ordering::Relaxed);
+        self.0
+            .versions_dropped
+            .fetch_add(versions, Ordering::Relaxed);
+    }
+
+    pub(crate) fn tombstones_dropped(&self) -> u64 {
+        self.0.tombstones_dropped.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn versions_dropped(&self) -> u64 {
+        self.0.versions_dropped.load(Ordering::Relaxed)
+    }
+
+    /// Returns the IDs of the segments that are compacted.
+    #[must_use]
+    pub fn segment_ids(&self) -> &HashSet<SegmentId> {
+        &self.0.segment_ids
+    }
+
+    /// Returns the level the created segments are written into.
+    #[must_use]
+    pub fn dest_level(&self) -> u8 {
+        self.0.dest_level
+    }
+
+    /// Returns the size of the input segments in bytes.
+    #[must_use]
+    pub fn input_bytes(&self) -> u64 {
+        self.0.input_bytes
+    }
+
+    /// Returns the amount of input bytes that have been processed so far.
+    ///
+    /// This is an estimate, based on the amount of processed items.
+    #[must_use]
+    pub fn bytes_processed(&self) -> u64 {
+        let items_processed = self.0.items_processed.load(Ordering::Relaxed);
+
+        if self.0.input_items == 0 {
+            return 0;
+        }
+
+        let bytes = u128::from(self.0.input_bytes) * u128::from(items_processed)
+            / u128::from(self.0.input_items);
+
+        u64::try_from(bytes)
+            .unwrap_or(u64::MAX)
+            .min(self.0.input_bytes)
+    }
+
+    /// Returns the amount of segments that have been written so far.
+    #[must_use]
+    pub fn segments_written(&self) -> usize {
+        self.0.segments_written.load(Ordering::Relaxed)
+    }
+
+    /// Returns the time since the compaction was started.
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        self.0.start.elapsed()
+    }
+
+    /// Returns the estimated time until the compaction is finished.
+    ///
+    /// Returns `None` if no progress has been made yet.
+    #[must_use]
+    pub fn eta(&self) -> Option<Duration> {
+        let bytes_processed = self.bytes_processed();
+
+        if bytes_processed == 0 {
+            return None;
+        }
+
+        let bytes_left = self.input_bytes().saturating_sub(bytes_processed);
+
+        #[allow(clippy::cast_precision_loss)]
+        let secs_per_byte = self.elapsed().as_secs_f64() / bytes_processed as f64;
+
+        #[allow(clippy::cast_precision_loss)]
+        Some(Duration::from_secs_f64(secs_per_byte * bytes_left as f64))
+    }
+
+    /// Cancels the compaction.
+    ///
+    /// The compaction stops cooperatively, so it may take a moment
+    /// until the compaction worker returns.
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::Release);
+    }
+
+    /// Returns `true` if the compaction was cancelled.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::Acquire)
+    }
+}
+
+/// List of running compactions of a tree
+#[derive(Clone, Default)]
+pub struct ActiveCompactions(Arc<Mutex<Vec<CompactionHandle>>>);
+
+impl ActiveCompactions {
+    /// Registers a compaction, unregistering it when the returned guard is dropped.
+    pub(crate) fn register(&self, handle: CompactionHandle) -> Registration<'_> {
+        self.0
+            .lock()
+            .expect("lock is poisoned")
+            .push(handle.clone());
+
+        Registration { list: self, handle }
+    }
+
+    /// Returns the handles of all running compactions.
+    pub(crate) fn list(&self) -> Vec<CompactionHandle> {
+        self.0.lock().expect("lock is poisoned").clone()
+    }
+}
+
+/// Unregisters a compaction from the [`ActiveCompactions`] when dropped
+pub struct Registration<'a> {
+    list: &'a ActiveCompactions,
+    handle: CompactionHandle,
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        self.list
+            .0
+            .lock()
+            .expect("lock is poisoned")
+            .retain(|x| !Arc::ptr_eq(&x.0, &self.handle.0));
+    }
+}
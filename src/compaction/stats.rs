@@ -0,0 +1,237 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use crate::coding::{Decode, DecodeError, Encode, EncodeError};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::{
+    io::{Read, Write},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Cumulative compaction statistics of a single level
+///
+/// Compactions are accounted to the level they write into.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LevelStats {
+    /// Bytes read from the input segments
+    pub bytes_read: u64,
+
+    /// Bytes written into the level
+    pub bytes_written: u64,
+
+    /// Amount of compactions that wrote into the level
+    pub compactions: u64,
+
+    /// Time spent compacting
+    pub time_spent: Duration,
+
+    /// Tombstones that were dropped by garbage collection
+    pub tombstones_dropped: u64,
+
+    /// Stale versions that were dropped by garbage collection
+    pub versions_dropped: u64,
+}
+
+impl LevelStats {
+    fn add(&mut self, other: &Self) {
+        self.bytes_read += other.bytes_read;
+        self.bytes_written += other.bytes_written;
+        self.compactions += other.compactions;
+        self.time_spent += other.time_spent;
+        self.tombstones_dropped += other.tombstones_dropped;
+        self.versions_dropped += other.versions_dropped;
+    }
+}
+
+impl Encode for LevelStats {
+    fn encode_into<W: Write>(&self, writer: &mut W) -> Result<(), EncodeError> {
+        writer.write_u64::<LittleEndian>(self.bytes_read)?;
+        writer.write_u64::<LittleEndian>(self.bytes_written)?;
+        writer.write_u64::<LittleEndian>(self.compactions)?;
+
+        // NOTE: Nobody is going to spend 2^64 microseconds compacting
+        #[allow(clippy::cast_possible_truncation)]
+        writer.write_u64::<LittleEndian>(self.time_spent.as_micros() as u64)?;
+
+        writer.write_u64::<LittleEndian>(self.tombstones_dropped)?;
+        writer.write_u64::<LittleEndian>(self.versions_dropped)?;
+        Ok(())
+    }
+}
+
+impl Decode for LevelStats {
+    fn decode_from<R: Read>(reader: &mut R) -> Result<Self, DecodeError> {
+        Ok(Self {
+            bytes_read: reader.read_u64::<LittleEndian>()?,
+            bytes_written: reader.read_u64::<LittleEndian>()?,
+            compactions: reader.read_u64::<LittleEndian>()?,
+            time_spent: Duration::from_micros(reader.read_u64::<LittleEndian>()?),
+            tombstones_dropped: reader.read_u64::<LittleEndian>()?,
+            versions_dropped: reader.read_u64::<LittleEndian>()?,
+        })
+    }
+}
+
+/// Cumulative compaction statistics of a tree
+///
+/// Can be encoded, so it can be persisted and restored
+/// using [`crate::AbstractTree::set_compaction_stats`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[allow(clippy::module_name_repetitions)]
+pub struct CompactionStats {
+    /// Bytes written by memtable flushes
+    pub bytes_flushed: u64,
+
+    /// Statistics per level
+    pub levels: Vec<LevelStats>,
+}
+
+impl CompactionStats {
+    /// Returns the statistics of all levels combined.
+    #[must_use]
+    pub fn total(&self) -> LevelStats {
+        let mut total = LevelStats::default();
+
+        for level in &self.levels {
+            total.add(level);
+        }
+
+        total
+    }
+
+    /// Returns the write amplification of the tree.
+    ///
+    /// This is the amount of bytes written by flushes and compactions,
+    /// divided by the amount of bytes written by flushes.
+    ///
+    /// Returns `None` if nothing has been flushed yet.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn write_amplification(&self) -> Option<f64> {
+        if self.bytes_flushed == 0 {
+            return None;
+        }
+
+        let bytes_written = self.bytes_flushed + self.total().bytes_written;
+        Some(bytes_written as f64 / self.bytes_flushed as f64)
+    }
+
+    /// Returns the write amplification caused by compactions into the given level.
+    ///
+    /// This is the amount of bytes written into the level,
+    /// divided by the amount of bytes written by flushes.
+    ///
+    /// Returns `None` if nothing has been flushed yet.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn level_write_amplification(&self, level: usize) -> Option<f64> {
+        if self.bytes_flushed == 0 {
+            return None;
+        }
+
+        let bytes_written = self.levels.get(level).map_or(0, |x| x.bytes_written);
+        Some(bytes_written as f64 / self.bytes_flushed as f64)
+    }
+}
+
+impl Encode for CompactionStats {
+    fn encode_into<W: Write>(&self, writer: &mut W) -> Result<(), EncodeError> {
+        writer.write_u64::<LittleEndian>(self.bytes_flushed)?;
+
+        // NOTE: Level count is 255 max
+        #[allow(clippy::cast_possible_truncation)]
+        writer.write_u8(self.levels.len() as u8)?;
+
+        for level in &self.levels {
+            level.encode_into(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Decode for CompactionStats {
+    fn decode_from<R: Read>(reader: &mut R) -> Result<Self, DecodeError> {
+        let bytes_flushed = reader.read_u64::<LittleEndian>()?;
+        let level_count = reader.read_u8()?;
+
+        let levels = (0..level_count)
+            .map(|_| LevelStats::decode_from(reader))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            bytes_flushed,
+            levels,
+        })
+    }
+}
+
+/// Records the compaction statistics of a tree
+#[derive(Clone, Default)]
+pub struct StatsRecorder(Arc<Mutex<CompactionStats>>);
+
+impl StatsRecorder {
+    pub fn record_flush(&self, bytes: u64) {
+        self.0.lock().expect("lock is poisoned").bytes_flushed += bytes;
+    }
+
+    pub fn record_compaction(&self, dest_level: u8, stats: &LevelStats) {
+        let mut lock = self.0.lock().expect("lock is poisoned");
+
+        let idx = usize::from(dest_level);
+
+        if lock.levels.len() <= idx {
+            lock.levels.resize_with(idx + 1, LevelStats::default);
+        }
+
+        if let Some(level) = lock.levels.get_mut(idx) {
+            level.add(stats);
+        }
+    }
+
+    pub fn get(&self) -> CompactionStats {
+        self.0.lock().expect("lock is poisoned").clone()
+    }
+
+    pub fn set(&self, stats: CompactionStats) {
+        *self.0.lock().expect("lock is poisoned") = stats;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use test_log::test;
+
+    #[test]
+    fn compaction_stats_roundtrip() -> crate::Result<()> {
+        let before = CompactionStats {
+            bytes_flushed: 100,
+            levels: vec![
+                LevelStats::default(),
+                LevelStats {
+                    bytes_read: 100,
+                    bytes_written: 150,
+                    compactions: 2,
+                    time_spent: Duration::from_millis(5),
+                    tombstones_dropped: 3,
+                    versions_dropped: 4,
+                },
+            ],
+        };
+
+        let buf = before.encode_into_vec();
+
+        let mut cursor = Cursor::new(buf);
+        let after = CompactionStats::decode_from(&mut cursor)?;
+
+        assert_eq!(before, after);
+        assert_eq!(Some(2.5), after.write_amplification());
+        assert_eq!(Some(1.5), after.level_write_amplification(1));
+
+        Ok(())
+    }
+}
@@ -2,8 +2,18 @@
 // This source code is licensed under both the Apache 2.0 and MIT License
 // (found in the LICENSE-* files in the repository)
 
+use super::filter::{CompactionFilter, Verdict};
 use crate::{InternalValue, SeqNo, UserKey, ValueType};
-use std::iter::Peekable;
+use std::{iter::Peekable, sync::Arc};
+
+/// Gets notified about the versions a compaction drops
+pub trait DropObserver: Send + Sync {
+    /// Called for every version that is dropped or replaced by a tombstone.
+    ///
+    /// `newer_seqno` is the seqno of the next newer version of the same key
+    /// in the stream, or `None` if the dropped version is the newest one.
+    fn on_drop(&self, item: &InternalValue, newer_seqno: Option<SeqNo>);
+}
 
 /// Consumes a stream of KVs and emits a new stream according to GC and tombstone rules
 ///
@@ -12,6 +22,35 @@ use std::iter::Peekable;
 pub struct CompactionStream<I: Iterator<Item = crate::Result<InternalValue>>> {
     inner: Peekable<I>,
     gc_seqno_threshold: SeqNo,
+
+    /// Unix timestamp (in seconds) used to determine if values have expired
+    now: u64,
+
+    /// User-defined filter that may drop or rewrite values
+    filter: Option<Arc<dyn CompactionFilter>>,
+
+    /// Amount of tombstones dropped by garbage collection
+    tombstones_dropped: u64,
+
+    /// Amount of stale versions dropped by garbage collection
+    versions_dropped: u64,
+
+    /// Seqnos of live snapshots, sorted ascending
+    ///
+    /// If set, only the newest version visible to each snapshot is kept.
+    snapshots: Option<Vec<SeqNo>>,
+
+    /// User key and snapshot stripe of the last emitted item
+    last_emitted: Option<(UserKey, usize)>,
+
+    /// User key and seqno of the last item read from the inner iterator
+    last_read: Option<(UserKey, SeqNo)>,
+
+    /// Gets notified about dropped versions
+    drop_observer: Option<Arc<dyn DropObserver>>,
+
+    /// Rewrites the seqnos of versions that every reader can see to 0
+    zero_seqnos: bool,
 }
 
 impl<I: Iterator<Item = crate::Result<InternalValue>>> CompactionStream<I> {
@@ -23,10 +62,178 @@ impl<I: Iterator<Item = crate::Result<InternalValue>>> CompactionStream<I> {
         Self {
             inner: iter,
             gc_seqno_threshold,
+            now: crate::time::unix_timestamp().as_secs(),
+            filter: None,
+            tombstones_dropped: 0,
+            versions_dropped: 0,
+            snapshots: None,
+            last_emitted: None,
+            last_read: None,
+            drop_observer: None,
+            zero_seqnos: false,
         }
     }
 
-    fn drain_key_min(&mut self, key: &UserKey) -> crate::Result<()> {
+    /// Returns the amount of tombstones dropped by garbage collection.
+    pub fn tombstones_dropped(&self) -> u64 {
+        self.tombstones_dropped
+    }
+
+    /// Returns the amount of stale versions dropped by garbage collection.
+    pub fn versions_dropped(&self) -> u64 {
+        self.versions_dropped
+    }
+
+    /// Installs a compaction filter.
+    #[must_use]
+    pub fn with_filter(mut self, filter: Option<Arc<dyn CompactionFilter>>) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Installs an observer that is notified about dropped versions.
+    #[must_use]
+    pub fn with_drop_observer(mut self, observer: Option<Arc<dyn DropObserver>>) -> Self {
+        self.drop_observer = observer;
+        self
+    }
+
+    /// Rewrites the seqnos of versions below the GC seqno threshold to 0.
+    ///
+    /// Such a version is the only version of its key below the threshold,
+    /// and is visible to every reader, so it does not need its seqno anymore.
+    /// Zeroed seqnos take less space and compress better.
+    ///
+    /// Must only be used when writing into the last level, and if no
+    /// other version of the compacted keys stays behind in the last level,
+    /// because versions are ordered by seqno.
+    #[must_use]
+    pub fn with_zero_seqnos(mut self, zero_seqnos: bool) -> Self {
+        self.zero_seqnos = zero_seqnos;
+        self
+    }
+
+    fn notify_drop(&self, item: &InternalValue, newer_seqno: Option<SeqNo>) {
+        if let Some(observer) = &self.drop_observer {
+            observer.on_drop(item, newer_seqno);
+        }
+    }
+
+    /// Sets the seqnos of the live snapshots.
+    ///
+    /// The snapshots split the versions of a key into stripes; a snapshot
+    /// with seqno `s` sees the versions with a seqno below `s`.
+    /// Of each stripe, only the newest version is kept, because no
+    /// snapshot can see the older ones.
+    ///
+    /// If `None`, all versions that are not older than the GC seqno threshold are kept.
+    #[must_use]
+    pub fn with_snapshots(mut self, snapshots: Option<&[SeqNo]>) -> Self {
+        self.snapshots = snapshots.map(|snapshots| {
+            let mut snapshots = snapshots.to_vec();
+            snapshots.sort_unstable();
+            snapshots.dedup();
+            snapshots
+        });
+        self
+    }
+
+    /// Returns the snapshot stripe the given seqno falls into.
+    ///
+    /// Versions in the same stripe are seen by exactly the same snapshots.
+    fn stripe(&self, seqno: SeqNo) -> Option<usize> {
+        self.snapshots
+            .as_ref()
+            .map(|snapshots| snapshots.partition_point(|&snapshot| snapshot <= seqno))
+    }
+
+    /// Returns `true` if a newer version of the item's key was emitted in the same stripe.
+    fn is_hidden_in_stripe(&self, item: &InternalValue) -> bool {
+        let Some(stripe) = self.stripe(item.key.seqno) else {
+            return false;
+        };
+
+        self.last_emitted
+            .as_ref()
+            .is_some_and(|(key, last_stripe)| *last_stripe == stripe && *key == item.key.user_key)
+    }
+
+    /// Remembers the emitted item, runs the compaction filter on it, and maybe zeroes its seqno.
+    fn emit(&mut self, item: InternalValue) -> InternalValue {
+        let stripe = self.stripe(item.key.seqno);
+
+        if let Some(stripe) = stripe {
+            self.last_emitted = Some((item.key.user_key.clone(), stripe));
+        }
+
+        let mut item = self.apply_filter(item);
+
+        // NOTE: A snapshot that is older than the version must not see it,
+        // so only zero versions that are in the oldest snapshot stripe
+        if self.zero_seqnos
+            && item.key.seqno < self.gc_seqno_threshold
+            && stripe.unwrap_or_default() == 0
+        {
+            item.key.seqno = 0;
+        }
+
+        item
+    }
+
+    /// Sets the unix timestamp (in seconds) used to determine if values have expired.
+    #[cfg(test)]
+    #[must_use]
+    pub fn with_expiry_time(mut self, now: u64) -> Self {
+        self.now = now;
+        self
+    }
+
+    /// Replaces an expired value with a tombstone.
+    ///
+    /// Expired values are invisible to readers, but they still cover older versions,
+    /// so we cannot drop them entirely; the tombstone is then dropped when it reaches the last level.
+    fn expire(&self, item: InternalValue, newer_seqno: Option<SeqNo>) -> InternalValue {
+        if item.is_expired(self.now) {
+            self.notify_drop(&item, newer_seqno);
+            InternalValue::new_tombstone(item.key.user_key, item.key.seqno)
+        } else {
+            item
+        }
+    }
+
+    /// Runs the compaction filter (if any) on a surviving item.
+    fn apply_filter(&self, item: InternalValue) -> InternalValue {
+        let Some(filter) = &self.filter else {
+            return item;
+        };
+
+        // IMPORTANT: Items that are not older than the eviction seqno may be
+        // visible to some snapshot, so we can never alter them
+        if item.is_tombstone() || item.key.seqno >= self.gc_seqno_threshold {
+            return item;
+        }
+
+        let expires_at = item.expires_at();
+        let user_value = item.clone().into_user_value().value;
+
+        match filter.filter(&item.key.user_key, &user_value) {
+            Verdict::Keep => item,
+            Verdict::Remove => InternalValue::new_tombstone(item.key.user_key, item.key.seqno),
+            Verdict::ChangeValue(value) => match expires_at {
+                Some(expires_at) => InternalValue::new_expiring(
+                    item.key.user_key,
+                    value,
+                    expires_at,
+                    item.key.seqno,
+                ),
+                None => InternalValue::new(item.key, value),
+            },
+        }
+    }
+
+    fn drain_key_min(&mut self, head: &InternalValue) -> crate::Result<()> {
+        let mut newer_seqno = head.key.seqno;
+
         loop {
             let Some(next) = self.inner.peek() else {
                 return Ok(());
@@ -43,10 +250,14 @@ impl<I: Iterator<Item = crate::Result<InternalValue>>> CompactionStream<I> {
             };
 
             // Consume version
-            if next.key.user_key == key {
+            if next.key.user_key == head.key.user_key {
                 // NOTE: We know the next value is not empty, because we just peeked it
                 #[allow(clippy::expect_used)]
-                self.inner.next().expect("should not be empty")?;
+                let dropped = self.inner.next().expect("should not be empty")?;
+
+                self.notify_drop(&dropped, Some(newer_seqno));
+                newer_seqno = dropped.key.seqno;
+                self.versions_dropped += 1;
             } else {
                 return Ok(());
             }
@@ -61,6 +272,22 @@ impl<I: Iterator<Item = crate::Result<InternalValue>>> Iterator for CompactionSt
         loop {
             let head = fail_iter!(self.inner.next()?);
 
+            let newer_seqno = self
+                .last_read
+                .as_ref()
+                .filter(|(key, _)| *key == head.key.user_key)
+                .map(|(_, seqno)| *seqno);
+            self.last_read = Some((head.key.user_key.clone(), head.key.seqno));
+
+            let head = self.expire(head, newer_seqno);
+
+            // NOTE: A newer version is visible to every snapshot that could see this one
+            if self.is_hidden_in_stripe(&head) {
+                self.notify_drop(&head, newer_seqno);
+                self.versions_dropped += 1;
+                continue;
+            }
+
             if let Some(peeked) = self.inner.peek() {
                 let Ok(peeked) = peeked else {
                     // NOTE: We just asserted, the peeked value is an error
@@ -74,26 +301,30 @@ impl<I: Iterator<Item = crate::Result<InternalValue>>> Iterator for CompactionSt
 
                 // NOTE: Only item of this key and thus latest version, so return it no matter what
                 if peeked.key.user_key > head.key.user_key {
-                    return Some(Ok(head));
+                    return Some(Ok(self.emit(head)));
                 }
 
                 if peeked.key.seqno < self.gc_seqno_threshold {
                     // NOTE: If next item is an actual value, and current value is weak tombstone,
                     // drop the tombstone
-                    let drop_weak_tombstone = peeked.key.value_type == ValueType::Value
-                        && head.key.value_type == ValueType::WeakTombstone;
+                    let drop_weak_tombstone = matches!(
+                        peeked.key.value_type,
+                        ValueType::Value | ValueType::ExpiringValue
+                    ) && head.key.value_type == ValueType::WeakTombstone;
 
                     // NOTE: Next item is expired,
                     // so the tail of this user key is entirely expired, so drain it all
-                    fail_iter!(self.drain_key_min(&head.key.user_key));
+                    fail_iter!(self.drain_key_min(&head));
 
                     if drop_weak_tombstone {
+                        self.notify_drop(&head, newer_seqno);
+                        self.tombstones_dropped += 1;
                         continue;
                     }
                 }
             }
 
-            return Some(Ok(head));
+            return Some(Ok(self.emit(head)));
         }
     }
 }
@@ -137,6 +368,280 @@ mod tests {
         };
     }
 
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn compaction_stream_expired_value() -> crate::Result<()> {
+        let vec = vec![
+            InternalValue::new_expiring(*b"a", *b"new", 100, 2),
+            InternalValue::from_components(*b"a", *b"old", 1, ValueType::Value),
+            InternalValue::new_expiring(*b"b", *b"new", 300, 2),
+            InternalValue::from_components(*b"b", *b"old", 1, ValueType::Value),
+        ];
+
+        let iter = vec.iter().cloned().map(Ok);
+        let mut iter = CompactionStream::new(iter, SeqNo::MAX).with_expiry_time(200);
+
+        // NOTE: Expired value is turned into a tombstone, which still covers older versions
+        let item = iter.next().unwrap()?;
+        assert_eq!(ValueType::Tombstone, item.key.value_type);
+        assert_eq!(*b"a", &*item.key.user_key);
+        assert_eq!(2, item.key.seqno);
+        assert!(item.value.is_empty());
+
+        assert_eq!(
+            InternalValue::new_expiring(*b"b", *b"new", 300, 2),
+            iter.next().unwrap()?,
+        );
+        iter_closed!(iter);
+
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn compaction_stream_expired_value_no_gc() -> crate::Result<()> {
+        let vec = vec![
+            InternalValue::new_expiring(*b"a", *b"new", 100, 2),
+            InternalValue::from_components(*b"a", *b"old", 1, ValueType::Value),
+        ];
+
+        let iter = vec.iter().cloned().map(Ok);
+        let mut iter = CompactionStream::new(iter, 0).with_expiry_time(200);
+
+        // NOTE: Old version may still be visible to a snapshot
+        assert_eq!(
+            InternalValue::new_tombstone(*b"a", 2),
+            iter.next().unwrap()?,
+        );
+        assert_eq!(
+            InternalValue::from_components(*b"a", *b"old", 1, ValueType::Value),
+            iter.next().unwrap()?,
+        );
+        iter_closed!(iter);
+
+        Ok(())
+    }
+
+    struct DropPrefixFilter;
+
+    impl CompactionFilter for DropPrefixFilter {
+        fn filter(&self, key: &[u8], value: &[u8]) -> Verdict {
+            if key.starts_with(b"drop") {
+                Verdict::Remove
+            } else if value == b"change" {
+                Verdict::ChangeValue((*b"changed").into())
+            } else {
+                Verdict::Keep
+            }
+        }
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn compaction_stream_filter() -> crate::Result<()> {
+        let vec = vec![
+            InternalValue::from_components(*b"a", *b"change", 5, ValueType::Value),
+            InternalValue::from_components(*b"b", *b"keep", 5, ValueType::Value),
+            InternalValue::from_components(*b"drop_c", *b"", 5, ValueType::Value),
+            InternalValue::from_components(*b"drop_c", *b"", 4, ValueType::Value),
+            InternalValue::new_expiring(*b"e", *b"change", u64::MAX, 5),
+        ];
+
+        let iter = vec.iter().cloned().map(Ok);
+        let mut iter =
+            CompactionStream::new(iter, 10).with_filter(Some(Arc::new(DropPrefixFilter)));
+
+        let item = iter.next().unwrap()?;
+        assert_eq!(*b"changed", &*item.value);
+
+        let item = iter.next().unwrap()?;
+        assert_eq!(*b"keep", &*item.value);
+
+        // NOTE: Removed item becomes a tombstone so older versions are not resurrected
+        assert_eq!(
+            InternalValue::new_tombstone(*b"drop_c", 5),
+            iter.next().unwrap()?
+        );
+
+        // NOTE: Expiry timestamp is retained when changing the value
+        let item = iter.next().unwrap()?;
+        assert_eq!(Some(u64::MAX), item.expires_at());
+        assert_eq!(*b"changed", &*item.into_user_value().value);
+
+        iter_closed!(iter);
+
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn compaction_stream_filter_snapshot() -> crate::Result<()> {
+        let vec = vec![
+            InternalValue::from_components(*b"a", *b"change", 5, ValueType::Value),
+            InternalValue::from_components(*b"drop_b", *b"", 5, ValueType::Value),
+        ];
+
+        let iter = vec.iter().cloned().map(Ok);
+        let mut iter = CompactionStream::new(iter, 5).with_filter(Some(Arc::new(DropPrefixFilter)));
+
+        // NOTE: Items may be visible to a snapshot, so they are not touched
+        assert_eq!(&vec[0], &iter.next().unwrap()?);
+        assert_eq!(*b"change", &*vec[0].value);
+        assert_eq!(&vec[1], &iter.next().unwrap()?);
+        assert_eq!(ValueType::Value, vec[1].key.value_type);
+        iter_closed!(iter);
+
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn compaction_stream_snapshot_stripes() -> crate::Result<()> {
+        let vec = vec![
+            InternalValue::from_components(*b"a", *b"9", 9, ValueType::Value),
+            InternalValue::from_components(*b"a", *b"7", 7, ValueType::Value),
+            InternalValue::from_components(*b"a", *b"5", 5, ValueType::Value),
+            InternalValue::from_components(*b"a", *b"3", 3, ValueType::Value),
+            InternalValue::from_components(*b"a", *b"1", 1, ValueType::Value),
+            InternalValue::from_components(*b"b", *b"5", 5, ValueType::Value),
+        ];
+
+        let iter = vec.iter().cloned().map(Ok);
+        let mut iter = CompactionStream::new(iter, 0).with_snapshots(Some(&[8, 4]));
+
+        // NOTE: The snapshots split the versions into the stripes [0, 4), [4, 8) and [8, ∞)
+        assert_eq!(&vec[0], &iter.next().unwrap()?);
+        assert_eq!(&vec[1], &iter.next().unwrap()?);
+        assert_eq!(&vec[3], &iter.next().unwrap()?);
+        assert_eq!(&vec[5], &iter.next().unwrap()?);
+        iter_closed!(iter);
+
+        assert_eq!(2, iter.versions_dropped());
+
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn compaction_stream_snapshot_stripes_no_snapshots() -> crate::Result<()> {
+        #[rustfmt::skip]
+        let vec = stream![
+          "a", "", "T",
+          "a", "old", "V",
+          "a", "older", "V",
+        ];
+
+        let iter = vec.iter().cloned().map(Ok);
+        let mut iter = CompactionStream::new(iter, 0).with_snapshots(Some(&[]));
+
+        // NOTE: No snapshot can see the older versions
+        assert_eq!(
+            InternalValue::from_components(*b"a", *b"", 999, ValueType::Tombstone),
+            iter.next().unwrap()?,
+        );
+        iter_closed!(iter);
+
+        Ok(())
+    }
+
+    #[derive(Default)]
+    struct CollectDropped(std::sync::Mutex<Vec<(InternalValue, Option<SeqNo>)>>);
+
+    impl DropObserver for CollectDropped {
+        fn on_drop(&self, item: &InternalValue, newer_seqno: Option<SeqNo>) {
+            self.0
+                .lock()
+                .expect("lock is poisoned")
+                .push((item.clone(), newer_seqno));
+        }
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn compaction_stream_drop_observer() -> crate::Result<()> {
+        let vec = vec![
+            InternalValue::new_expiring(*b"a", *b"new", 100, 3),
+            InternalValue::from_components(*b"a", *b"old", 2, ValueType::Value),
+            InternalValue::from_components(*b"a", *b"older", 1, ValueType::Value),
+            InternalValue::from_components(*b"b", *b"b", 1, ValueType::Value),
+        ];
+
+        let observer = Arc::new(CollectDropped::default());
+
+        let iter = vec.iter().cloned().map(Ok);
+        let mut iter = CompactionStream::new(iter, SeqNo::MAX)
+            .with_expiry_time(200)
+            .with_drop_observer(Some(observer.clone()));
+
+        assert_eq!(
+            InternalValue::new_tombstone(*b"a", 3),
+            iter.next().unwrap()?
+        );
+        assert_eq!(&vec[3], &iter.next().unwrap()?);
+        iter_closed!(iter);
+
+        // NOTE: The expired value is reported as well, not the tombstone replacing it
+        assert_eq!(
+            vec![
+                (vec[0].clone(), None),
+                (vec[1].clone(), Some(3)),
+                (vec[2].clone(), Some(2)),
+            ],
+            *observer.0.lock().expect("lock is poisoned"),
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn compaction_stream_zero_seqnos() -> crate::Result<()> {
+        let vec = vec![
+            InternalValue::from_components(*b"a", *b"new", 4, ValueType::Value),
+            InternalValue::from_components(*b"a", *b"old", 3, ValueType::Value),
+            InternalValue::new_expiring(*b"b", *b"b", u64::MAX, 2),
+            InternalValue::from_components(*b"c", *b"c", 9, ValueType::Value),
+        ];
+
+        let iter = vec.iter().cloned().map(Ok);
+        let mut iter = CompactionStream::new(iter, 5).with_zero_seqnos(true);
+
+        assert_eq!(
+            InternalValue::from_components(*b"a", *b"new", 0, ValueType::Value),
+            iter.next().unwrap()?,
+        );
+        assert_eq!(
+            InternalValue::new_expiring(*b"b", *b"b", u64::MAX, 0),
+            iter.next().unwrap()?,
+        );
+
+        // NOTE: Versions that are not older than the threshold keep their seqno
+        assert_eq!(&vec[3], &iter.next().unwrap()?);
+        iter_closed!(iter);
+
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn compaction_stream_zero_seqnos_snapshot() -> crate::Result<()> {
+        let vec = vec![
+            InternalValue::from_components(*b"a", *b"new", 4, ValueType::Value),
+            InternalValue::from_components(*b"a", *b"old", 2, ValueType::Value),
+        ];
+
+        let iter = vec.iter().cloned().map(Ok);
+        let mut iter = CompactionStream::new(iter, 5)
+            .with_snapshots(Some(&[3]))
+            .with_zero_seqnos(true);
+
+        // NOTE: Snapshot 3 must not see the newer version
+        assert_eq!(&vec[0], &iter.next().unwrap()?);
+        iter_closed!(iter);
+
+        Ok(())
+    }
+
     #[test]
     #[allow(clippy::unwrap_used)]
     fn compaction_stream_queue_weak_tombstones() {
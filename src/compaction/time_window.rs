@@ -0,0 +1,244 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use super::{maintenance::choose_least_effort_compaction, Choice, CompactionStrategy};
+use crate::{
+    config::Config, level_manifest::LevelManifest, segment::Segment, time::unix_timestamp, HashSet,
+};
+use std::{collections::BTreeMap, sync::Arc};
+
+/// Extracts a timestamp from a key, used to assign segments to time windows
+///
+/// # Examples
+///
+/// ```
+/// use lsm_tree::compaction::TimestampExtractor;
+///
+/// /// Parses keys that look like `series/timestamp`
+/// struct SeriesTimestamp;
+///
+/// impl TimestampExtractor for SeriesTimestamp {
+///     fn extract(&self, key: &[u8]) -> Option<u64> {
+///         let pos = key.iter().rposition(|&x| x == b'/')?;
+///         let bytes = key.get(pos + 1..)?;
+///         std::str::from_utf8(bytes).ok()?.parse().ok()
+///     }
+/// }
+/// ```
+pub trait TimestampExtractor: Send + Sync {
+    /// Returns the key's timestamp as Unix timestamp in seconds.
+    ///
+    /// If `None` is returned, the segment's creation time is used instead.
+    fn extract(&self, key: &[u8]) -> Option<u64>;
+}
+
+/// Time-window compaction strategy (TWCS)
+///
+/// Groups segments into fixed-size time windows.
+/// Segments inside the same window are merged, windows are never merged with each other,
+/// so a whole window can be dropped cheaply once it is past the retention period.
+///
+/// The newest window is merged if it collects `min_merge_width` segments,
+/// older windows (that should not receive writes anymore) are merged down to a single segment.
+///
+/// The timestamp of a segment is the time its newest data was written,
+/// or, if a [`TimestampExtractor`] is configured, the newest timestamp
+/// of the segment's first and last key.
+///
+/// Only segments that are adjacent in write order (sequence numbers) are merged,
+/// so a window that is interleaved with another window (e.g. because of late writes)
+/// may consist of multiple segments.
+///
+/// Same as `TimeWindowCompactionStrategy` in Cassandra.
+#[derive(Clone)]
+pub struct Strategy {
+    /// Size of a time window in seconds
+    ///
+    /// Default = 1 day
+    pub window_size_seconds: u64,
+
+    /// Windows that are older than this are dropped, will be disabled if 0 or None
+    ///
+    /// Default = None
+    pub retention_seconds: Option<u64>,
+
+    /// Amount of segments in the newest window that triggers a merge.
+    ///
+    /// Default = 4
+    pub min_merge_width: usize,
+
+    /// Maximum amount of segments that are merged at once.
+    ///
+    /// Default = 32
+    pub max_merge_width: usize,
+
+    /// Extracts timestamps from keys, so windows are based on the data's
+    /// own timestamps instead of its write time.
+    ///
+    /// Default = None
+    pub timestamp_extractor: Option<Arc<dyn TimestampExtractor>>,
+}
+
+impl Default for Strategy {
+    fn default() -> Self {
+        Self {
+            window_size_seconds: /* 1 day */ 60 * 60 * 24,
+            retention_seconds: None,
+            min_merge_width: 4,
+            max_merge_width: 32,
+            timestamp_extractor: None,
+        }
+    }
+}
+
+impl Strategy {
+    /// Configures a new `TimeWindow` compaction strategy
+    #[must_use]
+    pub fn new(window_size_seconds: u64, retention_seconds: Option<u64>) -> Self {
+        Self {
+            window_size_seconds,
+            retention_seconds,
+            ..Default::default()
+        }
+    }
+
+    /// Groups segments by their time window, sorted from oldest to newest window.
+    fn group_by_window<'a>(
+        &self,
+        segments: impl Iterator<Item = &'a Segment>,
+    ) -> BTreeMap<u64, Vec<&'a Segment>> {
+        let mut windows: BTreeMap<u64, Vec<&Segment>> = BTreeMap::new();
+
+        for segment in segments {
+            windows
+                .entry(self.segment_window(segment))
+                .or_default()
+                .push(segment);
+        }
+
+        windows
+    }
+
+    /// Splits segments into runs that are adjacent in write order and belong to the same window.
+    ///
+    /// Returns the runs sorted from oldest to newest window,
+    /// each run sorted from oldest to newest segment.
+    fn group_by_run<'a>(
+        &self,
+        segments: impl Iterator<Item = &'a Segment>,
+    ) -> Vec<(u64, Vec<Segment>)> {
+        let mut segments = segments.cloned().collect::<Vec<_>>();
+        segments.sort_by_key(|x| x.metadata.seqnos.1);
+
+        let mut runs: Vec<(u64, Vec<Segment>)> = vec![];
+
+        for segment in segments {
+            let window = self.segment_window(&segment);
+
+            match runs.last_mut() {
+                Some((run_window, run)) if *run_window == window => run.push(segment),
+                _ => runs.push((window, vec![segment])),
+            }
+        }
+
+        // NOTE: Sort is stable, so runs of the same window stay in write order
+        runs.sort_by_key(|(window, _)| *window);
+
+        runs
+    }
+
+    /// Returns the time window of a segment.
+    fn segment_window(&self, segment: &Segment) -> u64 {
+        self.segment_timestamp(segment) / self.window_size_seconds.max(1)
+    }
+
+    /// Returns the timestamp of a segment, in seconds.
+    fn segment_timestamp(&self, segment: &Segment) -> u64 {
+        let extracted = self.timestamp_extractor.as_ref().and_then(|extractor| {
+            let key_range = &segment.metadata.key_range;
+            extractor
+                .extract(key_range.min())
+                .max(extractor.extract(key_range.max()))
+        });
+
+        // NOTE: Use the age of the newest data instead of the file's creation time,
+        // so segments rewritten by compaction stay in their window
+        extracted.unwrap_or_else(|| {
+            // NOTE: Unix timestamp in seconds fits into u64
+            #[allow(clippy::cast_possible_truncation)]
+            let secs = (*segment.metadata.data_created_at / 1_000_000_000) as u64;
+            secs
+        })
+    }
+}
+
+impl CompactionStrategy for Strategy {
+    fn get_name(&self) -> &'static str {
+        "TimeWindowStrategy"
+    }
+
+    fn choose(&self, levels: &LevelManifest, _: &Config) -> Choice {
+        let resolved_view = levels.resolved_view();
+
+        if let Some(retention_seconds) = self.retention_seconds.filter(|&x| x > 0) {
+            let now = unix_timestamp().as_secs();
+            let window_size = self.window_size_seconds.max(1);
+
+            let segment_ids: HashSet<_> = self
+                .group_by_window(resolved_view.iter().flat_map(|lvl| &lvl.segments))
+                .into_iter()
+                .filter(|(window, _)| {
+                    let window_end = (window + 1).saturating_mul(window_size);
+                    now.saturating_sub(window_end) > retention_seconds
+                })
+                .flat_map(|(_, segments)| segments.into_iter().map(Segment::id))
+                .collect();
+
+            if !segment_ids.is_empty() {
+                log::debug!("Dropping time windows past retention: {segment_ids:?}");
+                return Choice::Drop(segment_ids);
+            }
+        }
+
+        // NOTE: First level always exists, trivial
+        #[allow(clippy::expect_used)]
+        let first_level = resolved_view.first().expect("L0 should always exist");
+
+        // NOTE: Only merge L0 segments, so we never pull data out of a deeper level
+        //
+        // Segments of different windows may interleave in write order, so we only
+        // merge consecutive segments of a window, to not reorder versions
+        let runs = self.group_by_run(first_level.iter());
+        let newest_window = runs.last().map(|(window, _)| *window);
+
+        for (window, segments) in runs {
+            let threshold = if Some(window) == newest_window {
+                self.min_merge_width.max(2)
+            } else {
+                2
+            };
+
+            if segments.len() < threshold {
+                continue;
+            }
+
+            let count = segments.len().min(self.max_merge_width.max(2));
+            let segment_ids = choose_least_effort_compaction(&segments, count);
+
+            log::debug!(
+                "Merging {} segments in time window {window}",
+                segment_ids.len()
+            );
+
+            return Choice::Merge(super::Input {
+                dest_level: 0,
+                segment_ids,
+                // NOTE: A window should end up as a single segment
+                target_size: u64::MAX,
+            });
+        }
+
+        Choice::DoNothing
+    }
+}
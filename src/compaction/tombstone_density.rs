@@ -0,0 +1,195 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use super::{Choice, CompactionStrategy, Input as CompactionInput};
+use crate::{
+    config::Config, level_manifest::LevelManifest, segment::Segment, HashSet, KeyRange, SegmentId,
+};
+
+/// Tombstone-density-triggered compaction
+///
+/// Picks the segment with the highest ratio of tombstones (if it exceeds `threshold`),
+/// and compacts it (and all segments it overlaps in the same and deeper levels)
+/// into the last level, where the tombstones can be evicted.
+///
+/// Useful for delete-heavy workloads, where scans otherwise slow down
+/// because they need to skip over piles of tombstones.
+///
+/// This strategy is meant to be run in addition to the tree's main compaction strategy.
+#[derive(Clone)]
+pub struct Strategy {
+    /// Ratio of tombstones (to items) in a segment that triggers a compaction
+    ///
+    /// Default = 0.5
+    pub threshold: f32,
+
+    /// The target segment size as disk (possibly compressed).
+    ///
+    /// Default = 64 MiB
+    pub target_size: u64,
+}
+
+impl Default for Strategy {
+    fn default() -> Self {
+        Self {
+            threshold: 0.5,
+            target_size:/* 64 Mib */ 64 * 1_024 * 1_024,
+        }
+    }
+}
+
+impl Strategy {
+    /// Configures a new `TombstoneDensity` compaction strategy.
+    ///
+    /// # Panics
+    ///
+    /// Panics, if `threshold` is not in (0.0, 1.0].
+    #[must_use]
+    pub fn new(threshold: f32) -> Self {
+        assert!(
+            threshold > 0.0 && threshold <= 1.0,
+            "tombstone ratio threshold should be in (0.0, 1.0]",
+        );
+
+        Self {
+            threshold,
+            ..Default::default()
+        }
+    }
+
+    /// Collects the segments that need to be merged together with a segment
+    /// in `level_idx`, so its tombstones can be evicted in the last level.
+    ///
+    /// All overlapping segments in the same and deeper levels need to be pulled in,
+    /// otherwise an older version of a key could be left above the evicted tombstone.
+    /// Because pulling in segments may widen the key range, this is repeated
+    /// until the key range is stable.
+    fn pull_in(levels: &LevelManifest, level_idx: usize, segment: &Segment) -> HashSet<SegmentId> {
+        let mut key_range = segment.metadata.key_range.clone();
+
+        loop {
+            let segments: Vec<&Segment> = levels
+                .levels
+                .iter()
+                .skip(level_idx)
+                .flat_map(|level| level.overlapping_segments(&key_range))
+                .collect();
+
+            let next_key_range =
+                KeyRange::aggregate(segments.iter().map(|x| &x.metadata.key_range));
+
+            if next_key_range == key_range {
+                return segments.into_iter().map(Segment::id).collect();
+            }
+
+            key_range = next_key_range;
+        }
+    }
+}
+
+impl CompactionStrategy for Strategy {
+    fn get_name(&self) -> &'static str {
+        "TombstoneDensityStrategy"
+    }
+
+    fn choose(&self, levels: &LevelManifest, _: &Config) -> Choice {
+        let Some((level_idx, segment)) = levels
+            .levels
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, level)| level.segments.iter().map(move |segment| (idx, segment)))
+            .filter(|(_, segment)| !levels.hidden_set().is_hidden(segment.id()))
+            .filter(|(_, segment)| segment.tombstone_count() > 0)
+            .filter(|(_, segment)| segment.tombstone_ratio() >= self.threshold)
+            .max_by(|(_, a), (_, b)| {
+                a.tombstone_ratio()
+                    .partial_cmp(&b.tombstone_ratio())
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+        else {
+            return Choice::DoNothing;
+        };
+
+        let segment_ids = Self::pull_in(levels, level_idx, segment);
+
+        if levels.hidden_set().is_blocked(segment_ids.iter().copied()) {
+            // IMPORTANT: Compaction is blocked because of other
+            // on-going compaction
+            return Choice::DoNothing;
+        }
+
+        log::debug!(
+            "Segment {} has tombstone ratio of {}, compacting {segment_ids:?} into last level",
+            segment.id(),
+            segment.tombstone_ratio(),
+        );
+
+        Choice::Merge(CompactionInput {
+            segment_ids,
+            dest_level: levels.last_level_index(),
+            target_size: self.target_size,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AbstractTree, SeqNo};
+    use std::sync::Arc;
+    use test_log::test;
+
+    #[test]
+    fn tombstone_density_below_threshold() -> crate::Result<()> {
+        let folder = tempfile::tempdir()?;
+        let tree = Config::new(&folder).open()?;
+
+        tree.insert("a", "a", 0);
+        tree.insert("b", "b", 0);
+        tree.remove("c", 0);
+        tree.flush_active_memtable(0)?;
+
+        let levels = tree.levels.read().expect("lock is poisoned");
+
+        assert_eq!(
+            Choice::DoNothing,
+            Strategy::new(0.5).choose(&levels, &Config::default()),
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn tombstone_density_pull_in_deeper_levels() -> crate::Result<()> {
+        let folder = tempfile::tempdir()?;
+        let tree = Config::new(&folder).open()?;
+
+        tree.insert("a", "a", 0);
+        tree.insert("b", "b", 0);
+        tree.flush_active_memtable(0)?;
+        tree.compact(Arc::new(crate::compaction::MoveDown(0, 3)), SeqNo::MAX)?;
+
+        tree.insert("x", "x", 1);
+        tree.insert("y", "y", 1);
+        tree.flush_active_memtable(0)?;
+        tree.compact(Arc::new(crate::compaction::MoveDown(0, 2)), SeqNo::MAX)?;
+
+        tree.remove("a", 2);
+        tree.remove("b", 2);
+        tree.flush_active_memtable(0)?;
+
+        let levels = tree.levels.read().expect("lock is poisoned");
+
+        assert_eq!(
+            Choice::Merge(CompactionInput {
+                segment_ids: [0, 2].into_iter().collect(),
+                dest_level: levels.last_level_index(),
+                target_size: 64 * 1_024 * 1_024,
+            }),
+            Strategy::new(0.5).choose(&levels, &Config::default()),
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,394 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use super::{Choice, CompactionStrategy, Input as CompactionInput};
+use crate::{level_manifest::LevelManifest, segment::Segment, Config, HashSet, SegmentId};
+
+/// A sorted run of segments
+///
+/// Every segment in L0 is its own run, every other (non-empty) level is a single run.
+struct Run {
+    /// Level the run lives in
+    level_idx: u8,
+
+    segment_ids: Vec<SegmentId>,
+
+    /// Size of the run in bytes
+    size: u64,
+}
+
+impl Run {
+    fn from_segments<'a>(level_idx: u8, segments: impl Iterator<Item = &'a Segment>) -> Self {
+        let mut segment_ids = vec![];
+        let mut size = 0;
+
+        for segment in segments {
+            segment_ids.push(segment.id());
+            size += segment.metadata.file_size;
+        }
+
+        Self {
+            level_idx,
+            segment_ids,
+            size,
+        }
+    }
+}
+
+/// Universal compaction strategy (hybrid tiered)
+///
+/// Treats the tree as a list of sorted runs, ordered from newest to oldest.
+/// Every segment in L0 is a sorted run, and every non-empty level below is a sorted run.
+///
+/// Runs are merged, if one of these triggers is met (checked in order):
+///
+/// 1. Space amplification: all runs (but the oldest) together are larger than
+///    `max_size_amplification_percent` percent of the oldest run, so everything is merged
+///    into the last level.
+///
+/// 2. Size ratio: starting at the newest run, runs are gathered as long as the next run is not
+///    larger than `size_ratio` percent of the accumulated size.
+///    If at least `min_merge_width` runs are gathered, they are merged.
+///
+/// 3. Run count: there are more than `max_run_count` runs, so the newest runs are merged
+///    to get the run count back to the limit.
+///
+/// Universal compaction has lower write amplification than [`super::Leveled`],
+/// but higher space and read amplification.
+///
+/// Same as `kCompactionStyleUniversal` in `RocksDB`.
+#[derive(Clone)]
+pub struct Strategy {
+    /// Percentage a run may be larger than the accumulated size
+    /// of the newer runs, to still be included in a merge.
+    ///
+    /// Default = 1
+    ///
+    /// Same as `size_ratio` in `RocksDB`.
+    pub size_ratio: u32,
+
+    /// Minimum amount of runs to merge because of the size ratio trigger.
+    ///
+    /// Default = 2
+    ///
+    /// Same as `min_merge_width` in `RocksDB`.
+    pub min_merge_width: usize,
+
+    /// Size of all runs (but the oldest) in percent of the oldest run's size,
+    /// that triggers a full compaction.
+    ///
+    /// Default = 200
+    ///
+    /// Same as `max_size_amplification_percent` in `RocksDB`.
+    pub max_size_amplification_percent: u32,
+
+    /// Amount of runs that triggers a compaction regardless of their sizes.
+    ///
+    /// Default = 8
+    ///
+    /// Same as `level0_file_num_compaction_trigger` in `RocksDB`.
+    pub max_run_count: usize,
+
+    /// The target segment size as disk (possibly compressed).
+    ///
+    /// Default = 64 MiB
+    pub target_size: u64,
+}
+
+impl Default for Strategy {
+    fn default() -> Self {
+        Self {
+            size_ratio: 1,
+            min_merge_width: 2,
+            max_size_amplification_percent: 200,
+            max_run_count: 8,
+            target_size:/* 64 Mib */ 64 * 1_024 * 1_024,
+        }
+    }
+}
+
+impl Strategy {
+    /// Returns the sorted runs of the tree, ordered from newest to oldest.
+    fn sorted_runs(levels: &LevelManifest) -> Vec<Run> {
+        let mut runs = vec![];
+
+        for (idx, level) in levels.levels.iter().enumerate() {
+            // NOTE: Level count is 255 max
+            #[allow(clippy::cast_possible_truncation)]
+            let idx = idx as u8;
+
+            if level.is_empty() {
+                continue;
+            }
+
+            if idx == 0 {
+                let mut segments = level.segments.iter().collect::<Vec<_>>();
+                segments.sort_by_key(|segment| std::cmp::Reverse(segment.metadata.seqnos.1));
+
+                runs.extend(
+                    segments
+                        .into_iter()
+                        .map(|segment| Run::from_segments(0, std::iter::once(segment))),
+                );
+            } else {
+                runs.push(Run::from_segments(idx, level.segments.iter()));
+            }
+        }
+
+        runs
+    }
+
+    /// Picks the newest runs, using the size ratio trigger.
+    fn pick_by_size_ratio(&self, runs: &[Run]) -> usize {
+        let mut accumulated_size = 0;
+        let mut count = 0;
+
+        for run in runs {
+            if count > 0 {
+                let limit = accumulated_size * (100 + u64::from(self.size_ratio)) / 100;
+
+                if run.size > limit {
+                    break;
+                }
+            }
+
+            accumulated_size += run.size;
+            count += 1;
+        }
+
+        count
+    }
+
+    /// Merges the newest `count` runs.
+    ///
+    /// The created segments are put as deep as possible, while still staying above
+    /// the next older run, so the runs stay ordered by age.
+    fn merge_newest(&self, levels: &LevelManifest, runs: &[Run], count: usize) -> Choice {
+        let (picked, rest) = runs.split_at(count);
+
+        let segment_ids: HashSet<_> = picked
+            .iter()
+            .flat_map(|run| run.segment_ids.iter().copied())
+            .collect();
+
+        if levels.hidden_set().is_blocked(segment_ids.iter().copied()) {
+            // IMPORTANT: Compaction is blocked because of other
+            // on-going compaction
+            return Choice::DoNothing;
+        }
+
+        let dest_level = match rest.first() {
+            Some(next_run) => next_run.level_idx.saturating_sub(1),
+            None => levels.last_level_index(),
+        };
+
+        Choice::Merge(CompactionInput {
+            segment_ids,
+            dest_level,
+            target_size: self.target_size,
+        })
+    }
+}
+
+impl CompactionStrategy for Strategy {
+    fn get_name(&self) -> &'static str {
+        "UniversalStrategy"
+    }
+
+    fn choose(&self, levels: &LevelManifest, _: &Config) -> Choice {
+        let runs = Self::sorted_runs(levels);
+
+        if runs.len() < 2 {
+            return Choice::DoNothing;
+        }
+
+        // 1. Space amplification
+        if let Some((oldest, newer)) = runs.split_last() {
+            let newer_size = newer.iter().map(|run| run.size).sum::<u64>();
+            let limit = oldest.size * u64::from(self.max_size_amplification_percent) / 100;
+
+            if newer_size > limit {
+                log::debug!(
+                    "Universal compaction: space amplification trigger ({newer_size}B > {limit}B)",
+                );
+                return self.merge_newest(levels, &runs, runs.len());
+            }
+        }
+
+        // 2. Size ratio
+        let count = self.pick_by_size_ratio(&runs);
+
+        if count >= self.min_merge_width.max(2) {
+            log::debug!("Universal compaction: size ratio trigger ({count} runs)");
+            return self.merge_newest(levels, &runs, count);
+        }
+
+        // 3. Run count
+        if runs.len() > self.max_run_count {
+            let count = runs.len() - self.max_run_count + 1;
+
+            log::debug!(
+                "Universal compaction: run count trigger ({} runs), merging {count} runs",
+                runs.len(),
+            );
+            return self.merge_newest(levels, &runs, count.max(2));
+        }
+
+        Choice::DoNothing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AbstractTree, SeqNo, Tree};
+    use std::sync::Arc;
+    use test_log::test;
+
+    fn flush_keys(tree: &Tree, prefix: &str, count: usize, seqno: SeqNo) -> crate::Result<()> {
+        for idx in 0..count {
+            tree.insert(format!("{prefix}{idx:0>6}"), "a".repeat(100), seqno);
+        }
+        tree.flush_active_memtable(0)?;
+        Ok(())
+    }
+
+    fn ids(ids: &[SegmentId]) -> HashSet<SegmentId> {
+        ids.iter().copied().collect()
+    }
+
+    #[test]
+    fn universal_single_run() -> crate::Result<()> {
+        let folder = tempfile::tempdir()?;
+        let tree = Config::new(&folder).open()?;
+
+        flush_keys(&tree, "a", 100, 0)?;
+
+        let levels = tree.levels.read().expect("lock is poisoned");
+        assert_eq!(
+            Choice::DoNothing,
+            Strategy::default().choose(&levels, &Config::default()),
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn universal_size_ratio() -> crate::Result<()> {
+        let folder = tempfile::tempdir()?;
+        let tree = Config::new(&folder).open()?;
+
+        flush_keys(&tree, "a", 100, 0)?;
+        flush_keys(&tree, "b", 100, 1)?;
+
+        let levels = tree.levels.read().expect("lock is poisoned");
+        assert_eq!(
+            Choice::Merge(CompactionInput {
+                segment_ids: ids(&[0, 1]),
+                dest_level: levels.last_level_index(),
+                target_size: 64 * 1_024 * 1_024,
+            }),
+            Strategy::default().choose(&levels, &Config::default()),
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn universal_no_trigger() -> crate::Result<()> {
+        let folder = tempfile::tempdir()?;
+        let tree = Config::new(&folder).open()?;
+
+        flush_keys(&tree, "a", 1_000, 0)?;
+        flush_keys(&tree, "b", 10, 1)?;
+
+        let levels = tree.levels.read().expect("lock is poisoned");
+        assert_eq!(
+            Choice::DoNothing,
+            Strategy::default().choose(&levels, &Config::default()),
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn universal_space_amplification() -> crate::Result<()> {
+        let folder = tempfile::tempdir()?;
+        let tree = Config::new(&folder).open()?;
+
+        flush_keys(&tree, "a", 10, 0)?;
+        flush_keys(&tree, "b", 1_000, 1)?;
+
+        let strategy = Strategy {
+            // NOTE: Disable size ratio trigger
+            min_merge_width: usize::MAX,
+            ..Default::default()
+        };
+
+        let levels = tree.levels.read().expect("lock is poisoned");
+        assert_eq!(
+            Choice::Merge(CompactionInput {
+                segment_ids: ids(&[0, 1]),
+                dest_level: levels.last_level_index(),
+                target_size: 64 * 1_024 * 1_024,
+            }),
+            strategy.choose(&levels, &Config::default()),
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn universal_run_count() -> crate::Result<()> {
+        let folder = tempfile::tempdir()?;
+        let tree = Config::new(&folder).open()?;
+
+        flush_keys(&tree, "a", 10_000, 0)?;
+        flush_keys(&tree, "b", 100, 1)?;
+        flush_keys(&tree, "c", 1, 2)?;
+
+        let strategy = Strategy {
+            max_run_count: 2,
+            ..Default::default()
+        };
+
+        let levels = tree.levels.read().expect("lock is poisoned");
+
+        // NOTE: Oldest run is still in L0, so the merged segments need to stay in L0
+        assert_eq!(
+            Choice::Merge(CompactionInput {
+                segment_ids: ids(&[1, 2]),
+                dest_level: 0,
+                target_size: 64 * 1_024 * 1_024,
+            }),
+            strategy.choose(&levels, &Config::default()),
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn universal_stay_above_older_run() -> crate::Result<()> {
+        let folder = tempfile::tempdir()?;
+        let tree = Config::new(&folder).open()?;
+
+        flush_keys(&tree, "a", 1_000, 0)?;
+        tree.compact(Arc::new(crate::compaction::MoveDown(0, 6)), SeqNo::MAX)?;
+
+        flush_keys(&tree, "b", 10, 1)?;
+        flush_keys(&tree, "c", 10, 2)?;
+
+        let levels = tree.levels.read().expect("lock is poisoned");
+        assert_eq!(
+            Choice::Merge(CompactionInput {
+                segment_ids: ids(&[1, 2]),
+                dest_level: 5,
+                target_size: 64 * 1_024 * 1_024,
+            }),
+            Strategy::default().choose(&levels, &Config::default()),
+        );
+
+        Ok(())
+    }
+}
@@ -2,21 +2,38 @@
 // This source code is licensed under both the Apache 2.0 and MIT License
 // (found in the LICENSE-* files in the repository)
 
-use super::{CompactionStrategy, Input as CompactionPayload};
+use super::{
+    progress::{ActiveCompactions, CompactionHandle},
+    stats::{LevelStats, StatsRecorder},
+    CompactionFilter, CompactionStrategy, Input as CompactionPayload,
+};
 use crate::{
-    compaction::{stream::CompactionStream, Choice},
+    blob_tree::relocation::{BlobRelocation, ValueLogHandle},
+    compaction::{
+        compact_range, drop_range,
+        stream::{CompactionStream, DropObserver},
+        Choice,
+    },
+    config::TreeType,
     file::SEGMENTS_FOLDER,
     level_manifest::LevelManifest,
     level_scanner::LevelScanner,
     merge::Merger,
-    segment::{multi_writer::MultiWriter, Segment},
+    rate_limiter::{IoPriority, RateLimiter},
+    segment::{
+        multi_writer::{remove_segment_files, MultiWriter},
+        Segment,
+    },
     stop_signal::StopSignal,
     tree::inner::TreeId,
-    Config, GlobalSegmentId, InternalValue, SegmentId, SeqNo,
+    CompactionError, CompactionPhase, Config, GlobalSegmentId, InternalValue, KeyRange, SegmentId,
+    SeqNo, UserKey,
 };
 use std::{
+    ops::Bound,
+    path::Path,
     sync::{atomic::AtomicU64, Arc, RwLock, RwLockWriteGuard},
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 pub type CompactionReader<'a> = Box<dyn Iterator<Item = crate::Result<InternalValue>> + 'a>;
@@ -40,8 +57,22 @@ pub struct Options {
     /// the tree is dropped.
     pub stop_signal: StopSignal,
 
+    /// Compactions that are currently running.
+    pub active_compactions: ActiveCompactions,
+
+    /// Cumulative compaction statistics.
+    pub stats: StatsRecorder,
+
     /// Evicts items that are older than this seqno (MVCC GC).
     pub eviction_seqno: u64,
+
+    /// Seqnos of the live snapshots, if known.
+    ///
+    /// Versions that no snapshot can see are evicted, even if they are newer than `eviction_seqno`.
+    pub snapshots: Option<Vec<SeqNo>>,
+
+    /// Value log, if this is the index tree of a blob tree.
+    pub value_log: Option<ValueLogHandle>,
 }
 
 impl Options {
@@ -52,8 +83,12 @@ impl Options {
             config: tree.config.clone(),
             levels: tree.levels.clone(),
             stop_signal: tree.stop_signal.clone(),
+            active_compactions: tree.active_compactions.clone(),
+            stats: tree.compaction_stats.clone(),
             strategy,
             eviction_seqno: 0,
+            snapshots: None,
+            value_log: tree.value_log.get().cloned(),
         }
     }
 }
@@ -74,7 +109,7 @@ pub fn do_compaction(opts: &Options) -> crate::Result<()> {
     log::debug!("compactor: choice: {choice:?}");
 
     match choice {
-        Choice::Merge(payload) => merge_segments(original_levels, opts, &payload),
+        Choice::Merge(payload) => merge_segments(original_levels, opts, &payload, None),
         Choice::Move(payload) => move_segments(original_levels, opts, payload),
         Choice::Drop(payload) => drop_segments(
             original_levels,
@@ -91,10 +126,130 @@ pub fn do_compaction(opts: &Options) -> crate::Result<()> {
     }
 }
 
+/// Compacts all segments overlapping a key range down to the strategy's target level.
+///
+/// Levels are processed top-down, so each level's overlapping segments
+/// are merged into the next level, until the target level is reached.
+///
+/// If the segments of a level are currently being compacted by another compaction,
+/// the level is retried until they are released.
+///
+/// This will block until the compactor is fully finished.
+#[allow(clippy::significant_drop_tightening)]
+pub fn do_compact_range(opts: &Options, strategy: &compact_range::Strategy) -> crate::Result<()> {
+    for level_idx in 0..strategy.target_level() {
+        loop {
+            if opts.stop_signal.is_stopped() {
+                log::debug!("Stopping compact_range because of stop signal");
+                return Ok(());
+            }
+
+            let levels = opts.levels.write().expect("lock is poisoned");
+            let segment_ids = strategy.pick(&levels, level_idx);
+
+            if segment_ids.is_empty() {
+                break;
+            }
+
+            if levels.hidden_set().is_blocked(segment_ids.iter().copied()) {
+                // IMPORTANT: Wait for the other compaction to finish,
+                // without holding the levels lock
+                drop(levels);
+
+                log::trace!("compactor: compact_range of L{level_idx} is blocked, retrying");
+                std::thread::sleep(Duration::from_millis(10));
+                continue;
+            }
+
+            log::debug!(
+                "compactor: compact_range L{level_idx}->L{}: {segment_ids:?}",
+                level_idx + 1,
+            );
+
+            match strategy.choice_for_level(&levels, level_idx, segment_ids) {
+                Choice::Move(payload) => move_segments(levels, opts, payload)?,
+                Choice::Merge(payload) => merge_segments(levels, opts, &payload, None)?,
+                _ => {}
+            }
+
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes all items inside a key range from the tree's segments.
+///
+/// Segments fully contained in the key range are dropped without rewriting,
+/// segments that only partially overlap the key range are rewritten without the key range.
+///
+/// Levels are processed bottom-up, so removing a newer version
+/// (or tombstone) of a key can never resurrect an older version.
+///
+/// This will block until the compactor is fully finished.
+pub fn do_drop_range(opts: &Options, strategy: &drop_range::Strategy) -> crate::Result<()> {
+    let last_level = opts
+        .levels
+        .read()
+        .expect("lock is poisoned")
+        .last_level_index();
+
+    for level_idx in (0..=last_level).rev() {
+        let levels = opts.levels.write().expect("lock is poisoned");
+        let choice = strategy.choose_level(&levels, level_idx);
+
+        log::debug!("compactor: drop range choice for L{level_idx}: {choice:?}");
+
+        if let Choice::Drop(payload) = choice {
+            drop_segments(
+                levels,
+                opts,
+                &payload
+                    .into_iter()
+                    .map(|x| (opts.tree_id, x).into())
+                    .collect::<Vec<_>>(),
+            )?;
+        } else {
+            drop(levels);
+        }
+
+        let partially_overlapping = {
+            let levels = opts.levels.read().expect("lock is poisoned");
+
+            levels
+                .levels
+                .get(level_idx as usize)
+                .map(|level| strategy.partially_overlapping(level))
+                .unwrap_or_default()
+        };
+
+        // NOTE: Rewrite each segment on its own, so the created segments
+        // do not overlap with any other segment of the level
+        for segment_id in partially_overlapping {
+            merge_segments(
+                opts.levels.write().expect("lock is poisoned"),
+                opts,
+                &CompactionPayload {
+                    segment_ids: std::iter::once(segment_id).collect(),
+                    dest_level: level_idx,
+                    target_size: u64::MAX,
+                },
+                Some(strategy.key_range()),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
 fn create_compaction_stream<'a>(
     levels: &LevelManifest,
     to_compact: &[SegmentId],
     eviction_seqno: SeqNo,
+    snapshots: Option<&[SeqNo]>,
+    filter: Option<Arc<dyn CompactionFilter>>,
+    rate_limiter: Option<&Arc<RateLimiter>>,
 ) -> crate::Result<Option<CompactionStream<Merger<CompactionReader<'a>>>>> {
     let mut readers: Vec<CompactionReader<'_>> = vec![];
     let mut found = 0;
@@ -127,24 +282,30 @@ fn create_compaction_stream<'a>(
                 continue;
             };
 
-            readers.push(Box::new(LevelScanner::from_indexes(
-                level.clone(),
-                (Some(lo), Some(hi)),
-            )?));
+            readers.push(Box::new(
+                LevelScanner::from_indexes(level.clone(), (Some(lo), Some(hi)))?
+                    .use_rate_limiter(rate_limiter.cloned()),
+            ));
 
             found += hi - lo + 1;
         } else {
             for &id in to_compact {
                 if let Some(segment) = level.segments.iter().find(|x| x.id() == id) {
                     found += 1;
-                    readers.push(Box::new(segment.scan()?));
+                    readers.push(Box::new(
+                        segment.scan()?.use_rate_limiter(rate_limiter.cloned()),
+                    ));
                 }
             }
         }
     }
 
     Ok(if found == to_compact.len() {
-        Some(CompactionStream::new(Merger::new(readers), eviction_seqno))
+        Some(
+            CompactionStream::new(Merger::new(readers), eviction_seqno)
+                .with_snapshots(snapshots)
+                .with_filter(filter),
+        )
     } else {
         None
     })
@@ -178,11 +339,310 @@ fn move_segments(
     })
 }
 
+/// Options for writing a sorted run of segments
+struct RunOptions<'a> {
+    config: &'a Config,
+    segments_base_folder: &'a Path,
+    segment_id_generator: &'a Arc<AtomicU64>,
+    stop_signal: &'a StopSignal,
+    handle: &'a CompactionHandle,
+    payload: &'a CompactionPayload,
+
+    /// Creation time of the newest data in the input segments
+    data_created_at: Option<u128>,
+
+    /// Evicts tombstones, because there is no data beneath the destination level
+    is_last_level: bool,
+
+    /// Zeroes the seqnos of versions below the eviction seqno
+    zero_seqnos: bool,
+
+    /// Removes all items inside this key range
+    drop_range: Option<&'a KeyRange>,
+
+    /// Relocates blobs out of fragmented blob files
+    blob_relocation: Option<&'a Arc<BlobRelocation>>,
+}
+
+/// Wraps an error that occurred while compacting the payload's segments.
+fn compaction_error(
+    payload: &CompactionPayload,
+    phase: CompactionPhase,
+    source: crate::Error,
+) -> crate::Error {
+    let mut segment_ids = payload.segment_ids.iter().copied().collect::<Vec<_>>();
+    segment_ids.sort_unstable();
+
+    log::error!("Compaction of segments {segment_ids:?} failed in {phase} phase: {source:?}");
+
+    CompactionError {
+        segment_ids,
+        phase,
+        source: Box::new(source),
+    }
+    .into()
+}
+
+/// Deletes the segments written by a failed run, and wraps the error.
+fn abort_run(
+    opts: &RunOptions<'_>,
+    segment_writer: MultiWriter,
+    phase: CompactionPhase,
+    source: crate::Error,
+) -> crate::Error {
+    if let Err(e) = segment_writer.abort() {
+        log::error!("Failed to delete segments of failed compaction: {e:?}");
+    }
+
+    compaction_error(opts.payload, phase, source)
+}
+
+/// Writes a merge stream into a sorted run of segments.
+///
+/// Returns `None` if the compaction was stopped by the stop signal or cancelled,
+/// in which case the segments written so far are deleted.
+fn write_run<I: Iterator<Item = crate::Result<InternalValue>>>(
+    opts: &RunOptions<'_>,
+    mut merge_iter: CompactionStream<I>,
+) -> crate::Result<Option<Vec<SegmentId>>> {
+    let mut segment_writer = MultiWriter::new(
+        opts.segments_base_folder.to_path_buf(),
+        opts.segment_id_generator.clone(),
+        opts.payload.target_size,
+    )
+    .map_err(|e| compaction_error(opts.payload, CompactionPhase::Write, e))?
+    .use_compression(opts.config.level_compression(opts.payload.dest_level))
+    .use_dictionary_size(opts.config.zstd_dictionary_size)
+    .use_data_created_at(opts.data_created_at)
+    .use_rate_limiter(opts.config.rate_limiter.clone(), IoPriority::Low)
+    .use_data_block_size(opts.config.level_data_block_size(opts.payload.dest_level))
+    .use_index_block_size(opts.config.level_index_block_size(opts.payload.dest_level))
+    .use_bloom_policy(opts.config.level_bloom_policy(opts.payload.dest_level))
+    .use_partitioner(opts.config.segment_partitioner.clone());
+
+    let mut blob_writer = None;
+    let mut segments_reported = 0;
+    let mut tombstones_dropped = 0;
+
+    // NOTE: User key and seqno of the last version removed by the drop range
+    let mut last_dropped: Option<(UserKey, SeqNo)> = None;
+
+    for (idx, item) in merge_iter.by_ref().enumerate() {
+        let item = match item {
+            Ok(item) => item,
+            Err(e) => return Err(abort_run(opts, segment_writer, CompactionPhase::Read, e)),
+        };
+
+        // NOTE: Poll for cancellation and report progress every once in a while
+        if idx % 1_000 == 0 {
+            if opts.stop_signal.is_stopped() || opts.handle.is_cancelled() {
+                segment_writer.abort()?;
+                return Ok(None);
+            }
+
+            if idx > 0 {
+                opts.handle.add_items_processed(1_000);
+            }
+
+            let segment_count = segment_writer.segment_count();
+            opts.handle
+                .add_segments_written(segment_count - segments_reported);
+            segments_reported = segment_count;
+        }
+
+        // NOTE: All versions of the key are removed, so nothing can be resurrected
+        if opts
+            .drop_range
+            .is_some_and(|range| range.contains_key(&item.key.user_key))
+        {
+            if let Some(blob_relocation) = opts.blob_relocation {
+                let newer_seqno = last_dropped
+                    .as_ref()
+                    .filter(|(key, _)| *key == item.key.user_key)
+                    .map(|(_, seqno)| *seqno);
+
+                blob_relocation.on_drop(&item, newer_seqno);
+                last_dropped = Some((item.key.user_key.clone(), item.key.seqno));
+            }
+            continue;
+        }
+
+        // IMPORTANT: We can only drop tombstones when writing into last level
+        if opts.is_last_level && item.is_tombstone() {
+            tombstones_dropped += 1;
+            continue;
+        }
+
+        let item = match opts.blob_relocation {
+            Some(blob_relocation) => match blob_relocation.relocate(&mut blob_writer, item) {
+                Ok(item) => item,
+                Err(e) => return Err(abort_run(opts, segment_writer, CompactionPhase::Write, e)),
+            },
+            None => item,
+        };
+
+        if let Err(e) = segment_writer.write(item) {
+            return Err(abort_run(opts, segment_writer, CompactionPhase::Write, e));
+        }
+    }
+
+    let segment_ids = segment_writer
+        .finish()
+        .map_err(|e| compaction_error(opts.payload, CompactionPhase::Write, e))?;
+    opts.handle
+        .add_segments_written(segment_ids.len().saturating_sub(segments_reported));
+    opts.handle.add_dropped(
+        merge_iter.tombstones_dropped() + tombstones_dropped,
+        merge_iter.versions_dropped(),
+    );
+
+    if let (Some(blob_relocation), Some(blob_writer)) = (opts.blob_relocation, blob_writer) {
+        blob_relocation.add_writer(blob_writer);
+    }
+
+    Ok(Some(segment_ids))
+}
+
+/// Splits the key space of the input segments into disjoint key ranges (subcompactions),
+/// using the data block boundaries of the input segments as split points.
+///
+/// Every subcompaction should at least fill one segment of the target size.
+fn split_into_subranges(
+    segments: &[Segment],
+    max_subcompactions: usize,
+    target_size: u64,
+) -> Vec<(Bound<UserKey>, Bound<UserKey>)> {
+    let unbounded = vec![(Bound::Unbounded, Bound::Unbounded)];
+
+    if max_subcompactions <= 1 {
+        return unbounded;
+    }
+
+    let input_size = segments
+        .iter()
+        .map(|segment| segment.metadata.file_size)
+        .sum::<u64>();
+
+    let full_segments = usize::try_from(input_size / target_size.max(1)).unwrap_or(usize::MAX);
+
+    let mut boundaries = segments
+        .iter()
+        .flat_map(Segment::data_block_end_keys)
+        .collect::<Vec<_>>();
+
+    boundaries.sort();
+    boundaries.dedup();
+
+    // NOTE: Splitting after the highest key would create an empty key range
+    boundaries.pop();
+
+    let count = max_subcompactions
+        .min(full_segments)
+        .min(boundaries.len() + 1);
+
+    if count <= 1 {
+        return unbounded;
+    }
+
+    let mut subranges = Vec::with_capacity(count);
+    let mut lo = Bound::Unbounded;
+
+    for idx in 1..count {
+        // NOTE: Split after a block's end key, so blocks are usually not read by two subcompactions
+        // (unless the versions of the split key continue in the next block)
+        #[allow(clippy::expect_used)]
+        let split_key = boundaries
+            .get(idx * boundaries.len() / count)
+            .expect("should exist");
+
+        subranges.push((lo, Bound::Included(split_key.clone())));
+        lo = Bound::Excluded(split_key.clone());
+    }
+
+    subranges.push((lo, Bound::Unbounded));
+
+    subranges
+}
+
+/// Compacts the subranges of the input segments in parallel, one thread per subrange.
+///
+/// Returns the created segments, ordered by key range.
+fn run_subcompactions(
+    opts: &RunOptions<'_>,
+    segments: &[Segment],
+    subranges: Vec<(Bound<UserKey>, Bound<UserKey>)>,
+    eviction_seqno: SeqNo,
+    snapshots: Option<&[SeqNo]>,
+    filter: Option<&Arc<dyn CompactionFilter>>,
+) -> crate::Result<Option<Vec<SegmentId>>> {
+    log::debug!(
+        "Splitting compaction into {} subcompactions",
+        subranges.len()
+    );
+
+    std::thread::scope(|scope| {
+        let handles = subranges
+            .into_iter()
+            .map(|range| {
+                let filter = filter.cloned();
+
+                scope.spawn(move || {
+                    let readers = segments
+                        .iter()
+                        .filter(|segment| segment.metadata.key_range.overlaps_with_bounds(&range))
+                        .map(|segment| {
+                            segment.scan_range(range.clone(), opts.config.rate_limiter.clone())
+                        })
+                        .collect::<crate::Result<Vec<_>>>()
+                        .map_err(|e| compaction_error(opts.payload, CompactionPhase::Read, e))?;
+
+                    let merge_iter = CompactionStream::new(Merger::new(readers), eviction_seqno)
+                        .with_snapshots(snapshots)
+                        .with_filter(filter)
+                        .with_zero_seqnos(opts.zero_seqnos)
+                        .with_drop_observer(
+                            opts.blob_relocation
+                                .map(|x| x.clone() as Arc<dyn DropObserver>),
+                        );
+
+                    write_run(opts, merge_iter)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut writer_results = vec![];
+        let mut is_stopped = false;
+        let mut error = None;
+
+        for handle in handles {
+            #[allow(clippy::expect_used)]
+            match handle.join().expect("subcompaction should not panic") {
+                Ok(Some(segment_ids)) => writer_results.extend(segment_ids),
+                Ok(None) => is_stopped = true,
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
+        }
+
+        if is_stopped || error.is_some() {
+            // NOTE: Other subcompactions may have already finished,
+            // so we need to delete their segments (failures are logged)
+            let _ = remove_segment_files(opts.segments_base_folder, writer_results.iter().copied());
+
+            return error.map_or(Ok(None), Err);
+        }
+
+        Ok(Some(writer_results))
+    })
+}
+
 #[allow(clippy::too_many_lines)]
 fn merge_segments(
     mut levels: RwLockWriteGuard<'_, LevelManifest>,
     opts: &Options,
     payload: &CompactionPayload,
+    drop_range: Option<&KeyRange>,
 ) -> crate::Result<()> {
     if opts.stop_signal.is_stopped() {
         log::debug!("Stopping before compaction because of stop signal");
@@ -217,23 +677,74 @@ fn merge_segments(
         "Compacting segments {:?} into L{}, compression={}, mvcc_gc_watermark={}",
         payload.segment_ids,
         payload.dest_level,
-        opts.config.compression,
+        opts.config.level_compression(payload.dest_level),
         opts.eviction_seqno,
     );
 
-    let Some(merge_iter) = create_compaction_stream(
-        &levels,
-        &payload.segment_ids.iter().copied().collect::<Vec<_>>(),
-        opts.eviction_seqno,
-    )?
-    else {
-        log::warn!(
-            "Compaction task tried to compact segments that do not exist, declining to run it"
-        );
-        return Ok(());
+    // NOTE: Blob trees store value handles in the index tree, which are meaningless to the filter
+    let filter = match opts.config.tree_type {
+        TreeType::Standard => opts.config.compaction_filter.clone(),
+        TreeType::Blob => None,
+    };
+
+    // NOTE: Only index trees of blob trees have a value log
+    let blob_relocation = opts
+        .value_log
+        .clone()
+        .zip(opts.config.blob_file_relocation_threshold)
+        .map(|(value_log, threshold)| Arc::new(BlobRelocation::new(value_log, threshold)));
+
+    let subranges = split_into_subranges(
+        &segments,
+        opts.config.max_subcompactions,
+        payload.target_size,
+    );
+
+    let merge_iter = if subranges.len() > 1 {
+        None
+    } else {
+        let Some(merge_iter) = create_compaction_stream(
+            &levels,
+            &payload.segment_ids.iter().copied().collect::<Vec<_>>(),
+            opts.eviction_seqno,
+            opts.snapshots.as_deref(),
+            filter.clone(),
+            opts.config.rate_limiter.as_ref(),
+        )
+        .map_err(|e| compaction_error(payload, CompactionPhase::Read, e))?
+        else {
+            log::warn!(
+                "Compaction task tried to compact segments that do not exist, declining to run it"
+            );
+            return Ok(());
+        };
+
+        Some(
+            merge_iter
+                .with_drop_observer(blob_relocation.clone().map(|x| x as Arc<dyn DropObserver>)),
+        )
     };
 
     let last_level = levels.last_level_index();
+    let is_last_level = payload.dest_level == last_level;
+
+    // NOTE: Zeroed seqnos cannot be ordered against other versions of the same key,
+    // so all overlapping segments of the last level need to be part of the compaction
+    let zero_seqnos = is_last_level && {
+        let key_range = KeyRange::aggregate(segments.iter().map(|x| &x.metadata.key_range));
+
+        levels
+            .levels
+            .get(usize::from(last_level))
+            .is_some_and(|level| {
+                level
+                    .segments
+                    .iter()
+                    .filter(|x| x.metadata.key_range.overlaps_with_key_range(&key_range))
+                    .all(|x| payload.segment_ids.contains(&x.id()))
+            })
+    };
+    let merge_iter = merge_iter.map(|x| x.with_zero_seqnos(zero_seqnos));
 
     levels.hide_segments(payload.segment_ids.iter().copied());
 
@@ -241,105 +752,82 @@ fn merge_segments(
     // does not block possible other compactions and reads
     drop(levels);
 
-    // NOTE: Only evict tombstones when reaching the last level,
-    // That way we don't resurrect data beneath the tombstone
-    let is_last_level = payload.dest_level == last_level;
-
     let start = Instant::now();
 
-    let Ok(segment_writer) = MultiWriter::new(
-        segments_base_folder.clone(),
-        opts.segment_id_generator.clone(),
-        payload.target_size,
-        /* opts.segment_id_generator.clone(),
-        payload.target_size,
-        crate::segment::writer::Options {
-            folder: segments_base_folder.clone(),
-            segment_id: 0, // TODO: this is never used in MultiWriter
-            data_block_size: opts.config.data_block_size,
-            index_block_size: opts.config.index_block_size,
-        }, */
-    ) else {
-        log::error!("Compaction failed");
-
-        // IMPORTANT: Show the segments again, because compaction failed
-        opts.levels
-            .write()
-            .expect("lock is poisoned")
-            .show_segments(payload.segment_ids.iter().copied());
+    let handle = CompactionHandle::new(
+        payload.segment_ids.clone(),
+        payload.dest_level,
+        segments.iter().map(|x| x.metadata.file_size).sum(),
+        segments.iter().map(|x| x.metadata.item_count).sum(),
+    );
+    let _registration = opts.active_compactions.register(handle.clone());
+
+    let run_options = RunOptions {
+        config: &opts.config,
+        segments_base_folder: &segments_base_folder,
+        segment_id_generator: &opts.segment_id_generator,
+        stop_signal: &opts.stop_signal,
+        handle: &handle,
+        payload,
+        data_created_at: segments
+            .iter()
+            .map(|segment| *segment.metadata.data_created_at)
+            .max(),
+
+        // NOTE: Only evict tombstones when reaching the last level,
+        // That way we don't resurrect data beneath the tombstone
+        is_last_level,
+        zero_seqnos,
+
+        drop_range,
+        blob_relocation: blob_relocation.as_ref(),
+    };
 
-        return Ok(());
+    let writer_results = match merge_iter {
+        Some(merge_iter) => write_run(&run_options, merge_iter),
+        None => run_subcompactions(
+            &run_options,
+            &segments,
+            subranges,
+            opts.eviction_seqno,
+            opts.snapshots.as_deref(),
+            filter.as_ref(),
+        ),
     };
 
-    let mut segment_writer = segment_writer
-        .use_compression(opts.config.compression)
-        .use_data_block_size(opts.config.data_block_size)
-        .use_bloom_policy({
-            use crate::segment::filter::BloomConstructionPolicy;
-
-            if opts.config.bloom_bits_per_key >= 0 {
-                // NOTE: Apply some MONKEY to have very high FPR on small levels
-                // because it's cheap
-                //
-                // See https://nivdayan.github.io/monkeykeyvaluestore.pdf
-                match payload.dest_level {
-                    0 => BloomConstructionPolicy::FpRate(0.00001),
-                    1 => BloomConstructionPolicy::FpRate(0.0005),
-                    _ => BloomConstructionPolicy::BitsPerKey(
-                        opts.config.bloom_bits_per_key.unsigned_abs(),
-                    ),
-                }
+    // NOTE: All runs have finished, so their blob writers have been dropped or added
+    if !matches!(writer_results, Ok(Some(_))) {
+        if let Some(blob_relocation) = &blob_relocation {
+            blob_relocation.abort();
+        }
+    }
+
+    let writer_results = match writer_results {
+        Ok(Some(writer_results)) => writer_results,
+        Ok(None) => {
+            if handle.is_cancelled() {
+                log::debug!("compactor: compaction was cancelled");
             } else {
-                BloomConstructionPolicy::BitsPerKey(0)
+                log::debug!("compactor: stopping amidst compaction because of stop signal");
             }
-        });
-
-    for (idx, item) in merge_iter.enumerate() {
-        let Ok(item) = item else {
-            log::error!("Compaction failed");
 
-            // IMPORTANT: Show the segments again, because compaction failed
+            // IMPORTANT: Show the segments again, so they can be compacted later
             opts.levels
                 .write()
                 .expect("lock is poisoned")
                 .show_segments(payload.segment_ids.iter().copied());
 
             return Ok(());
-        };
-
-        // IMPORTANT: We can only drop tombstones when writing into last level
-        if is_last_level && item.is_tombstone() {
-            continue;
         }
-
-        if segment_writer.write(item).is_err() {
-            log::error!("Compaction failed");
-
+        Err(e) => {
             // IMPORTANT: Show the segments again, because compaction failed
             opts.levels
                 .write()
                 .expect("lock is poisoned")
                 .show_segments(payload.segment_ids.iter().copied());
 
-            return Ok(());
-        };
-
-        if idx % 1_000_000 == 0 && opts.stop_signal.is_stopped() {
-            log::debug!("compactor: stopping amidst compaction because of stop signal");
-            return Ok(());
+            return Err(e);
         }
-    }
-
-    let Ok(writer_results) = segment_writer.finish() else {
-        log::error!("Compaction failed");
-
-        // IMPORTANT: Show the segments again, because compaction failed
-        opts.levels
-            .write()
-            .expect("lock is poisoned")
-            .show_segments(payload.segment_ids.iter().copied());
-
-        return Ok(());
     };
 
     log::debug!(
@@ -348,9 +836,34 @@ fn merge_segments(
         writer_results.len(),
     );
 
-    let Ok(created_segments) = writer_results
-        .into_iter()
-        .map(|segment_id| -> crate::Result<Segment> {
+    // IMPORTANT: Relocated blobs need to be registered before the segments pointing to them
+    let pending_blob_files = match blob_relocation
+        .as_ref()
+        .map(|x| x.register_blob_files())
+        .transpose()
+    {
+        Ok(guard) => guard.flatten(),
+        Err(e) => {
+            // NOTE: Failures are logged, the compaction error takes precedence
+            let _ = remove_segment_files(&segments_base_folder, writer_results.iter().copied());
+
+            if let Some(blob_relocation) = &blob_relocation {
+                blob_relocation.abort();
+            }
+
+            // IMPORTANT: Show the segments again, because compaction failed
+            opts.levels
+                .write()
+                .expect("lock is poisoned")
+                .show_segments(payload.segment_ids.iter().copied());
+
+            return Err(compaction_error(payload, CompactionPhase::Write, e));
+        }
+    };
+
+    let created_segments = writer_results
+        .iter()
+        .map(|&segment_id| -> crate::Result<Segment> {
             let segment_file_path = segments_base_folder.join(segment_id.to_string());
 
             Segment::recover(
@@ -358,6 +871,7 @@ fn merge_segments(
                 opts.tree_id,
                 opts.config.cache.clone(),
                 opts.config.descriptor_table.clone(),
+                opts.config.verify_cache_hits,
             )
 
             /* let segment_id = trailer.metadata.id;
@@ -409,17 +923,22 @@ fn merge_segments(
             }
             .into()) */
         })
-        .collect::<crate::Result<Vec<_>>>()
-    else {
-        log::error!("Compaction failed");
+        .collect::<crate::Result<Vec<_>>>();
 
-        // IMPORTANT: Show the segments again, because compaction failed
-        opts.levels
-            .write()
-            .expect("lock is poisoned")
-            .show_segments(payload.segment_ids.iter().copied());
+    let created_segments = match created_segments {
+        Ok(created_segments) => created_segments,
+        Err(e) => {
+            // NOTE: Failures are logged, the compaction error takes precedence
+            let _ = remove_segment_files(&segments_base_folder, writer_results.iter().copied());
 
-        return Ok(());
+            // IMPORTANT: Show the segments again, because compaction failed
+            opts.levels
+                .write()
+                .expect("lock is poisoned")
+                .show_segments(payload.segment_ids.iter().copied());
+
+            return Err(compaction_error(payload, CompactionPhase::Load, e));
+        }
     };
 
     // NOTE: Mind lock order L -> M -> S
@@ -451,7 +970,12 @@ fn merge_segments(
     if let Err(e) = swap_result {
         // IMPORTANT: Show the segments again, because compaction failed
         levels.show_segments(payload.segment_ids.iter().copied());
-        return Err(e);
+
+        for segment in created_segments {
+            segment.mark_as_deleted();
+        }
+
+        return Err(compaction_error(payload, CompactionPhase::Manifest, e));
     }
 
     // NOTE: If the application were to crash >here< it's fine
@@ -461,8 +985,26 @@ fn merge_segments(
         segment.mark_as_deleted();
     }
 
+    // NOTE: Still holding the levels lock, so no GC scan can see the new segments yet
+    if let Some(blob_relocation) = &blob_relocation {
+        blob_relocation.apply_garbage();
+    }
+
     levels.show_segments(payload.segment_ids.iter().copied());
     drop(levels);
+    drop(pending_blob_files);
+
+    opts.stats.record_compaction(
+        payload.dest_level,
+        &LevelStats {
+            bytes_read: handle.input_bytes(),
+            bytes_written: created_segments.iter().map(|x| x.metadata.file_size).sum(),
+            compactions: 1,
+            time_spent: start.elapsed(),
+            tombstones_dropped: handle.tombstones_dropped(),
+            versions_dropped: handle.versions_dropped(),
+        },
+    );
 
     log::trace!("Compaction successful");
 
@@ -549,4 +1091,55 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn compaction_split_into_subranges() -> crate::Result<()> {
+        use super::split_into_subranges;
+        use std::ops::Bound;
+
+        let folder = tempfile::tempdir()?;
+
+        let tree = crate::Config::new(folder).data_block_size(1_024).open()?;
+
+        for idx in 0..1_000 {
+            tree.insert(format!("{idx:0>6}"), "a".repeat(100), 0);
+        }
+        tree.flush_active_memtable(0)?;
+
+        let segments = tree
+            .levels
+            .read()
+            .expect("lock is poisoned")
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let input_size = segments.first().expect("should exist").metadata.file_size;
+
+        assert_eq!(1, split_into_subranges(&segments, 1, 1_024).len());
+        assert_eq!(1, split_into_subranges(&segments, 4, input_size).len());
+
+        let subranges = split_into_subranges(&segments, 4, 1_024);
+        assert_eq!(4, subranges.len());
+        assert_eq!(Bound::Unbounded, subranges.first().expect("should exist").0);
+        assert_eq!(Bound::Unbounded, subranges.last().expect("should exist").1);
+
+        for pair in subranges.windows(2) {
+            let (Bound::Included(hi), Bound::Excluded(lo)) = (&pair[0].1, &pair[1].0) else {
+                panic!("subranges should be adjacent");
+            };
+            assert_eq!(hi, lo);
+        }
+
+        // NOTE: Every item is returned by exactly one subrange
+        let segment = segments.first().expect("should exist");
+        let mut count = 0;
+
+        for range in subranges {
+            count += segment.scan_range(range, None)?.count();
+        }
+        assert_eq!(1_000, count);
+
+        Ok(())
+    }
 }
@@ -2,7 +2,10 @@
 // This source code is licensed under both the Apache 2.0 and MIT License
 // (found in the LICENSE-* files in the repository)
 
-use crate::coding::{Decode, DecodeError, Encode, EncodeError};
+use crate::{
+    coding::{Decode, DecodeError, Encode, EncodeError},
+    Slice,
+};
 use byteorder::{ReadBytesExt, WriteBytesExt};
 use std::io::{Read, Write};
 
@@ -33,6 +36,19 @@ pub enum CompressionType {
     /// - 10 may save even more space than 9, but the speed trade off may not be worth it
     #[cfg(feature = "miniz")]
     Miniz(u8),
+
+    /// Zstandard compression
+    ///
+    /// Compression level (1-22) can be adjusted.
+    ///
+    /// - 1 optimizes for speed
+    /// - 3 is a good default
+    /// - 19+ optimizes for space, at a much higher CPU cost
+    ///
+    /// Small data blocks compress much better using a trained dictionary,
+    /// see `Config::zstd_dictionary_size`.
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
 }
 
 impl Encode for CompressionType {
@@ -56,6 +72,17 @@ impl Encode for CompressionType {
                 writer.write_u8(2)?;
                 writer.write_u8(*level)?;
             }
+
+            #[cfg(feature = "zstd")]
+            Self::Zstd(level) => {
+                assert!((1..=22).contains(level), "invalid zstd compression level");
+
+                writer.write_u8(3)?;
+
+                // NOTE: We just asserted the level is in 1..=22
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                writer.write_u8(*level as u8)?;
+            }
         };
 
         Ok(())
@@ -87,6 +114,15 @@ impl Decode for CompressionType {
                 Ok(Self::Miniz(level))
             }
 
+            #[cfg(feature = "zstd")]
+            3 => {
+                let level = i32::from(reader.read_u8()?);
+
+                assert!((1..=22).contains(&level), "invalid zstd compression level");
+
+                Ok(Self::Zstd(level))
+            }
+
             tag => Err(DecodeError::InvalidTag(("CompressionType", tag))),
         }
     }
@@ -105,11 +141,98 @@ impl std::fmt::Display for CompressionType {
 
                 #[cfg(feature = "miniz")]
                 Self::Miniz(_) => "miniz",
+
+                #[cfg(feature = "zstd")]
+                Self::Zstd(_) => "zstd",
             }
         )
     }
 }
 
+/// Compression dictionary, trained from the data blocks of a segment
+///
+/// Small blocks share little redundancy within themselves, so they compress poorly;
+/// a dictionary provides the common patterns of all blocks of the segment.
+pub struct CompressionDictionary {
+    /// Raw dictionary, as stored in the segment file
+    raw: Slice,
+
+    /// Digested dictionary for compression, only exists when writing a segment
+    #[cfg(feature = "zstd")]
+    encoder: Option<zstd::dict::EncoderDictionary<'static>>,
+
+    /// Digested dictionary for decompression
+    #[cfg(feature = "zstd")]
+    decoder: zstd::dict::DecoderDictionary<'static>,
+}
+
+impl CompressionDictionary {
+    /// Loads a dictionary for decompressing blocks.
+    pub fn new(raw: Slice) -> Self {
+        Self {
+            #[cfg(feature = "zstd")]
+            encoder: None,
+
+            #[cfg(feature = "zstd")]
+            decoder: zstd::dict::DecoderDictionary::copy(&raw),
+
+            raw,
+        }
+    }
+
+    /// Trains a dictionary of at most `max_size` bytes from sample blocks.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if there are not enough samples to train a dictionary.
+    #[cfg(feature = "zstd")]
+    pub fn train<S: AsRef<[u8]>>(
+        samples: &[S],
+        max_size: usize,
+        level: i32,
+    ) -> crate::Result<Self> {
+        let raw = zstd::dict::from_samples(samples, max_size)?;
+
+        Ok(Self {
+            encoder: Some(zstd::dict::EncoderDictionary::copy(&raw, level)),
+            decoder: zstd::dict::DecoderDictionary::copy(&raw),
+            raw: raw.into(),
+        })
+    }
+
+    /// Returns the raw dictionary.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.raw
+    }
+
+    /// Compresses a block using the dictionary.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the dictionary was not trained, but loaded from disk.
+    #[cfg(feature = "zstd")]
+    pub(crate) fn compress(&self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        #[allow(clippy::expect_used)]
+        let encoder = self
+            .encoder
+            .as_ref()
+            .expect("dictionary should be trained for compression");
+
+        zstd::bulk::Compressor::with_prepared_dictionary(encoder)?.compress(data)
+    }
+
+    /// Decompresses a block of `uncompressed_length` bytes using the dictionary.
+    #[cfg(feature = "zstd")]
+    pub(crate) fn decompress(
+        &self,
+        data: &[u8],
+        uncompressed_length: usize,
+    ) -> std::io::Result<Vec<u8>> {
+        zstd::bulk::Decompressor::with_prepared_dictionary(&self.decoder)?
+            .decompress(data, uncompressed_length)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -146,4 +269,43 @@ mod tests {
             }
         }
     }
+
+    #[cfg(feature = "zstd")]
+    mod zstd {
+        use super::*;
+        use test_log::test;
+
+        #[test]
+        fn compression_serialize_zstd() -> Result<(), DecodeError> {
+            for lvl in 1..=22 {
+                let serialized = CompressionType::Zstd(lvl).encode_into_vec();
+                assert_eq!(2, serialized.len());
+
+                let deserialized = CompressionType::decode_from(&mut &serialized[..])?;
+                assert_eq!(CompressionType::Zstd(lvl), deserialized);
+            }
+
+            Ok(())
+        }
+
+        #[test]
+        fn compression_dictionary_roundtrip() -> crate::Result<()> {
+            let samples = (0..1_000u32)
+                .map(|x| format!("user#{x:0>8}#name=user-{x}#email=user-{x}@example.com"))
+                .collect::<Vec<_>>();
+
+            let dictionary = CompressionDictionary::train(&samples, 4_096, 3)?;
+
+            let data = samples.concat();
+            let compressed = dictionary.compress(data.as_bytes())?;
+            assert!(compressed.len() < data.len());
+
+            // NOTE: A dictionary loaded from disk can only decompress
+            let loaded = CompressionDictionary::new(dictionary.as_bytes().into());
+            let decompressed = loaded.decompress(&compressed, data.len())?;
+            assert_eq!(data.as_bytes(), decompressed);
+
+            Ok(())
+        }
+    }
 }
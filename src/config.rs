@@ -2,10 +2,16 @@
 // This source code is licensed under both the Apache 2.0 and MIT License
 // (found in the LICENSE-* files in the repository)
 
-use crate::{path::absolute_path, BlobTree, CompressionType, Cache, DescriptorTable, Tree};
+use crate::{
+    compaction::{CompactionFilter, SegmentPartitioner},
+    path::absolute_path, segment::filter::BloomConstructionPolicy,
+    BlobTree, Cache, CompressionType, DescriptorTable, MemtableType, RateLimiter, Tree,
+    WriteBufferManager,
+};
 use std::{
     path::{Path, PathBuf},
     sync::Arc,
+    time::Duration,
 };
 
 /// LSM-tree type
@@ -41,6 +47,70 @@ impl TryFrom<u8> for TreeType {
 
 const DEFAULT_FILE_FOLDER: &str = ".lsm.data";
 
+/// Segment options of a single level
+///
+/// Options that are not set fall back to the tree-wide configuration.
+#[derive(Clone, Debug, Default)]
+pub struct LevelOptions {
+    /// What type of compression is used
+    pub compression: Option<CompressionType>,
+
+    /// How bloom filters are constructed
+    pub bloom_policy: Option<BloomConstructionPolicy>,
+
+    /// Block size of data blocks
+    pub data_block_size: Option<u32>,
+
+    /// Block size of index blocks
+    pub index_block_size: Option<u32>,
+}
+
+impl LevelOptions {
+    /// Sets the compression method of the level.
+    #[must_use]
+    pub fn compression(mut self, compression: CompressionType) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    /// Sets the bloom filter construction policy of the level.
+    ///
+    /// Use `BloomConstructionPolicy::BitsPerKey(0)` to disable bloom filters.
+    #[must_use]
+    pub fn bloom_policy(mut self, policy: BloomConstructionPolicy) -> Self {
+        self.bloom_policy = Some(policy);
+        self
+    }
+
+    /// Sets the data block size of the level.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the block size is smaller than 1 KiB or larger than 512 KiB.
+    #[must_use]
+    pub fn data_block_size(mut self, block_size: u32) -> Self {
+        assert!(block_size >= 1_024);
+        assert!(block_size <= 512 * 1_024);
+
+        self.data_block_size = Some(block_size);
+        self
+    }
+
+    /// Sets the index block size of the level.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the block size is smaller than 1 KiB or larger than 512 KiB.
+    #[must_use]
+    pub fn index_block_size(mut self, block_size: u32) -> Self {
+        assert!(block_size >= 1_024);
+        assert!(block_size <= 512 * 1_024);
+
+        self.index_block_size = Some(block_size);
+        self
+    }
+}
+
 #[derive(Clone)]
 /// Tree configuration builder
 pub struct Config {
@@ -58,6 +128,9 @@ pub struct Config {
     /// What type of compression is used for blobs
     pub blob_compression: CompressionType,
 
+    /// Maximum size of the compression dictionary trained per segment
+    pub zstd_dictionary_size: u32,
+
     // /// Table type (unused)
     // #[allow(unused)]
     // pub(crate) table_type: TableType,
@@ -80,6 +153,9 @@ pub struct Config {
     #[doc(hidden)]
     pub cache: Arc<Cache>,
 
+    /// Whether to verify the checksums of blocks that are served from the cache
+    pub verify_cache_hits: bool,
+
     /// Blob file (value log segment) target size in bytes
     #[doc(hidden)]
     pub blob_file_target_size: u64,
@@ -88,9 +164,44 @@ pub struct Config {
     #[doc(hidden)]
     pub blob_file_separation_threshold: u32,
 
+    /// Stale ratio above which compactions relocate blobs out of a blob file
+    #[doc(hidden)]
+    pub blob_file_relocation_threshold: Option<f32>,
+
     /// Descriptor table to use
     #[doc(hidden)]
     pub descriptor_table: Arc<DescriptorTable>,
+
+    /// In-memory representation of memtables
+    pub memtable_type: MemtableType,
+
+    /// Write buffer manager to use
+    #[doc(hidden)]
+    pub write_buffer_manager: Option<Arc<WriteBufferManager>>,
+
+    /// Compaction filter to use
+    #[doc(hidden)]
+    pub compaction_filter: Option<Arc<dyn CompactionFilter>>,
+
+    /// Segment partitioner to use
+    #[doc(hidden)]
+    pub segment_partitioner: Option<Arc<dyn SegmentPartitioner>>,
+
+    /// Maximum amount of threads a single compaction may use
+    pub max_subcompactions: usize,
+
+    /// Rate limiter for flush & compaction I/O
+    #[doc(hidden)]
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+
+    /// Amount of times a failed compaction is retried
+    pub compaction_max_retries: u32,
+
+    /// Initial backoff between compaction retries
+    pub compaction_retry_backoff: Duration,
+
+    /// Per-level segment options, indexed by level
+    pub level_options: Vec<LevelOptions>,
 }
 
 impl Default for Config {
@@ -100,6 +211,7 @@ impl Default for Config {
             descriptor_table: Arc::new(DescriptorTable::new(256)),
 
             cache: Arc::new(Cache::with_capacity_bytes(/* 16 MiB */ 16 * 1_024 * 1_024)),
+            verify_cache_hits: false,
 
             data_block_size: /* 4 KiB */ 4_096,
             index_block_size: /* 4 KiB */ 4_096,
@@ -108,10 +220,27 @@ impl Default for Config {
             // table_type: TableType::Block,
             compression: CompressionType::None,
             blob_compression: CompressionType::None,
+            zstd_dictionary_size: 0,
             bloom_bits_per_key: 10,
 
             blob_file_target_size: /* 64 MiB */ 64 * 1_024 * 1_024,
             blob_file_separation_threshold: /* 4 KiB */ 4 * 1_024,
+            blob_file_relocation_threshold: None,
+
+            memtable_type: MemtableType::SkipList,
+
+            write_buffer_manager: None,
+            compaction_filter: None,
+            segment_partitioner: None,
+
+            max_subcompactions: 1,
+
+            rate_limiter: None,
+
+            compaction_max_retries: 0,
+            compaction_retry_backoff: Duration::from_millis(100),
+
+            level_options: Vec::new(),
         }
     }
 }
@@ -165,6 +294,21 @@ impl Config {
         self
     }
 
+    /// Sets the maximum size of the compression dictionary
+    /// that is trained from the data blocks of every segment.
+    ///
+    /// Small data blocks compress poorly on their own, a dictionary
+    /// makes the shared patterns of all blocks available to each block.
+    ///
+    /// Only used for levels that use zstd compression.
+    ///
+    /// Default = 0 (no dictionary)
+    #[must_use]
+    pub fn zstd_dictionary_size(mut self, bytes: u32) -> Self {
+        self.zstd_dictionary_size = bytes;
+        self
+    }
+
     /// Sets the amount of levels of the LSM tree (depth of tree).
     ///
     /// Defaults to 7, like `LevelDB` and `RocksDB`.
@@ -228,6 +372,88 @@ impl Config {
         self
     }
 
+    /// Overrides the segment options of a level.
+    ///
+    /// For example, to skip compression in the hot, small levels
+    /// and compress the last level heavily:
+    ///
+    /// ```
+    /// # let folder = tempfile::tempdir()?;
+    /// # #[cfg(feature = "miniz")]
+    /// # {
+    /// use lsm_tree::{CompressionType, Config, LevelOptions};
+    ///
+    /// let config = Config::new(folder)
+    ///     .compression(CompressionType::Miniz(6))
+    ///     .level_options(0, LevelOptions::default().compression(CompressionType::None))
+    ///     .level_options(1, LevelOptions::default().compression(CompressionType::None))
+    ///     .level_options(6, LevelOptions::default().compression(CompressionType::Miniz(9)));
+    /// # }
+    /// #
+    /// # Ok::<(), lsm_tree::Error>(())
+    /// ```
+    #[must_use]
+    pub fn level_options(mut self, level: u8, options: LevelOptions) -> Self {
+        let idx = usize::from(level);
+
+        if self.level_options.len() <= idx {
+            self.level_options
+                .resize_with(idx + 1, LevelOptions::default);
+        }
+
+        if let Some(slot) = self.level_options.get_mut(idx) {
+            *slot = options;
+        }
+
+        self
+    }
+
+    fn get_level_options(&self, level: u8) -> Option<&LevelOptions> {
+        self.level_options.get(usize::from(level))
+    }
+
+    /// Returns the compression method of the given level.
+    pub(crate) fn level_compression(&self, level: u8) -> CompressionType {
+        self.get_level_options(level)
+            .and_then(|x| x.compression)
+            .unwrap_or(self.compression)
+    }
+
+    /// Returns the data block size of the given level.
+    pub(crate) fn level_data_block_size(&self, level: u8) -> u32 {
+        self.get_level_options(level)
+            .and_then(|x| x.data_block_size)
+            .unwrap_or(self.data_block_size)
+    }
+
+    /// Returns the index block size of the given level.
+    pub(crate) fn level_index_block_size(&self, level: u8) -> u32 {
+        self.get_level_options(level)
+            .and_then(|x| x.index_block_size)
+            .unwrap_or(self.index_block_size)
+    }
+
+    /// Returns the bloom filter construction policy of the given level.
+    pub(crate) fn level_bloom_policy(&self, level: u8) -> BloomConstructionPolicy {
+        if let Some(policy) = self.get_level_options(level).and_then(|x| x.bloom_policy) {
+            return policy;
+        }
+
+        if self.bloom_bits_per_key < 0 {
+            return BloomConstructionPolicy::BitsPerKey(0);
+        }
+
+        // NOTE: Apply some MONKEY to have very high FPR on small levels
+        // because it's cheap
+        //
+        // See https://nivdayan.github.io/monkeykeyvaluestore.pdf
+        match level {
+            0 => BloomConstructionPolicy::FpRate(0.00001),
+            1 => BloomConstructionPolicy::FpRate(0.0005),
+            _ => BloomConstructionPolicy::BitsPerKey(self.bloom_bits_per_key.unsigned_abs()),
+        }
+    }
+
     /// Sets the global cache.
     ///
     /// You can create a global [`Cache`] and share it between multiple
@@ -240,6 +466,19 @@ impl Config {
         self
     }
 
+    /// Sets whether blocks that are served from the cache are checked
+    /// against their checksum, like blocks that are read from disk.
+    ///
+    /// Cached blocks were verified when they were loaded from disk, so verifying
+    /// them again costs CPU time, but also detects in-memory corruption.
+    ///
+    /// Default = false
+    #[must_use]
+    pub fn verify_cache_hits(mut self, enabled: bool) -> Self {
+        self.verify_cache_hits = enabled;
+        self
+    }
+
     /// Sets the target size of blob files.
     ///
     /// Smaller blob files allow more granular garbage collection
@@ -271,6 +510,125 @@ impl Config {
         self
     }
 
+    /// Lets compactions of the index tree relocate blobs out of fragmented blob files.
+    ///
+    /// While merging index segments, values that point into a blob file whose
+    /// ratio of stale blobs is at least `ratio` are copied into new blob files.
+    /// Versions dropped by compactions are counted as stale blobs right away,
+    /// so blob files become fully stale without running a GC scan,
+    /// and can then be freed using `gc_drop_stale`.
+    ///
+    /// Defaults to `None` (disabled).
+    ///
+    /// This option has no effect when not used for opening a blob tree.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ratio is not in `(0.0, 1.0]`.
+    #[must_use]
+    pub fn blob_file_relocation_threshold(mut self, ratio: f32) -> Self {
+        assert!(
+            ratio > 0.0 && ratio <= 1.0,
+            "relocation threshold should be in (0.0, 1.0]"
+        );
+
+        self.blob_file_relocation_threshold = Some(ratio);
+        self
+    }
+
+    /// Sets the in-memory representation of memtables.
+    ///
+    /// [`MemtableType::Vector`] makes writes much cheaper,
+    /// at the cost of slower reads, which is useful for bulk loading.
+    ///
+    /// Defaults to [`MemtableType::SkipList`].
+    #[must_use]
+    pub fn memtable_type(mut self, memtable_type: MemtableType) -> Self {
+        self.memtable_type = memtable_type;
+        self
+    }
+
+    /// Sets the global write buffer manager.
+    ///
+    /// You can create a global [`WriteBufferManager`] and share it between multiple
+    /// trees to cap global memtable memory usage.
+    ///
+    /// Defaults to no write buffer manager.
+    #[must_use]
+    pub fn use_write_buffer_manager(mut self, manager: Arc<WriteBufferManager>) -> Self {
+        self.write_buffer_manager = Some(manager);
+        self
+    }
+
+    /// Sets the compaction filter, which may drop or rewrite items during compaction.
+    ///
+    /// See [`CompactionFilter`] for more information.
+    ///
+    /// Defaults to no compaction filter.
+    #[must_use]
+    pub fn compaction_filter(mut self, filter: Arc<dyn CompactionFilter>) -> Self {
+        self.compaction_filter = Some(filter);
+        self
+    }
+
+    /// Sets the segment partitioner, which forces segment boundaries
+    /// between keys in compaction output.
+    ///
+    /// See [`SegmentPartitioner`] for more information.
+    ///
+    /// Defaults to no segment partitioner.
+    #[must_use]
+    pub fn segment_partitioner(mut self, partitioner: Arc<dyn SegmentPartitioner>) -> Self {
+        self.segment_partitioner = Some(partitioner);
+        self
+    }
+
+    /// Sets the maximum amount of threads a single compaction may use.
+    ///
+    /// Large compactions are split into disjoint key ranges (subcompactions),
+    /// which are compacted in parallel.
+    /// Each subcompaction writes at least one segment of the compaction's target size.
+    ///
+    /// Defaults to 1 (no subcompactions).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is 0.
+    #[must_use]
+    pub fn max_subcompactions(mut self, n: usize) -> Self {
+        assert!(n > 0);
+
+        self.max_subcompactions = n;
+        self
+    }
+
+    /// Sets the rate limiter, which throttles flush & compaction I/O.
+    ///
+    /// You can create a global [`RateLimiter`] and share it between multiple
+    /// trees to cap global background I/O throughput.
+    /// Flushes are prioritized over compactions.
+    ///
+    /// Defaults to no rate limiter.
+    #[must_use]
+    pub fn use_rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Sets how many times a failed compaction is retried, before the error is returned.
+    ///
+    /// The backoff starts at `backoff` and doubles with every retry.
+    /// Because the compaction strategy is consulted again, a retry
+    /// may choose different segments.
+    ///
+    /// Defaults to no retries.
+    #[must_use]
+    pub fn compaction_retries(mut self, max_retries: u32, backoff: Duration) -> Self {
+        self.compaction_max_retries = max_retries;
+        self.compaction_retry_backoff = backoff;
+        self
+    }
+
     #[must_use]
     #[doc(hidden)]
     pub fn descriptor_table(mut self, descriptor_table: Arc<DescriptorTable>) -> Self {
@@ -294,6 +652,7 @@ impl Config {
     /// Will return `Err` if an IO error occurs.
     pub fn open_as_blob_tree(mut self) -> crate::Result<BlobTree> {
         self.tree_type = TreeType::Blob;
+
         BlobTree::open(self)
     }
 }
@@ -5,8 +5,97 @@
 use crate::{
     coding::{DecodeError, EncodeError},
     version::Version,
-    Checksum, CompressionType,
+    Checksum, CompressionType, SegmentId,
 };
+use std::path::PathBuf;
+
+/// Phase of a compaction
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CompactionPhase {
+    /// Reading the input segments
+    Read,
+
+    /// Writing the output segments
+    Write,
+
+    /// Loading the written output segments
+    Load,
+
+    /// Persisting the level manifest
+    Manifest,
+}
+
+impl std::fmt::Display for CompactionPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Read => "read",
+                Self::Write => "write",
+                Self::Load => "load",
+                Self::Manifest => "manifest",
+            }
+        )
+    }
+}
+
+/// Compaction failed
+///
+/// The input segments are left untouched, and the segments
+/// written by the failed compaction are deleted.
+#[derive(Debug)]
+pub struct CompactionError {
+    /// Input segments of the failed compaction
+    pub segment_ids: Vec<SegmentId>,
+
+    /// Phase the compaction failed in
+    pub phase: CompactionPhase,
+
+    /// Underlying error
+    pub source: Box<Error>,
+}
+
+impl std::fmt::Display for CompactionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "compaction of segments {:?} failed in {} phase: {}",
+            self.segment_ids, self.phase, self.source,
+        )
+    }
+}
+
+impl std::error::Error for CompactionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+/// Block (or blob) that failed verification
+#[derive(Debug)]
+pub struct CorruptedBlock {
+    /// Segment or blob file the block is stored in
+    pub path: PathBuf,
+
+    /// Offset of the block inside the file
+    pub offset: u64,
+
+    /// Underlying error
+    pub source: Box<Error>,
+}
+
+impl std::fmt::Display for CorruptedBlock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "corrupted block at offset {} in {}: {}",
+            self.offset,
+            self.path.display(),
+            self.source,
+        )
+    }
+}
 
 /// Represents errors that can occur in the LSM-tree
 #[derive(Debug)]
@@ -35,6 +124,15 @@ pub enum Error {
 
     /// Value log errors
     ValueLog(value_log::Error),
+
+    /// Compaction failed
+    Compaction(CompactionError),
+
+    /// Verification found corrupted blocks
+    Corrupted(Vec<CorruptedBlock>),
+
+    /// The requested level does not exist
+    InvalidLevel(u8),
 }
 
 impl std::fmt::Display for Error {
@@ -50,8 +148,11 @@ impl std::error::Error for Error {
             Self::Encode(e) => Some(e),
             Self::Decode(e) => Some(e),
             Self::ValueLog(e) => Some(e),
+            Self::Compaction(e) => Some(e),
             Self::Decompress(_)
+            | Self::Corrupted(_)
             | Self::InvalidVersion(_)
+            | Self::InvalidLevel(_)
             | Self::Unrecoverable
             | Self::InvalidChecksum(_) => None,
         }
@@ -76,6 +177,12 @@ impl From<DecodeError> for Error {
     }
 }
 
+impl From<CompactionError> for Error {
+    fn from(value: CompactionError) -> Self {
+        Self::Compaction(value)
+    }
+}
+
 impl From<value_log::Error> for Error {
     fn from(value: value_log::Error) -> Self {
         Self::ValueLog(value)
@@ -2,7 +2,7 @@
 // This source code is licensed under both the Apache 2.0 and MIT License
 // (found in the LICENSE-* files in the repository)
 
-use crate::{level_manifest::level::Level, segment::Scanner, InternalValue};
+use crate::{level_manifest::level::Level, segment::Scanner, InternalValue, RateLimiter};
 use std::sync::Arc;
 
 /// Scans through a disjoint level
@@ -13,6 +13,7 @@ pub struct LevelScanner {
     lo: usize,
     hi: usize,
     lo_reader: Option<Scanner>,
+    rate_limiter: Option<Arc<RateLimiter>>,
 }
 
 impl LevelScanner {
@@ -32,8 +33,19 @@ impl LevelScanner {
             lo,
             hi,
             lo_reader: Some(lo_reader),
+            rate_limiter: None,
         })
     }
+
+    /// Sets the rate limiter that throttles block reads.
+    #[must_use]
+    pub fn use_rate_limiter(mut self, rate_limiter: Option<Arc<RateLimiter>>) -> Self {
+        self.lo_reader = self
+            .lo_reader
+            .map(|reader| reader.use_rate_limiter(rate_limiter.clone()));
+        self.rate_limiter = rate_limiter;
+        self
+    }
 }
 
 impl Iterator for LevelScanner {
@@ -54,7 +66,7 @@ impl Iterator for LevelScanner {
                     let scanner =
                         fail_iter!(self.segments.get(self.lo).expect("should exist").scan());
 
-                    self.lo_reader = Some(scanner);
+                    self.lo_reader = Some(scanner.use_rate_limiter(self.rate_limiter.clone()));
                 }
             } else {
                 return None;
@@ -171,6 +171,8 @@ mod path;
 #[doc(hidden)]
 pub mod range;
 
+mod rate_limiter;
+
 mod seqno;
 mod snapshot;
 mod windows;
@@ -182,6 +184,7 @@ mod time;
 mod tree;
 mod value;
 mod version;
+mod write_buffer_manager;
 
 #[doc(hidden)]
 pub mod segment;
@@ -209,16 +212,18 @@ pub use {
     cache::Cache,
     coding::{DecodeError, EncodeError},
     compression::CompressionType,
-    config::{Config, TreeType},
+    config::{Config, LevelOptions, TreeType},
     descriptor_table::DescriptorTable,
-    error::{Error, Result},
-    memtable::Memtable,
+    error::{CompactionError, CompactionPhase, CorruptedBlock, Error, Result},
+    memtable::{Memtable, MemtableType},
     r#abstract::AbstractTree,
+    rate_limiter::{IoPriority, RateLimiter},
     seqno::SequenceNumberCounter,
     snapshot::Snapshot,
     tree::Tree,
     value::{SeqNo, UserKey, UserValue, ValueType},
     version::Version,
+    write_buffer_manager::WriteBufferManager,
 };
 
 pub use any_tree::AnyTree;
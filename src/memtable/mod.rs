@@ -2,20 +2,84 @@
 // This source code is licensed under both the Apache 2.0 and MIT License
 // (found in the LICENSE-* files in the repository)
 
+mod skiplist;
+mod vector;
+
 use crate::key::InternalKey;
-use crate::value::{InternalValue, SeqNo, UserValue, ValueType};
-use crossbeam_skiplist::SkipMap;
-use std::ops::RangeBounds;
+use crate::value::{InternalValue, SeqNo, UserValue};
+use enum_dispatch::enum_dispatch;
+use std::ops::{Bound, RangeBounds};
 use std::sync::atomic::AtomicU64;
 
+pub use skiplist::SkipListMemtable;
+pub use vector::VectorMemtable;
+
+/// In-memory representation of the memtable's content
+///
+/// Implementations need to keep items sorted by [`InternalKey`]
+/// (at least when being read), and replace items with an identical key.
+#[enum_dispatch]
+pub trait MemtableRepr {
+    /// Inserts an item.
+    fn insert(&self, key: InternalKey, value: UserValue);
+
+    /// Returns the item by key if it exists.
+    ///
+    /// The item with the highest seqno (below `seqno`, if given) will be returned.
+    fn get(&self, key: &[u8], seqno: Option<SeqNo>) -> Option<InternalValue>;
+
+    /// Creates an iterator over a range of items.
+    fn range<'a>(
+        &'a self,
+        range: (Bound<InternalKey>, Bound<InternalKey>),
+    ) -> Box<dyn DoubleEndedIterator<Item = InternalValue> + 'a>;
+
+    /// Counts the amount of items.
+    fn len(&self) -> usize;
+
+    /// Returns `true` if there are no items.
+    fn is_empty(&self) -> bool;
+
+    /// Removes all items.
+    fn clear(&mut self);
+}
+
+/// Memtable representation
+///
+/// Different representations are suited for different workloads,
+/// see [`MemtableType`].
+#[enum_dispatch(MemtableRepr)]
+#[allow(clippy::large_enum_variant)]
+pub enum MemtableImpl {
+    /// Lock-free skiplist
+    SkipList(SkipListMemtable),
+
+    /// Append-only, lazily sorted vector
+    Vector(VectorMemtable),
+}
+
+/// Memtable type
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum MemtableType {
+    /// Lock-free skiplist, good all-rounder for concurrent reads and writes
+    #[default]
+    SkipList,
+
+    /// Append-only vector that is sorted lazily on first read
+    ///
+    /// Very cheap to write to, especially if keys are inserted in ascending order,
+    /// so this is a good fit for bulk loading phases.
+    /// Reads are more expensive than with [`MemtableType::SkipList`].
+    Vector,
+}
+
 /// The memtable serves as an intermediary, ephemeral, sorted storage for new items
 ///
 /// When the Memtable exceeds some size, it should be flushed to a disk segment.
-#[derive(Default)]
 pub struct Memtable {
-    /// The actual content, stored in a lock-free skiplist.
+    /// The actual content, stored in some sorted representation.
     #[doc(hidden)]
-    pub items: SkipMap<InternalKey, UserValue>,
+    pub items: MemtableImpl,
 
     /// Approximate active memtable size.
     ///
@@ -28,7 +92,28 @@ pub struct Memtable {
     pub(crate) highest_seqno: AtomicU64,
 }
 
+impl Default for Memtable {
+    fn default() -> Self {
+        Self::new(MemtableType::default())
+    }
+}
+
 impl Memtable {
+    /// Creates an empty memtable using the given representation.
+    #[must_use]
+    pub fn new(memtable_type: MemtableType) -> Self {
+        let items = match memtable_type {
+            MemtableType::SkipList => MemtableImpl::SkipList(SkipListMemtable::default()),
+            MemtableType::Vector => MemtableImpl::Vector(VectorMemtable::default()),
+        };
+
+        Self {
+            items,
+            approximate_size: AtomicU64::default(),
+            highest_seqno: AtomicU64::default(),
+        }
+    }
+
     /// Clears the memtable.
     pub fn clear(&mut self) {
         self.items.clear();
@@ -39,10 +124,7 @@ impl Memtable {
 
     /// Creates an iterator over all items.
     pub fn iter(&self) -> impl DoubleEndedIterator<Item = InternalValue> + '_ {
-        self.items.iter().map(|entry| InternalValue {
-            key: entry.key().clone(),
-            value: entry.value().clone(),
-        })
+        self.items.range((Bound::Unbounded, Bound::Unbounded))
     }
 
     /// Creates an iterator over a range of items.
@@ -50,10 +132,8 @@ impl Memtable {
         &'a self,
         range: R,
     ) -> impl DoubleEndedIterator<Item = InternalValue> + 'a {
-        self.items.range(range).map(|entry| InternalValue {
-            key: entry.key().clone(),
-            value: entry.value().clone(),
-        })
+        self.items
+            .range((range.start_bound().cloned(), range.end_bound().cloned()))
     }
 
     /// Returns the item by key if it exists.
@@ -65,40 +145,7 @@ impl Memtable {
             return None;
         }
 
-        // NOTE: This range start deserves some explanation...
-        // InternalKeys are multi-sorted by 2 categories: user_key and Reverse(seqno). (tombstone doesn't really matter)
-        // We search for the lowest entry that is greater or equal the user's prefix key
-        // and has the seqno (or lower) we want  (because the seqno is stored in reverse order)
-        //
-        // Example: We search for "abc"
-        //
-        // key -> seqno
-        //
-        // a   -> 7
-        // abc -> 5 <<< This is the lowest key (highest seqno) that matches the key with seqno=None
-        // abc -> 4
-        // abc -> 3 <<< If searching for abc and seqno=4, we would get this
-        // abcdef -> 6
-        // abcdef -> 5
-        //
-        let lower_bound = InternalKey::new(
-            key,
-            match seqno {
-                Some(seqno) => seqno - 1,
-                None => SeqNo::MAX,
-            },
-            ValueType::Value,
-        );
-
-        let mut iter = self
-            .items
-            .range(lower_bound..)
-            .take_while(|entry| &*entry.key().user_key == key);
-
-        iter.next().map(|entry| InternalValue {
-            key: entry.key().clone(),
-            value: entry.value().clone(),
-        })
+        self.items.get(key, seqno)
     }
 
     /// Gets approximate size of memtable in bytes.
@@ -204,6 +251,140 @@ mod tests {
         assert_eq!((*b"hello-value-999991-2"), &*item.unwrap().value);
     }
 
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn memtable_vector_mvcc_point_read() {
+        let memtable = Memtable::new(MemtableType::Vector);
+
+        memtable.insert(InternalValue::from_components(
+            *b"hello-key-999991",
+            *b"hello-value-999991-2",
+            1,
+            ValueType::Value,
+        ));
+        memtable.insert(InternalValue::from_components(
+            *b"hello-key-999991",
+            *b"hello-value-999991",
+            0,
+            ValueType::Value,
+        ));
+        memtable.insert(InternalValue::from_components(
+            *b"a",
+            *b"a",
+            2,
+            ValueType::Value,
+        ));
+
+        assert_eq!(None, memtable.get(b"hello-key-99999", None));
+
+        let item = memtable.get(b"hello-key-999991", None);
+        assert_eq!(*b"hello-value-999991-2", &*item.unwrap().value);
+
+        let item = memtable.get(b"hello-key-999991", Some(1));
+        assert_eq!(*b"hello-value-999991", &*item.unwrap().value);
+
+        assert_eq!(None, memtable.get(b"hello-key-999991", Some(0)));
+        assert_eq!(3, memtable.len());
+    }
+
+    #[test]
+    fn memtable_vector_overwrite_same_key() {
+        let memtable = Memtable::new(MemtableType::Vector);
+
+        memtable.insert(InternalValue::from_components(
+            b"abc".to_vec(),
+            b"old".to_vec(),
+            0,
+            ValueType::Value,
+        ));
+        memtable.insert(InternalValue::from_components(
+            b"abc".to_vec(),
+            b"new".to_vec(),
+            0,
+            ValueType::Value,
+        ));
+
+        assert_eq!(1, memtable.len());
+        assert_eq!(
+            Some(InternalValue::from_components(
+                b"abc".to_vec(),
+                b"new".to_vec(),
+                0,
+                ValueType::Value,
+            )),
+            memtable.get(b"abc", None)
+        );
+    }
+
+    #[test]
+    fn memtable_vector_concurrent_out_of_order() {
+        const ITEM_COUNT: u64 = 1_000;
+
+        let memtable = Memtable::new(MemtableType::Vector);
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                // NOTE: Descending keys make every insert dirty the vector
+                for x in (0..ITEM_COUNT).rev() {
+                    memtable.insert(InternalValue::from_components(
+                        x.to_be_bytes(),
+                        *b"abc",
+                        0,
+                        ValueType::Value,
+                    ));
+                }
+            });
+
+            scope.spawn(|| {
+                // NOTE: Once a key is visible, it has to stay visible
+                for x in (0..ITEM_COUNT).rev() {
+                    while memtable.get(&x.to_be_bytes(), None).is_none() {
+                        std::hint::spin_loop();
+                    }
+
+                    for y in x..ITEM_COUNT {
+                        assert!(memtable.get(&y.to_be_bytes(), None).is_some());
+                    }
+                }
+            });
+        });
+
+        assert_eq!(ITEM_COUNT as usize, memtable.len());
+    }
+
+    #[test]
+    fn memtable_vector_range() {
+        let memtable = Memtable::new(MemtableType::Vector);
+
+        for key in [b"d", b"a", b"c", b"b", b"e"] {
+            memtable.insert(InternalValue::from_components(
+                key.to_vec(),
+                key.to_vec(),
+                0,
+                ValueType::Value,
+            ));
+        }
+
+        let lo = InternalKey::new(b"b".to_vec(), SeqNo::MAX, ValueType::Value);
+        let hi = InternalKey::new(b"d".to_vec(), SeqNo::MAX, ValueType::Value);
+
+        let keys = memtable
+            .range(lo..hi)
+            .map(|item| item.key.user_key)
+            .collect::<Vec<_>>();
+        assert_eq!(keys, [&b"b"[..], b"c"].map(Into::<crate::Slice>::into));
+
+        let keys = memtable
+            .iter()
+            .rev()
+            .map(|item| item.key.user_key)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            [&b"e"[..], b"d", b"c", b"b", b"a"].map(Into::<crate::Slice>::into)
+        );
+    }
+
     #[test]
     fn memtable_get() {
         let memtable = Memtable::default();
@@ -0,0 +1,83 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use super::MemtableRepr;
+use crate::key::InternalKey;
+use crate::value::{InternalValue, SeqNo, UserValue, ValueType};
+use crossbeam_skiplist::SkipMap;
+use std::ops::Bound;
+
+/// Memtable representation backed by a lock-free skiplist
+///
+/// This is the default representation, and works well for
+/// mixed workloads of concurrent writes and reads.
+#[derive(Default)]
+pub struct SkipListMemtable {
+    items: SkipMap<InternalKey, UserValue>,
+}
+
+impl MemtableRepr for SkipListMemtable {
+    fn insert(&self, key: InternalKey, value: UserValue) {
+        self.items.insert(key, value);
+    }
+
+    fn get(&self, key: &[u8], seqno: Option<SeqNo>) -> Option<InternalValue> {
+        // NOTE: This range start deserves some explanation...
+        // InternalKeys are multi-sorted by 2 categories: user_key and Reverse(seqno). (tombstone doesn't really matter)
+        // We search for the lowest entry that is greater or equal the user's prefix key
+        // and has the seqno (or lower) we want  (because the seqno is stored in reverse order)
+        //
+        // Example: We search for "abc"
+        //
+        // key -> seqno
+        //
+        // a   -> 7
+        // abc -> 5 <<< This is the lowest key (highest seqno) that matches the key with seqno=None
+        // abc -> 4
+        // abc -> 3 <<< If searching for abc and seqno=4, we would get this
+        // abcdef -> 6
+        // abcdef -> 5
+        //
+        let lower_bound = InternalKey::new(
+            key,
+            match seqno {
+                Some(seqno) => seqno - 1,
+                None => SeqNo::MAX,
+            },
+            ValueType::Value,
+        );
+
+        let mut iter = self
+            .items
+            .range(lower_bound..)
+            .take_while(|entry| &*entry.key().user_key == key);
+
+        iter.next().map(|entry| InternalValue {
+            key: entry.key().clone(),
+            value: entry.value().clone(),
+        })
+    }
+
+    fn range<'a>(
+        &'a self,
+        range: (Bound<InternalKey>, Bound<InternalKey>),
+    ) -> Box<dyn DoubleEndedIterator<Item = InternalValue> + 'a> {
+        Box::new(self.items.range(range).map(|entry| InternalValue {
+            key: entry.key().clone(),
+            value: entry.value().clone(),
+        }))
+    }
+
+    fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    fn clear(&mut self) {
+        self.items.clear();
+    }
+}
@@ -0,0 +1,150 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use super::MemtableRepr;
+use crate::key::InternalKey;
+use crate::value::{InternalValue, SeqNo, UserValue};
+use std::ops::{Bound, RangeBounds};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{RwLock, RwLockReadGuard};
+
+/// Memtable representation backed by an append-only vector
+///
+/// Inserts are simply appended, so writing is very cheap, especially
+/// when keys arrive in ascending order (e.g. during bulk loading).
+///
+/// The vector is lazily sorted (and deduplicated) the first time it is read
+/// after an out-of-order insert, so read-heavy workloads should use
+/// the skiplist representation instead.
+#[derive(Default)]
+pub struct VectorMemtable {
+    items: RwLock<Vec<(InternalKey, UserValue)>>,
+
+    /// `true` if the vector may contain unsorted or duplicate keys
+    is_dirty: AtomicBool,
+}
+
+impl VectorMemtable {
+    /// Sorts the vector if needed, keeping only the last written
+    /// entry for each internal key, which mirrors the skiplist behaviour.
+    #[allow(clippy::significant_drop_tightening)]
+    fn ensure_sorted(&self) {
+        if !self.is_dirty.load(Ordering::Acquire) {
+            return;
+        }
+
+        let mut items = self.items.write().expect("lock is poisoned");
+
+        // NOTE: Another reader may have sorted the vector in the meantime
+        if !self.is_dirty.load(Ordering::Acquire) {
+            return;
+        }
+
+        // NOTE: Sort is stable, so equal keys stay in insertion order
+        items.sort_by(|a, b| a.0.cmp(&b.0));
+
+        // NOTE: `dedup_by` passes the later element first,
+        // so move it into the retained slot before removing it
+        items.dedup_by(|later, earlier| {
+            if later.0 == earlier.0 {
+                std::mem::swap(later, earlier);
+                true
+            } else {
+                false
+            }
+        });
+
+        self.is_dirty.store(false, Ordering::Release);
+    }
+
+    /// Read-locks the vector, making sure it is sorted while the lock is held.
+    fn read_sorted(&self) -> RwLockReadGuard<'_, Vec<(InternalKey, UserValue)>> {
+        loop {
+            self.ensure_sorted();
+
+            let items = self.items.read().expect("lock is poisoned");
+
+            // NOTE: The dirty flag is only set while holding the write lock,
+            // so it cannot change while we hold the read lock
+            if !self.is_dirty.load(Ordering::Acquire) {
+                return items;
+            }
+        }
+    }
+}
+
+impl MemtableRepr for VectorMemtable {
+    fn insert(&self, key: InternalKey, value: UserValue) {
+        let mut items = self.items.write().expect("lock is poisoned");
+
+        if items.last().is_some_and(|(last, _)| *last >= key) {
+            self.is_dirty.store(true, Ordering::Release);
+        }
+
+        items.push((key, value));
+    }
+
+    fn get(&self, key: &[u8], seqno: Option<SeqNo>) -> Option<InternalValue> {
+        let items = self.read_sorted();
+
+        // NOTE: See skiplist memtable for an explanation of the search bounds
+        let seqno = seqno.map_or(SeqNo::MAX, |seqno| seqno - 1);
+
+        let idx = items.partition_point(|(k, _)| {
+            (&*k.user_key, std::cmp::Reverse(k.seqno)) < (key, std::cmp::Reverse(seqno))
+        });
+
+        items
+            .get(idx)
+            .filter(|(k, _)| &*k.user_key == key)
+            .map(|(k, v)| InternalValue {
+                key: k.clone(),
+                value: v.clone(),
+            })
+    }
+
+    fn range<'a>(
+        &'a self,
+        range: (Bound<InternalKey>, Bound<InternalKey>),
+    ) -> Box<dyn DoubleEndedIterator<Item = InternalValue> + 'a> {
+        let items = self.read_sorted();
+
+        let lo = match &range.0 {
+            Bound::Included(lo) => items.partition_point(|(k, _)| k < lo),
+            Bound::Excluded(lo) => items.partition_point(|(k, _)| k <= lo),
+            Bound::Unbounded => 0,
+        };
+
+        // NOTE: We cannot hand out references into the locked vector,
+        // so the matching range is copied (cheaply, because of ref counted slices)
+        #[allow(clippy::needless_collect)]
+        let collected = items
+            .get(lo..)
+            .unwrap_or_default()
+            .iter()
+            .take_while(|(k, _)| range.contains(k))
+            .map(|(k, v)| InternalValue {
+                key: k.clone(),
+                value: v.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        drop(items);
+
+        Box::new(collected.into_iter())
+    }
+
+    fn len(&self) -> usize {
+        self.read_sorted().len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.items.read().expect("lock is poisoned").is_empty()
+    }
+
+    fn clear(&mut self) {
+        self.items.get_mut().expect("lock is poisoned").clear();
+        self.is_dirty.store(false, Ordering::Release);
+    }
+}
@@ -0,0 +1,277 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// Tokens are refilled at most for this duration, so an idle
+/// limiter does not allow an unbounded burst afterwards
+const MAX_BURST: Duration = Duration::from_millis(100);
+
+/// Waiters re-check the rate at least this often,
+/// so rate changes are picked up quickly
+const MAX_SLEEP: Duration = Duration::from_millis(10);
+
+/// Priority of an I/O request
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum IoPriority {
+    /// Flushes, which block writes if they fall behind
+    High,
+
+    /// Compactions, which yield to pending high priority requests
+    Low,
+}
+
+struct Bucket {
+    /// Available tokens (bytes), may go negative when
+    /// a request is larger than the burst size
+    tokens: i64,
+
+    last_refill: Instant,
+}
+
+/// Token-bucket rate limiter for flush and compaction I/O
+///
+/// Segment writers are charged for every block they write,
+/// compaction scanners are charged for every block they read.
+///
+/// Share a single limiter between multiple trees to cap the
+/// global background I/O throughput.
+///
+/// # Examples
+///
+/// ```
+/// # use lsm_tree::{Config, RateLimiter};
+/// # use std::sync::Arc;
+/// #
+/// // Allow 50 MB/s of flush & compaction I/O
+/// let rate_limiter = Arc::new(RateLimiter::new(50 * 1_000 * 1_000));
+///
+/// # let folder = tempfile::tempdir()?;
+/// let tree = Config::new(folder)
+///     .use_rate_limiter(rate_limiter.clone())
+///     .open()?;
+///
+/// // Throttle background I/O some more, e.g. during peak hours
+/// rate_limiter.set_rate(10 * 1_000 * 1_000);
+/// #
+/// # Ok::<(), lsm_tree::Error>(())
+/// ```
+pub struct RateLimiter {
+    /// Bytes per second
+    rate: AtomicU64,
+
+    bucket: Mutex<Bucket>,
+
+    /// Amount of high priority requests currently waiting
+    pending_high: AtomicUsize,
+}
+
+impl std::fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RateLimiter({}B/s)", self.rate())
+    }
+}
+
+impl RateLimiter {
+    /// Creates a new rate limiter with the given rate in bytes per second.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes_per_second` is 0.
+    #[must_use]
+    pub fn new(bytes_per_second: u64) -> Self {
+        assert!(bytes_per_second > 0, "rate should be > 0");
+
+        Self {
+            rate: AtomicU64::new(bytes_per_second),
+            bucket: Mutex::new(Bucket {
+                tokens: Self::burst_size(bytes_per_second),
+                last_refill: Instant::now(),
+            }),
+            pending_high: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the rate in bytes per second.
+    #[must_use]
+    pub fn rate(&self) -> u64 {
+        self.rate.load(Ordering::Acquire)
+    }
+
+    /// Changes the rate in bytes per second.
+    ///
+    /// Threads that are currently throttled pick up the new rate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes_per_second` is 0.
+    pub fn set_rate(&self, bytes_per_second: u64) {
+        assert!(bytes_per_second > 0, "rate should be > 0");
+        self.rate.store(bytes_per_second, Ordering::Release);
+    }
+
+    fn burst_size(rate: u64) -> i64 {
+        let burst = u128::from(rate) * MAX_BURST.as_nanos() / 1_000_000_000;
+
+        // NOTE: Bursts are capped to i64::MAX, which is never reached in practice
+        #[allow(clippy::cast_possible_truncation)]
+        (burst.min(i64::MAX as u128) as i64).max(1)
+    }
+
+    /// Adds the tokens that accumulated since the last refill.
+    fn refill(bucket: &mut Bucket, rate: u64) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.last_refill);
+
+        let new_tokens = u128::from(rate) * elapsed.as_nanos() / 1_000_000_000;
+
+        // NOTE: Capped by burst size below
+        #[allow(clippy::cast_possible_truncation)]
+        let new_tokens = new_tokens.min(i64::MAX as u128) as i64;
+
+        bucket.tokens = bucket
+            .tokens
+            .saturating_add(new_tokens)
+            .min(Self::burst_size(rate));
+        bucket.last_refill = now;
+    }
+
+    /// Returns how long to wait until `tokens` have been refilled.
+    fn time_until(tokens: i64, rate: u64) -> Duration {
+        let nanos = u128::from(tokens.unsigned_abs()) * 1_000_000_000 / u128::from(rate);
+
+        // NOTE: Capped by MAX_SLEEP anyway
+        #[allow(clippy::cast_possible_truncation)]
+        Duration::from_nanos(nanos.min(u128::from(u64::MAX)) as u64)
+    }
+
+    /// Blocks until `bytes` may be read or written.
+    ///
+    /// Requests larger than the burst size are allowed to take the bucket into debt,
+    /// which is paid back by subsequent requests.
+    ///
+    /// Low priority requests wait as long as high priority requests are pending.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lock is poisoned.
+    #[allow(clippy::expect_used)]
+    pub fn request(&self, bytes: u64, priority: IoPriority) {
+        // NOTE: Requests are at most a block, so truncation is not a concern
+        #[allow(clippy::cast_possible_wrap)]
+        let bytes = bytes.min(i64::MAX as u64) as i64;
+
+        if priority == IoPriority::High {
+            self.pending_high.fetch_add(1, Ordering::AcqRel);
+        }
+
+        loop {
+            let rate = self.rate();
+
+            let wait = {
+                let mut bucket = self.bucket.lock().expect("lock is poisoned");
+                Self::refill(&mut bucket, rate);
+
+                let yields =
+                    priority == IoPriority::Low && self.pending_high.load(Ordering::Acquire) > 0;
+
+                if !yields && bucket.tokens > 0 {
+                    bucket.tokens -= bytes;
+                    break;
+                }
+
+                Self::time_until(bucket.tokens.min(0) - 1, rate)
+            };
+
+            std::thread::sleep(wait.min(MAX_SLEEP));
+        }
+
+        if priority == IoPriority::High {
+            self.pending_high.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use test_log::test;
+
+    #[test]
+    fn rate_limiter_throttles() {
+        let limiter = RateLimiter::new(1_000_000);
+
+        let start = Instant::now();
+
+        // NOTE: First 100 KB are the initial burst
+        for _ in 0..30 {
+            limiter.request(10_000, IoPriority::Low);
+        }
+
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(150), "{elapsed:?}");
+        assert!(elapsed < Duration::from_secs(2), "{elapsed:?}");
+    }
+
+    #[test]
+    fn rate_limiter_set_rate() {
+        let limiter = RateLimiter::new(1);
+        assert_eq!(1, limiter.rate());
+
+        // Drain initial burst
+        limiter.request(1, IoPriority::Low);
+
+        limiter.set_rate(1_000_000_000);
+        assert_eq!(1_000_000_000, limiter.rate());
+
+        let start = Instant::now();
+        limiter.request(1_000, IoPriority::Low);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn rate_limiter_high_priority_first() {
+        let limiter = Arc::new(RateLimiter::new(100_000));
+
+        // Drain the bucket into debt
+        limiter.request(100_000, IoPriority::Low);
+
+        let order = Arc::new(Mutex::new(vec![]));
+
+        std::thread::scope(|s| {
+            let low = s.spawn({
+                let limiter = limiter.clone();
+                let order = order.clone();
+                move || {
+                    std::thread::sleep(Duration::from_millis(10));
+                    limiter.request(1, IoPriority::Low);
+                    order
+                        .lock()
+                        .expect("lock is poisoned")
+                        .push(IoPriority::Low);
+                }
+            });
+
+            limiter.request(1, IoPriority::High);
+            order
+                .lock()
+                .expect("lock is poisoned")
+                .push(IoPriority::High);
+
+            low.join().expect("should join");
+        });
+
+        assert_eq!(
+            vec![IoPriority::High, IoPriority::Low],
+            *order.lock().expect("lock is poisoned"),
+        );
+    }
+}
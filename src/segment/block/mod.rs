@@ -18,6 +18,7 @@ pub(crate) use trailer::{Trailer, TRAILER_START_MARKER};
 
 use crate::{
     coding::{Decode, Encode},
+    compression::CompressionDictionary,
     CompressionType, Slice,
 };
 use std::fs::File;
@@ -39,10 +40,32 @@ impl Block {
         self.data.len()
     }
 
+    /// Checks the block's data against the checksum stored in its header.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the checksums do not match.
+    pub fn verify_checksum(&self) -> crate::Result<()> {
+        let checksum = Checksum::from_bytes(&self.data);
+
+        if checksum != self.header.checksum {
+            return Err(crate::Error::InvalidChecksum((
+                checksum,
+                self.header.checksum,
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Compresses and writes a block.
+    ///
+    /// The dictionary (if any) is only used by compression types that support dictionaries.
     pub fn to_writer<W: std::io::Write>(
         mut writer: &mut W,
         data: &[u8],
         compression: CompressionType,
+        dictionary: Option<&CompressionDictionary>,
     ) -> crate::Result<Header> {
         let checksum = xxh3_64(data);
 
@@ -61,7 +84,17 @@ impl Block {
 
             #[cfg(feature = "miniz")]
             CompressionType::Miniz(level) => &miniz_oxide::deflate::compress_to_vec(data, level),
+
+            #[cfg(feature = "zstd")]
+            CompressionType::Zstd(level) => &match dictionary {
+                Some(dictionary) => dictionary.compress(data)?,
+                None => zstd::bulk::compress(data, level)?,
+            },
         };
+
+        #[cfg(not(feature = "zstd"))]
+        let _ = dictionary;
+
         header.data_length = data.len() as u32;
 
         header.encode_into(&mut writer)?;
@@ -79,6 +112,7 @@ impl Block {
     pub fn from_reader<R: std::io::Read>(
         reader: &mut R,
         compression: CompressionType,
+        dictionary: Option<&CompressionDictionary>,
     ) -> crate::Result<Self> {
         let header = Header::decode_from(reader)?;
         let raw_data = Slice::from_reader(reader, header.data_length as usize)?;
@@ -104,16 +138,30 @@ impl Block {
             CompressionType::Miniz(_) => miniz_oxide::inflate::decompress_to_vec(&raw_data)
                 .map_err(|_| crate::Error::Decompress(compression))?
                 .into(),
+
+            #[cfg(feature = "zstd")]
+            CompressionType::Zstd(_) => {
+                zstd_decompress(&raw_data, &header, compression, dictionary)?.into()
+            }
         };
 
-        debug_assert_eq!(header.uncompressed_length, {
+        #[cfg(not(feature = "zstd"))]
+        let _ = dictionary;
+
+        let block = Self { header, data };
+
+        // NOTE: Verify the checksum first, so corrupted data
+        // surfaces as an error instead of failing the assertion below
+        block.verify_checksum()?;
+
+        debug_assert_eq!(block.header.uncompressed_length, {
             #[allow(clippy::expect_used, clippy::cast_possible_truncation)]
             {
-                data.len() as u32
+                block.data.len() as u32
             }
         });
 
-        Ok(Self { header, data })
+        Ok(block)
     }
 
     // TODO: take non-keyed block handle
@@ -122,6 +170,7 @@ impl Block {
         offset: BlockOffset,
         size: u32,
         compression: CompressionType,
+        dictionary: Option<&CompressionDictionary>,
     ) -> crate::Result<Self> {
         // TODO: use with_size_unzeroed (or whatever it will be called)
         // TODO: use a Slice::get_mut instead... needs value-log update
@@ -191,18 +240,52 @@ impl Block {
                     .map_err(|_| crate::Error::Decompress(compression))?
                     .into()
             }
+
+            #[cfg(feature = "zstd")]
+            CompressionType::Zstd(_) => {
+                // NOTE: We know that a header always exists and data is never empty
+                // So the slice is fine
+                #[allow(clippy::indexing_slicing)]
+                let raw_data = &buf[Header::serialized_len()..];
+
+                zstd_decompress(raw_data, &header, compression, dictionary)?.into()
+            }
         };
 
-        debug_assert_eq!(header.uncompressed_length, {
+        #[cfg(not(feature = "zstd"))]
+        let _ = dictionary;
+
+        let block = Self {
+            header,
+            data: Slice::from(data),
+        };
+
+        // NOTE: Verify the checksum first, see `from_reader`
+        block.verify_checksum()?;
+
+        debug_assert_eq!(block.header.uncompressed_length, {
             #[allow(clippy::expect_used, clippy::cast_possible_truncation)]
             {
-                data.len() as u32
+                block.data.len() as u32
             }
         });
 
-        Ok(Self {
-            header,
-            data: Slice::from(data),
-        })
+        Ok(block)
+    }
+}
+
+#[cfg(feature = "zstd")]
+fn zstd_decompress(
+    raw_data: &[u8],
+    header: &Header,
+    compression: CompressionType,
+    dictionary: Option<&CompressionDictionary>,
+) -> crate::Result<Vec<u8>> {
+    let capacity = header.uncompressed_length as usize;
+
+    match dictionary {
+        Some(dictionary) => dictionary.decompress(raw_data, capacity),
+        None => zstd::bulk::decompress(raw_data, capacity),
     }
+    .map_err(|_| crate::Error::Decompress(compression))
 }
@@ -388,7 +388,10 @@ impl DataBlock {
         let key_start = offset + reader.position() as usize;
         unwrappy!(reader.seek_relative(key_len as i64));
 
-        let val_len: usize = if value_type == u8::from(ValueType::Value) {
+        let has_value = value_type == u8::from(ValueType::Value)
+            || value_type == u8::from(ValueType::ExpiringValue);
+
+        let val_len: usize = if has_value {
             unwrappy!(reader.read_u32_varint()) as usize
         } else {
             0
@@ -396,7 +399,7 @@ impl DataBlock {
         let val_offset = offset + reader.position() as usize;
         unwrappy!(reader.seek_relative(val_len as i64));
 
-        Some(if value_type == u8::from(ValueType::Value) {
+        Some(if has_value {
             ParsedItem {
                 value_type,
                 seqno,
@@ -435,7 +438,10 @@ impl DataBlock {
 
         unwrappy!(reader.seek_relative(rest_key_len as i64));
 
-        let val_len: usize = if value_type == u8::from(ValueType::Value) {
+        let has_value = value_type == u8::from(ValueType::Value)
+            || value_type == u8::from(ValueType::ExpiringValue);
+
+        let val_len: usize = if has_value {
             unwrappy!(reader.read_u32_varint()) as usize
         } else {
             0
@@ -443,7 +449,7 @@ impl DataBlock {
         let val_offset = offset + reader.position() as usize;
         unwrappy!(reader.seek_relative(val_len as i64));
 
-        Some(if value_type == u8::from(ValueType::Value) {
+        Some(if has_value {
             ParsedItem {
                 value_type,
                 seqno,
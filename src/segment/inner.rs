@@ -6,7 +6,8 @@ use super::{
     block_index::NewBlockIndexImpl, filter::AMQFilter, meta::ParsedMeta, trailer::Trailer,
 };
 use crate::{
-    cache::Cache, descriptor_table::DescriptorTable, tree::inner::TreeId, GlobalSegmentId,
+    cache::Cache, compression::CompressionDictionary, descriptor_table::DescriptorTable,
+    tree::inner::TreeId, GlobalSegmentId,
 };
 use std::{
     path::PathBuf,
@@ -37,9 +38,15 @@ pub struct Inner {
     #[doc(hidden)]
     pub cache: Arc<Cache>,
 
+    /// Whether to verify the checksums of cached blocks
+    pub(crate) verify_cache_hits: bool,
+
     /// Pinned AMQ filter
     pub pinned_filter: Option<AMQFilter>,
 
+    /// Compression dictionary of data blocks
+    pub(crate) dictionary: Option<Arc<CompressionDictionary>>,
+
     // /// Pinned filter
     // #[doc(hidden)]
     // pub bloom_filter: Option<crate::bloom::BloomFilter>,
@@ -33,21 +33,28 @@ impl From<u128> for Timestamp {
 pub struct ParsedMeta {
     pub id: SegmentId,
     pub created_at: Timestamp,
+
+    /// Creation time of the newest data in the segment
+    ///
+    /// Unlike `created_at`, this is inherited through compactions.
+    pub data_created_at: Timestamp,
+
     pub data_block_count: u64,
     pub index_block_count: u64,
     pub key_range: KeyRange,
     pub seqnos: (SeqNo, SeqNo),
     pub file_size: u64,
     pub item_count: u64,
+    pub tombstone_count: u64,
 
     pub data_block_compression: CompressionType,
 }
 
 impl ParsedMeta {
-    #[allow(clippy::expect_used)]
+    #[allow(clippy::expect_used, clippy::too_many_lines)]
     pub fn from_trailer(file: &File, trailer: &Trailer) -> crate::Result<Self> {
         let ptr = trailer.metadata;
-        let block = Block::from_file(file, ptr.offset(), ptr.size(), CompressionType::None)?;
+        let block = Block::from_file(file, ptr.offset(), ptr.size(), CompressionType::None, None)?;
         let block = DataBlock::new(block);
 
         assert_eq!(
@@ -83,7 +90,16 @@ impl ParsedMeta {
                 .expect("Segment created_at should exist");
 
             let mut bytes = &bytes.value[..];
-            bytes.read_u128::<LittleEndian>()?.into()
+            bytes.read_u128::<LittleEndian>()?
+        };
+
+        // NOTE: Older segments may not have stored the data timestamp
+        let data_created_at = match block.point_read(b"#data_created_at", None) {
+            Some(bytes) => {
+                let mut bytes = &bytes.value[..];
+                bytes.read_u128::<LittleEndian>()?
+            }
+            None => created_at,
         };
 
         let item_count = {
@@ -95,6 +111,15 @@ impl ParsedMeta {
             bytes.read_u64::<LittleEndian>()?
         };
 
+        let tombstone_count = {
+            let bytes = block
+                .point_read(b"#tombstone_count", None)
+                .expect("tombstone_count should exist");
+
+            let mut bytes = &bytes.value[..];
+            bytes.read_u64::<LittleEndian>()?
+        };
+
         let data_block_count = {
             let bytes = block
                 .point_read(b"#data_block_count", None)
@@ -163,13 +188,15 @@ impl ParsedMeta {
 
         Ok(Self {
             id,
-            created_at,
+            created_at: created_at.into(),
+            data_created_at: data_created_at.into(),
             data_block_count,
             index_block_count,
             key_range,
             seqnos,
             file_size,
             item_count,
+            tombstone_count,
             data_block_compression,
         })
     }
@@ -24,7 +24,8 @@ pub use scanner::Scanner;
 pub use writer::Writer;
 
 use crate::{
-    cache::Cache, descriptor_table::DescriptorTable, InternalValue, SeqNo, TreeId, UserKey,
+    cache::Cache, compression::CompressionDictionary, descriptor_table::DescriptorTable,
+    CompressionType, CorruptedBlock, InternalValue, SeqNo, TreeId, UserKey,
 };
 use block_index::{NewBlockIndex, NewBlockIndexImpl, NewFullBlockIndex};
 use filter::{standard_bloom::CompositeHash, AMQ, AMQFilterBuilder};
@@ -42,9 +43,6 @@ use std::{
 // TODO:    we only need to truncate items from blocks that are not the first and last block
 // TODO:    because any block inbetween must (trivially) only contain relevant items
 
-// TODO: in Leveled compaction, compact segments that live very long and have
-// many versions (possibly unnecessary space usage of old, stale versions)
-
 // TODO: move into module
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum CachePolicy {
@@ -120,6 +118,10 @@ impl Segment {
         let id = self.global_id();
 
         if let Some(data_block) = self.cache.get_data_block(id, handle.offset()) {
+            if self.verify_cache_hits {
+                data_block.inner.verify_checksum()?;
+            }
+
             return Ok(data_block);
         }
 
@@ -137,6 +139,7 @@ impl Segment {
             handle.offset(),
             handle.size(),
             self.metadata.data_block_compression,
+            self.dictionary.as_deref(),
         )
         .map(DataBlock::new)?;
 
@@ -232,11 +235,172 @@ impl Segment {
             .try_into()
             .expect("data block count should fit");
 
-        Scanner::new(
+        Ok(Scanner::new(
             &self.path,
             block_count,
             self.metadata.data_block_compression,
-        )
+        )?
+        .use_dictionary(self.dictionary.clone()))
+    }
+
+    /// Reads every block of the segment from disk and checks its checksum.
+    ///
+    /// Blocks that fail to load are added to `corrupted`.
+    ///
+    /// Returns the amount of items in the intact data blocks.
+    pub(crate) fn verify(&self, corrupted: &mut Vec<CorruptedBlock>) -> crate::Result<usize> {
+        let file = std::fs::File::open(&self.path)?;
+
+        let mut check = |handle: BlockHandle,
+                         compression: CompressionType,
+                         dictionary: Option<&CompressionDictionary>|
+         -> crate::Result<Option<Block>> {
+            match Block::from_file(
+                &file,
+                handle.offset(),
+                handle.size(),
+                compression,
+                dictionary,
+            ) {
+                Ok(block) => Ok(Some(block)),
+                Err(crate::Error::Io(e)) => Err(crate::Error::Io(e)),
+                Err(e) => {
+                    log::error!(
+                        "Corrupted block at offset {} in segment {}: {e:?}",
+                        *handle.offset(),
+                        self.path.display(),
+                    );
+
+                    corrupted.push(CorruptedBlock {
+                        path: self.path.clone(),
+                        offset: *handle.offset(),
+                        source: Box::new(e),
+                    });
+
+                    Ok(None)
+                }
+            }
+        };
+
+        // NOTE: Only the TLI uses the data block compression, see Segment::recover
+        let handles = [
+            Some((self.trailer.tli, self.metadata.data_block_compression)),
+            self.trailer
+                .filter
+                .map(|handle| (handle, CompressionType::None)),
+            self.trailer
+                .dictionary
+                .map(|handle| (handle, CompressionType::None)),
+            Some((self.trailer.metadata, CompressionType::None)),
+        ];
+
+        for (handle, compression) in handles.into_iter().flatten() {
+            check(handle, compression, None)?;
+        }
+
+        let mut item_count = 0;
+
+        for handle in self.data_block_handles() {
+            let handle = BlockHandle::new(handle.offset(), handle.size());
+
+            if let Some(block) = check(
+                handle,
+                self.metadata.data_block_compression,
+                self.dictionary.as_deref(),
+            )? {
+                item_count += DataBlock::new(block).len();
+            }
+        }
+
+        Ok(item_count)
+    }
+
+    /// Returns the handles of all data blocks, ordered by key.
+    fn data_block_handles(&self) -> Vec<KeyedBlockHandle> {
+        let NewBlockIndexImpl::Full(block_index) = &*self.block_index;
+
+        block_index
+            .forward_reader(&[])
+            .map(Iterator::collect)
+            .unwrap_or_default()
+    }
+
+    /// Returns the end keys of all data blocks, ordered by key.
+    ///
+    /// Used to split compactions into disjoint key ranges.
+    pub(crate) fn data_block_end_keys(&self) -> Vec<UserKey> {
+        self.data_block_handles()
+            .into_iter()
+            .map(KeyedBlockHandle::into_end_key)
+            .collect()
+    }
+
+    /// Creates a scanner over the `Segment`, which only returns items inside the key range.
+    ///
+    /// Like [`Segment::scan`], but only reads the data blocks that may contain the key range.
+    ///
+    /// Used for subcompactions and thus not available to a user.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    #[doc(hidden)]
+    pub fn scan_range(
+        &self,
+        (lo, hi): (Bound<UserKey>, Bound<UserKey>),
+        rate_limiter: Option<Arc<crate::RateLimiter>>,
+    ) -> crate::Result<Box<dyn Iterator<Item = crate::Result<InternalValue>>>> {
+        let handles = self.data_block_handles();
+
+        let Some(lo_idx) = handles.iter().position(|handle| match &lo {
+            Bound::Included(key) => handle.end_key() >= key,
+            Bound::Excluded(key) => handle.end_key() > key,
+            Bound::Unbounded => true,
+        }) else {
+            return Ok(Box::new(std::iter::empty()));
+        };
+
+        // NOTE: The first block that ends after the upper bound is the last block to read
+        //
+        // The versions of a single key may span multiple blocks, so a block
+        // that ends exactly at an inclusive upper bound is not enough
+        let hi_idx = handles
+            .iter()
+            .skip(lo_idx)
+            .position(|handle| match &hi {
+                Bound::Included(key) => handle.end_key() > key,
+                Bound::Excluded(key) => handle.end_key() >= key,
+                Bound::Unbounded => false,
+            })
+            .map_or(handles.len() - 1, |idx| lo_idx + idx);
+
+        #[allow(clippy::expect_used)]
+        let scanner = Scanner::new_at(
+            &self.path,
+            handles.get(lo_idx).expect("should exist").offset(),
+            hi_idx - lo_idx + 1,
+            self.metadata.data_block_compression,
+        )?
+        .use_dictionary(self.dictionary.clone())
+        .use_rate_limiter(rate_limiter);
+
+        let is_below = move |key: &UserKey| match &lo {
+            Bound::Included(lo) => key < lo,
+            Bound::Excluded(lo) => key <= lo,
+            Bound::Unbounded => false,
+        };
+
+        let is_above = move |key: &UserKey| match &hi {
+            Bound::Included(hi) => key > hi,
+            Bound::Excluded(hi) => key >= hi,
+            Bound::Unbounded => false,
+        };
+
+        Ok(Box::new(
+            scanner
+                .skip_while(move |item| item.as_ref().is_ok_and(|x| is_below(&x.key.user_key)))
+                .take_while(move |item| item.as_ref().map_or(true, |x| !is_above(&x.key.user_key))),
+        ))
     }
 
     /// Creates an iterator over the `Segment`.
@@ -278,6 +442,7 @@ impl Segment {
         tree_id: TreeId,
         cache: Arc<Cache>,
         descriptor_table: Arc<DescriptorTable>,
+        verify_cache_hits: bool,
     ) -> crate::Result<Self> {
         // use block_index::{full_index::FullBlockIndex, two_level_index::TwoLevelBlockIndex};
         use trailer::Trailer;
@@ -304,6 +469,7 @@ impl Segment {
                 trailer.tli.offset(),
                 trailer.tli.size(),
                 metadata.data_block_compression, // TODO: index blocks may get their own compression level
+                None,
             )?;
 
             IndexBlock::new(block)
@@ -348,6 +514,7 @@ impl Segment {
                     filter_ptr.offset(),
                     filter_ptr.size(),
                     crate::CompressionType::None, // NOTE: We never write a filter block with compression
+                    None,
                 )?;
 
                 let mut reader = &block.data[..];
@@ -355,6 +522,25 @@ impl Segment {
             })
             .transpose()?;
 
+        let dictionary = trailer
+            .dictionary
+            .map(|dictionary_ptr| {
+                log::debug!(
+                    "Reading compression dictionary, with dictionary_ptr={dictionary_ptr:?}"
+                );
+
+                let block = Block::from_file(
+                    &file,
+                    dictionary_ptr.offset(),
+                    dictionary_ptr.size(),
+                    crate::CompressionType::None, // NOTE: We never write a dictionary with compression
+                    None,
+                )?;
+
+                Ok::<_, crate::Error>(Arc::new(CompressionDictionary::new(block.data)))
+            })
+            .transpose()?;
+
         descriptor_table.insert_for_table((tree_id, metadata.id).into(), Arc::new(file));
 
         let segment = Self(Arc::new(Inner {
@@ -365,6 +551,7 @@ impl Segment {
             trailer,
 
             cache,
+            verify_cache_hits,
 
             descriptor_table,
 
@@ -372,6 +559,8 @@ impl Segment {
 
             pinned_filter,
 
+            dictionary,
+
             is_deleted: AtomicBool::default(),
         }));
 
@@ -407,18 +596,19 @@ impl Segment {
     #[must_use]
     #[doc(hidden)]
     pub fn tombstone_count(&self) -> u64 {
-        todo!()
-
-        //  self.metadata.tombstone_count
+        self.metadata.tombstone_count
     }
 
     /// Returns the ratio of tombstone markers in the `Segment`.
     #[must_use]
     #[doc(hidden)]
+    #[allow(clippy::cast_precision_loss)]
     pub fn tombstone_ratio(&self) -> f32 {
-        todo!()
+        if self.metadata.item_count == 0 {
+            return 0.0;
+        }
 
-        //  self.metadata.tombstone_count as f32 / self.metadata.key_count as f32
+        self.metadata.tombstone_count as f32 / self.metadata.item_count as f32
     }
 }
 
@@ -451,6 +641,7 @@ mod tests {
                 0,
                 Arc::new(Cache::with_capacity_bytes(1_000_000)),
                 Arc::new(DescriptorTable::new(10)),
+                true,
             )?;
 
             assert_eq!(5, segment.id());
@@ -481,6 +672,42 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn v3_segment_tombstone_count() -> crate::Result<()> {
+        let dir = tempdir()?;
+        let file = dir.path().join("segment");
+
+        {
+            let mut writer = crate::segment::Writer::new(file.clone(), 5)?;
+            writer.write(crate::InternalValue::from_components(
+                b"abc",
+                b"asdasdasd",
+                3,
+                crate::ValueType::Value,
+            ))?;
+            writer.write(crate::InternalValue::new_tombstone(b"def", 3))?;
+            writer.write(crate::InternalValue::new_weak_tombstone(b"ghi", 3))?;
+            writer.write(crate::InternalValue::new_tombstone(b"jkl", 3))?;
+            let _trailer = writer.finish()?;
+        }
+
+        {
+            let segment = Segment::recover(
+                &file,
+                0,
+                Arc::new(Cache::with_capacity_bytes(1_000_000)),
+                Arc::new(DescriptorTable::new(10)),
+                true,
+            )?;
+
+            assert_eq!(4, segment.metadata.item_count);
+            assert_eq!(3, segment.tombstone_count());
+            assert!((segment.tombstone_ratio() - 0.75).abs() < f32::EPSILON);
+        }
+
+        Ok(())
+    }
+
     #[test]
     #[allow(clippy::unwrap_used)]
     fn v3_segment_scan() -> crate::Result<()> {
@@ -509,6 +736,7 @@ mod tests {
                 0,
                 Arc::new(Cache::with_capacity_bytes(1_000_000)),
                 Arc::new(DescriptorTable::new(10)),
+                true,
             )?;
 
             assert_eq!(5, segment.id());
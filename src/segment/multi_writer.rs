@@ -3,9 +3,14 @@
 // (found in the LICENSE-* files in the repository)
 
 use super::{filter::BloomConstructionPolicy, writer::Writer};
-use crate::{value::InternalValue, CompressionType, SegmentId, UserKey};
+use crate::{
+    compaction::SegmentPartitioner,
+    rate_limiter::{IoPriority, RateLimiter},
+    value::InternalValue,
+    CompressionType, SegmentId, UserKey,
+};
 use std::{
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{atomic::AtomicU64, Arc},
 };
 
@@ -17,6 +22,7 @@ pub struct MultiWriter {
     base_path: PathBuf,
 
     data_block_size: u32,
+    index_block_size: u32,
 
     /// Target size of segments in bytes
     ///
@@ -33,8 +39,18 @@ pub struct MultiWriter {
 
     pub compression: CompressionType,
 
+    dictionary_size: u32,
+
     bloom_policy: BloomConstructionPolicy,
 
+    data_created_at: Option<u128>,
+
+    rate_limiter: Option<Arc<RateLimiter>>,
+    io_priority: IoPriority,
+
+    /// Forces segment boundaries between keys
+    partitioner: Option<Arc<dyn SegmentPartitioner>>,
+
     current_key: Option<UserKey>,
 }
 
@@ -55,6 +71,7 @@ impl MultiWriter {
             base_path,
 
             data_block_size: 4_096,
+            index_block_size: 4_096,
 
             target_size,
             results: Vec::with_capacity(10),
@@ -64,8 +81,17 @@ impl MultiWriter {
 
             compression: CompressionType::None,
 
+            dictionary_size: 0,
+
             bloom_policy: BloomConstructionPolicy::default(),
 
+            data_created_at: None,
+
+            rate_limiter: None,
+            io_priority: IoPriority::Low,
+
+            partitioner: None,
+
             current_key: None,
         })
     }
@@ -77,6 +103,18 @@ impl MultiWriter {
             "data block size must be <= 4 MiB",
         );
         self.data_block_size = size;
+        self.writer = self.writer.use_data_block_size(size);
+        self
+    }
+
+    #[must_use]
+    pub(crate) fn use_index_block_size(mut self, size: u32) -> Self {
+        assert!(
+            size <= 4 * 1_024 * 1_024,
+            "index block size must be <= 4 MiB",
+        );
+        self.index_block_size = size;
+        self.writer = self.writer.use_index_block_size(size);
         self
     }
 
@@ -87,6 +125,14 @@ impl MultiWriter {
         self
     }
 
+    /// Sets the maximum size of the compression dictionary trained per segment.
+    #[must_use]
+    pub fn use_dictionary_size(mut self, size: u32) -> Self {
+        self.dictionary_size = size;
+        self.writer = self.writer.use_dictionary_size(size);
+        self
+    }
+
     #[must_use]
     pub fn use_bloom_policy(mut self, bloom_policy: BloomConstructionPolicy) -> Self {
         self.bloom_policy = bloom_policy;
@@ -94,6 +140,36 @@ impl MultiWriter {
         self
     }
 
+    /// Sets the creation time (in nanoseconds) of the newest data in the segments.
+    ///
+    /// Used to inherit the age of compacted data, instead of giving it a fresh lease.
+    #[must_use]
+    pub fn use_data_created_at(mut self, timestamp: Option<u128>) -> Self {
+        self.data_created_at = timestamp;
+        self.writer = self.writer.use_data_created_at(timestamp);
+        self
+    }
+
+    /// Sets the rate limiter that throttles block writes.
+    #[must_use]
+    pub fn use_rate_limiter(
+        mut self,
+        rate_limiter: Option<Arc<RateLimiter>>,
+        priority: IoPriority,
+    ) -> Self {
+        self.rate_limiter.clone_from(&rate_limiter);
+        self.io_priority = priority;
+        self.writer = self.writer.use_rate_limiter(rate_limiter, priority);
+        self
+    }
+
+    /// Sets the partitioner that is consulted between keys to force segment boundaries.
+    #[must_use]
+    pub fn use_partitioner(mut self, partitioner: Option<Arc<dyn SegmentPartitioner>>) -> Self {
+        self.partitioner = partitioner;
+        self
+    }
+
     fn get_next_segment_id(&mut self) -> u64 {
         self.current_segment_id = self
             .segment_id_generator
@@ -106,18 +182,30 @@ impl MultiWriter {
     fn rotate(&mut self) -> crate::Result<()> {
         log::debug!("Rotating segment writer");
 
+        let old_segment_id = self.current_segment_id;
         let new_segment_id = self.get_next_segment_id();
         let path = self.base_path.join(new_segment_id.to_string());
 
-        let new_writer = Writer::new(path, new_segment_id)?
+        let new_writer = Writer::new(path, new_segment_id)
+            // NOTE: Keep pointing to the old writer's segment, so it can be cleaned up
+            .inspect_err(|_| self.current_segment_id = old_segment_id)?
             .use_compression(self.compression)
+            .use_dictionary_size(self.dictionary_size)
             .use_data_block_size(self.data_block_size)
-            .use_bloom_policy(self.bloom_policy);
+            .use_index_block_size(self.index_block_size)
+            .use_bloom_policy(self.bloom_policy)
+            .use_data_created_at(self.data_created_at)
+            .use_rate_limiter(self.rate_limiter.clone(), self.io_priority);
 
         let old_writer = std::mem::replace(&mut self.writer, new_writer);
 
-        if let Some(segment_id) = old_writer.finish()? {
-            self.results.push(segment_id);
+        match old_writer.finish() {
+            Ok(Some(segment_id)) => self.results.push(segment_id),
+            Ok(None) => {}
+            Err(e) => {
+                remove_segment_files(&self.base_path, std::iter::once(old_segment_id))?;
+                return Err(e);
+            }
         }
 
         Ok(())
@@ -128,9 +216,16 @@ impl MultiWriter {
         let is_next_key = self.current_key.as_ref() < Some(&item.key.user_key);
 
         if is_next_key {
+            let is_boundary = match (&self.partitioner, &self.current_key) {
+                (Some(partitioner), Some(prev_key)) => {
+                    partitioner.should_partition(prev_key, &item.key.user_key)
+                }
+                _ => false,
+            };
+
             self.current_key = Some(item.key.user_key.clone());
 
-            if *self.writer.meta.file_pos >= self.target_size {
+            if is_boundary || self.writer.size() >= self.target_size {
                 self.rotate()?;
             }
         }
@@ -140,18 +235,85 @@ impl MultiWriter {
         Ok(())
     }
 
+    /// Returns the amount of segments that have been finished so far.
+    pub fn segment_count(&self) -> usize {
+        self.results.len()
+    }
+
+    /// Deletes all segments that have been written so far, including the current one.
+    pub fn abort(self) -> crate::Result<()> {
+        let Self {
+            base_path,
+            results,
+            current_segment_id,
+            writer,
+            ..
+        } = self;
+
+        // NOTE: Close the file before deleting it
+        drop(writer);
+
+        remove_segment_files(
+            &base_path,
+            results
+                .into_iter()
+                .chain(std::iter::once(current_segment_id)),
+        )
+    }
+
     /// Finishes the last segment, making sure all data is written durably
     ///
     /// Returns the metadata of created segments
+    ///
+    /// If finishing fails, all segments that have been written are deleted.
     pub fn finish(mut self) -> crate::Result<Vec<SegmentId>> {
-        if let Some(last_writer_result) = self.writer.finish()? {
-            self.results.push(last_writer_result);
+        match self.writer.finish() {
+            Ok(Some(last_writer_result)) => self.results.push(last_writer_result),
+            Ok(None) => {}
+            Err(e) => {
+                remove_segment_files(
+                    &self.base_path,
+                    self.results
+                        .into_iter()
+                        .chain(std::iter::once(self.current_segment_id)),
+                )?;
+                return Err(e);
+            }
         }
 
         Ok(self.results)
     }
 }
 
+/// Deletes the given segment files, skipping files that do not exist.
+///
+/// Tries to delete every file, even if deleting one of them fails,
+/// and returns the first error.
+pub fn remove_segment_files(
+    base_path: &Path,
+    segment_ids: impl IntoIterator<Item = SegmentId>,
+) -> crate::Result<()> {
+    let mut result = Ok(());
+
+    for segment_id in segment_ids {
+        let path = base_path.join(segment_id.to_string());
+
+        if let Err(e) = std::fs::remove_file(&path) {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                continue;
+            }
+
+            log::warn!("Failed to cleanup segment at {}: {e:?}", path.display());
+
+            if result.is_ok() {
+                result = Err(e.into());
+            }
+        }
+    }
+
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{AbstractTree, Config};
@@ -2,10 +2,19 @@
 // This source code is licensed under both the Apache 2.0 and MIT License
 // (found in the LICENSE-* files in the repository)
 
-use super::{Block, DataBlock};
-use crate::{CompressionType, InternalValue};
+use super::{block::Header as BlockHeader, Block, BlockOffset, DataBlock};
+use crate::{
+    compression::CompressionDictionary,
+    rate_limiter::{IoPriority, RateLimiter},
+    CompressionType, InternalValue,
+};
 use self_cell::self_cell;
-use std::{fs::File, io::BufReader, path::Path};
+use std::{
+    fs::File,
+    io::{BufReader, Seek, SeekFrom},
+    path::Path,
+    sync::Arc,
+};
 
 type BlockIter<'a> = Box<dyn Iterator<Item = InternalValue> + 'a>;
 
@@ -21,11 +30,15 @@ self_cell!(
 /// Segment reader that is optimized for consuming an entire segment
 pub struct Scanner {
     reader: BufReader<File>,
-    iter: Iter,
+    iter: Option<Iter>,
 
     compression: CompressionType,
+    dictionary: Option<Arc<CompressionDictionary>>,
     block_count: usize,
     read_count: usize,
+
+    /// Rate limiter that is charged for every read block
+    rate_limiter: Option<Arc<RateLimiter>>,
 }
 
 impl Scanner {
@@ -33,28 +46,64 @@ impl Scanner {
         path: &Path,
         block_count: usize,
         compression: CompressionType,
+    ) -> crate::Result<Self> {
+        Self::new_at(path, BlockOffset(0), block_count, compression)
+    }
+
+    /// Creates a scanner that starts reading at the data block at the given offset.
+    pub fn new_at(
+        path: &Path,
+        offset: BlockOffset,
+        block_count: usize,
+        compression: CompressionType,
     ) -> crate::Result<Self> {
         // TODO: a larger buffer size may be better for HDD, maybe make this configurable
         let mut reader = BufReader::with_capacity(8 * 4_096, File::open(path)?);
-
-        let block = Self::fetch_next_block(&mut reader, compression)?;
-        let iter = Iter::new(block, |block| Box::new(block.iter()));
+        reader.seek(SeekFrom::Start(*offset))?;
 
         Ok(Self {
             reader,
-            iter,
+            iter: None,
 
             compression,
+            dictionary: None,
             block_count,
-            read_count: 1,
+            read_count: 0,
+
+            rate_limiter: None,
         })
     }
 
-    fn fetch_next_block(
-        reader: &mut BufReader<File>,
-        compression: CompressionType,
-    ) -> crate::Result<DataBlock> {
-        Block::from_reader(reader, compression).map(DataBlock::new)
+    /// Sets the rate limiter that throttles block reads.
+    ///
+    /// Reads are charged with low (compaction) priority.
+    #[must_use]
+    pub fn use_rate_limiter(mut self, rate_limiter: Option<Arc<RateLimiter>>) -> Self {
+        self.rate_limiter = rate_limiter;
+        self
+    }
+
+    /// Sets the compression dictionary of the segment's data blocks.
+    #[must_use]
+    pub(crate) fn use_dictionary(mut self, dictionary: Option<Arc<CompressionDictionary>>) -> Self {
+        self.dictionary = dictionary;
+        self
+    }
+
+    fn fetch_next_block(&mut self) -> crate::Result<DataBlock> {
+        let block = Block::from_reader(
+            &mut self.reader,
+            self.compression,
+            self.dictionary.as_deref(),
+        )?;
+
+        if let Some(limiter) = &self.rate_limiter {
+            let bytes_read =
+                BlockHeader::serialized_len() as u64 + u64::from(block.header.data_length);
+            limiter.request(bytes_read, IoPriority::Low);
+        }
+
+        Ok(DataBlock::new(block))
     }
 }
 
@@ -63,8 +112,10 @@ impl Iterator for Scanner {
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            if let Some(item) = self.iter.with_dependent_mut(|_, iter| iter.next()) {
-                return Some(Ok(item));
+            if let Some(iter) = &mut self.iter {
+                if let Some(item) = iter.with_dependent_mut(|_, iter| iter.next()) {
+                    return Some(Ok(item));
+                }
             }
 
             if self.read_count >= self.block_count {
@@ -72,8 +123,8 @@ impl Iterator for Scanner {
             }
 
             // Init new block
-            let block = fail_iter!(Self::fetch_next_block(&mut self.reader, self.compression));
-            self.iter = Iter::new(block, |block| Box::new(block.iter()));
+            let block = fail_iter!(self.fetch_next_block());
+            self.iter = Some(Iter::new(block, |block| Box::new(block.iter())));
 
             self.read_count += 1;
         }
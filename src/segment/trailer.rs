@@ -26,6 +26,8 @@ const TRAILER_SIZE: usize = 128;
 /// |--------------|
 /// | filter block | <- may not exist
 /// |--------------|
+/// |  dictionary  | <- may not exist
+/// |--------------|
 /// |  ... TBD ... |
 /// |--------------|
 /// |   meta block |
@@ -47,6 +49,9 @@ pub struct Trailer {
     // // TODO: #46 https://github.com/fjall-rs/lsm-tree/issues/46
     // pub range_filter: BlockOffset,
     pub metadata: BlockHandle,
+
+    /// Compression dictionary for data blocks
+    pub dictionary: Option<BlockHandle>,
 }
 
 impl Trailer {
@@ -122,6 +127,12 @@ impl Encode for Trailer {
 
         self.metadata.encode_into(writer)?;
 
+        if let Some(handle) = &self.dictionary {
+            handle.encode_into(writer)
+        } else {
+            BlockHandle::default().encode_into(writer)
+        }?;
+
         Ok(())
     }
 }
@@ -133,6 +144,9 @@ impl Decode for Trailer {
         let filter = BlockHandle::decode_from(reader)?;
         let metadata = BlockHandle::decode_from(reader)?;
 
+        // NOTE: Trailers without a dictionary handle are zero padded
+        let dictionary = BlockHandle::decode_from(reader)?;
+
         Ok(Self {
             index_blocks: match *index_blocks.offset() {
                 0 => None,
@@ -144,6 +158,10 @@ impl Decode for Trailer {
                 _ => Some(filter),
             },
             metadata,
+            dictionary: match *dictionary.offset() {
+                0 => None,
+                _ => Some(dictionary),
+            },
         })
     }
 }
@@ -162,6 +180,7 @@ mod tests {
             index_blocks: Some(BlockHandle::new(BlockOffset(20), 5)),
             filter: Some(BlockHandle::new(BlockOffset(25), 5)),
             metadata: BlockHandle::new(BlockOffset(30), 5),
+            dictionary: Some(BlockHandle::new(BlockOffset(35), 5)),
         };
 
         let buf = before.encode_into_vec();
@@ -73,7 +73,7 @@ impl<W: std::io::Write + std::io::Seek> BlockIndexWriter<W> for FullIndexWriter
         let bytes =
             IndexBlock::encode_items(&self.block_handles, 1 /* TODO: hard coded for now */)?;
 
-        let header = Block::to_writer(block_file_writer, &bytes, self.compression)?;
+        let header = Block::to_writer(block_file_writer, &bytes, self.compression, None)?;
 
         // NOTE: We know that blocks never even approach u32 size
         #[allow(clippy::cast_possible_truncation)]
@@ -141,7 +141,7 @@ impl Writer {
             IndexBlock::encode_items(&self.block_handles, 1 /* TODO: hard coded for now */)?;
 
         // TODO: prev block offset
-        let _header = Block::to_writer(&mut self.write_buffer, &bytes, self.compression)?;
+        let _header = Block::to_writer(&mut self.write_buffer, &bytes, self.compression, None)?;
 
         let bytes_written = (BlockHeader::serialized_len() + bytes.len()) as u32;
 
@@ -219,7 +219,7 @@ impl Writer {
         let bytes =
             IndexBlock::encode_items(&self.tli_pointers, 1 /* TODO: hard coded for now */)?;
 
-        let _header = Block::to_writer(block_file_writer, &bytes, self.compression)?;
+        let _header = Block::to_writer(block_file_writer, &bytes, self.compression, None)?;
 
         // NOTE: We know that blocks never even approach u32 size
         #[allow(clippy::cast_possible_truncation)]
@@ -7,7 +7,9 @@ use super::{
 };
 use crate::{
     coding::Encode,
+    compression::CompressionDictionary,
     file::fsync_directory,
+    rate_limiter::{IoPriority, RateLimiter},
     segment::{filter::standard_bloom::Builder, index_block::BlockHandle},
     time::unix_timestamp,
     CompressionType, InternalValue, SegmentId, UserKey,
@@ -17,6 +19,7 @@ use std::{
     fs::File,
     io::{BufWriter, Seek, Write},
     path::PathBuf,
+    sync::Arc,
 };
 
 /// Serializes and compresses values into blocks and writes them to disk as segment
@@ -32,6 +35,19 @@ pub struct Writer {
     /// Compression to use
     compression: CompressionType,
 
+    /// Maximum size of the trained compression dictionary (0 = no dictionary)
+    dictionary_size: u32,
+
+    /// Compression dictionary, once trained
+    dictionary: Option<CompressionDictionary>,
+
+    /// Data blocks that are held back until the dictionary is trained
+    ///
+    /// Used as training samples.
+    dictionary_samples: Vec<(UserKey, Vec<u8>)>,
+    dictionary_samples_size: usize,
+    dictionary_trained: bool,
+
     /// Writer of data blocks
     #[allow(clippy::struct_field_names)]
     block_writer: BufWriter<File>,
@@ -52,6 +68,12 @@ pub struct Writer {
 
     bloom_policy: BloomConstructionPolicy,
 
+    /// Creation time of the newest data (if it is older than the segment)
+    data_created_at: Option<u128>,
+
+    /// Rate limiter that is charged for every written block
+    rate_limiter: Option<(Arc<RateLimiter>, IoPriority)>,
+
     /// Hashes for bloom filter
     ///
     /// using enhanced double hashing, so we got two u64s
@@ -73,6 +95,12 @@ impl Writer {
 
             compression: CompressionType::None,
 
+            dictionary_size: 0,
+            dictionary: None,
+            dictionary_samples: Vec::new(),
+            dictionary_samples_size: 0,
+            dictionary_trained: false,
+
             path: std::path::absolute(path)?,
 
             index_writer: Box::new(FullIndexWriter::new()),
@@ -88,6 +116,10 @@ impl Writer {
 
             bloom_policy: BloomConstructionPolicy::default(),
 
+            data_created_at: None,
+
+            rate_limiter: None,
+
             bloom_hash_buffer: Vec::new(),
         })
     }
@@ -102,6 +134,16 @@ impl Writer {
         self
     }
 
+    #[must_use]
+    pub(crate) fn use_index_block_size(mut self, size: u32) -> Self {
+        assert!(
+            size <= 4 * 1_024 * 1_024,
+            "index block size must be <= 4 MiB",
+        );
+        self.index_block_size = size;
+        self
+    }
+
     #[must_use]
     pub(crate) fn use_compression(mut self, compression: CompressionType) -> Self {
         self.compression = compression;
@@ -109,12 +151,48 @@ impl Writer {
         self
     }
 
+    /// Sets the maximum size of the compression dictionary
+    /// that is trained from the segment's data blocks.
+    ///
+    /// Only used by compression types that support dictionaries.
+    #[must_use]
+    pub(crate) fn use_dictionary_size(mut self, size: u32) -> Self {
+        self.dictionary_size = size;
+        self
+    }
+
     #[must_use]
     pub(crate) fn use_bloom_policy(mut self, bloom_policy: BloomConstructionPolicy) -> Self {
         self.bloom_policy = bloom_policy;
         self
     }
 
+    /// Sets the creation time (in nanoseconds) of the newest data in the segment.
+    ///
+    /// Defaults to the segment's creation time.
+    #[must_use]
+    pub(crate) fn use_data_created_at(mut self, timestamp: Option<u128>) -> Self {
+        self.data_created_at = timestamp;
+        self
+    }
+
+    /// Sets the rate limiter that throttles block writes.
+    #[must_use]
+    pub(crate) fn use_rate_limiter(
+        mut self,
+        rate_limiter: Option<Arc<RateLimiter>>,
+        priority: IoPriority,
+    ) -> Self {
+        self.rate_limiter = rate_limiter.map(|limiter| (limiter, priority));
+        self
+    }
+
+    /// Returns the size of the segment so far, including
+    /// data blocks that are held back for dictionary training.
+    pub(crate) fn size(&self) -> u64 {
+        *self.meta.file_pos + self.dictionary_samples_size as u64
+    }
+
     /// Writes an item.
     ///
     /// # Note
@@ -161,60 +239,135 @@ impl Writer {
         Ok(())
     }
 
+    /// Returns `true` if data blocks are held back to train a compression dictionary.
+    #[cfg_attr(not(feature = "zstd"), allow(clippy::unused_self))]
+    fn is_sampling(&self) -> bool {
+        #[cfg(feature = "zstd")]
+        if let CompressionType::Zstd(_) = self.compression {
+            return self.dictionary_size > 0 && !self.dictionary_trained;
+        }
+
+        false
+    }
+
+    /// Trains the compression dictionary from the held back data blocks, and writes them.
+    fn train_dictionary(&mut self) -> crate::Result<()> {
+        let samples = std::mem::take(&mut self.dictionary_samples);
+        self.dictionary_samples_size = 0;
+        self.dictionary_trained = true;
+
+        #[cfg(feature = "zstd")]
+        if let CompressionType::Zstd(level) = self.compression {
+            let start = std::time::Instant::now();
+
+            let blocks = samples.iter().map(|(_, bytes)| bytes).collect::<Vec<_>>();
+
+            match CompressionDictionary::train(&blocks, self.dictionary_size as usize, level) {
+                Ok(dictionary) => {
+                    log::trace!(
+                        "Trained {}B compression dictionary from {} blocks in {:?}",
+                        dictionary.as_bytes().len(),
+                        blocks.len(),
+                        start.elapsed(),
+                    );
+                    self.dictionary = Some(dictionary);
+                }
+                Err(e) => {
+                    // NOTE: Training fails if there are too few samples, which is fine
+                    log::debug!("Could not train compression dictionary, not using one: {e:?}");
+                }
+            }
+        }
+
+        for (last_key, bytes) in samples {
+            self.write_data_block(last_key, &bytes)?;
+        }
+
+        Ok(())
+    }
+
     /// Writes a compressed block to disk.
     ///
     /// This is triggered when a `Writer::write` causes the buffer to grow to the configured `block_size`.
     ///
     /// Should only be called when the block has items in it.
     pub(crate) fn spill_block(&mut self) -> crate::Result<()> {
-        let Some(last) = self.chunk.last() else {
+        if self.chunk.is_empty() {
             return Ok(());
-        };
+        }
 
         let bytes = DataBlock::encode_items(&self.chunk, 16, 1.33)?;
 
+        self.meta.item_count += self.chunk.len();
+
+        // NOTE: Expect is fine, because the chunk is not empty
+        //
+        // Also, we are allowed to remove the last item
+        // to get ownership of it, because the chunk is cleared after
+        // this anyway
+        #[allow(clippy::expect_used)]
+        let last_key = self
+            .chunk
+            .pop()
+            .expect("chunk should not be empty")
+            .key
+            .user_key;
+
+        // Set last key
+        self.meta.last_key = Some(last_key.clone());
+
+        // IMPORTANT: Clear chunk after everything else
+        self.chunk.clear();
+        self.chunk_size = 0;
+
+        if self.is_sampling() {
+            self.dictionary_samples_size += bytes.len();
+            self.dictionary_samples.push((last_key, bytes));
+
+            // NOTE: Zstd recommends ~100x the dictionary size as training data
+            if self.dictionary_samples_size >= self.dictionary_size as usize * 100 {
+                self.train_dictionary()?;
+            }
+
+            return Ok(());
+        }
+
+        self.write_data_block(last_key, &bytes)
+    }
+
+    /// Compresses and writes an encoded data block, and registers it in the block index.
+    fn write_data_block(&mut self, last_key: UserKey, bytes: &[u8]) -> crate::Result<()> {
         // TODO: prev block offset
-        let header = Block::to_writer(&mut self.block_writer, &bytes, self.compression)?;
+        let header = Block::to_writer(
+            &mut self.block_writer,
+            bytes,
+            self.compression,
+            self.dictionary.as_ref(),
+        )?;
 
         self.meta.uncompressed_size += u64::from(header.uncompressed_length);
 
         let bytes_written = BlockHeader::serialized_len() as u32 + header.data_length;
 
+        if let Some((limiter, priority)) = &self.rate_limiter {
+            limiter.request(bytes_written.into(), *priority);
+        }
+
         self.index_writer
             .register_data_block(KeyedBlockHandle::new(
-                last.key.user_key.clone(),
+                last_key,
                 self.meta.file_pos,
                 bytes_written,
             ))?;
 
         // Adjust metadata
         self.meta.file_pos += bytes_written as u64;
-        self.meta.item_count += self.chunk.len();
         self.meta.data_block_count += 1;
 
         // Back link stuff
         self.prev_pos.0 = self.prev_pos.1;
         self.prev_pos.1 += bytes_written as u64;
 
-        // Set last key
-        self.meta.last_key = Some(
-            // NOTE: Expect is fine, because the chunk is not empty
-            //
-            // Also, we are allowed to remove the last item
-            // to get ownership of it, because the chunk is cleared after
-            // this anyway
-            #[allow(clippy::expect_used)]
-            self.chunk
-                .pop()
-                .expect("chunk should not be empty")
-                .key
-                .user_key,
-        );
-
-        // IMPORTANT: Clear chunk after everything else
-        self.chunk.clear();
-        self.chunk_size = 0;
-
         Ok(())
     }
 
@@ -224,6 +377,10 @@ impl Writer {
     pub fn finish(mut self) -> crate::Result<Option<SegmentId>> {
         self.spill_block()?;
 
+        if !self.dictionary_samples.is_empty() {
+            self.train_dictionary()?;
+        }
+
         // No items written! Just delete segment file and return nothing
         if self.meta.item_count == 0 {
             std::fs::remove_file(&self.path)?;
@@ -265,7 +422,7 @@ impl Writer {
                 let bytes = filter.encode_into_vec();
 
                 let block =
-                    Block::to_writer(&mut self.block_writer, &bytes, CompressionType::None)?;
+                    Block::to_writer(&mut self.block_writer, &bytes, CompressionType::None, None)?;
 
                 let bytes_written = (BlockHeader::serialized_len() as u32) + block.data_length;
 
@@ -274,6 +431,26 @@ impl Writer {
         };
         log::trace!("filter_ptr={filter_handle:?}");
 
+        // Write compression dictionary
+        let dictionary_handle = match &self.dictionary {
+            Some(dictionary) => {
+                let dictionary_ptr = self.block_writer.stream_position()?;
+
+                let header = Block::to_writer(
+                    &mut self.block_writer,
+                    dictionary.as_bytes(),
+                    CompressionType::None,
+                    None,
+                )?;
+
+                let bytes_written = BlockHeader::serialized_len() as u32 + header.data_length;
+
+                Some(BlockHandle::new(BlockOffset(dictionary_ptr), bytes_written))
+            }
+            None => None,
+        };
+        log::trace!("dictionary_ptr={dictionary_handle:?}");
+
         // // TODO: #46 https://github.com/fjall-rs/lsm-tree/issues/46 - Write range filter
         // let rf_ptr = BlockOffset(0);
         // log::trace!("rf_ptr={rf_ptr}");
@@ -289,6 +466,9 @@ impl Writer {
         // Write metadata
         let metadata_start = BlockOffset(self.block_writer.stream_position()?);
 
+        let created_at = unix_timestamp().as_nanos();
+        let data_created_at = self.data_created_at.unwrap_or(created_at);
+
         let metadata_handle = {
             fn meta(key: &str, value: &[u8]) -> InternalValue {
                 InternalValue::from_components(key, value, 0, crate::ValueType::Value)
@@ -297,11 +477,12 @@ impl Writer {
             let meta_items = [
                 meta("#checksum_type", b"xxh3"),
                 meta("#compression#data", &self.compression.encode_into_vec()),
-                meta("#created_at", &unix_timestamp().as_nanos().to_le_bytes()),
+                meta("#created_at", &created_at.to_le_bytes()),
                 meta(
                     "#data_block_count",
                     &(self.meta.data_block_count as u64).to_le_bytes(),
                 ),
+                meta("#data_created_at", &data_created_at.to_le_bytes()),
                 meta("#hash_type", b"xxh3"),
                 meta("#id", &self.segment_id.to_le_bytes()),
                 meta(
@@ -350,7 +531,8 @@ impl Writer {
 
             // TODO: no binary index
             let bytes = DataBlock::encode_items(&meta_items, 1, 0.0)?;
-            let header = Block::to_writer(&mut self.block_writer, &bytes, CompressionType::None)?;
+            let header =
+                Block::to_writer(&mut self.block_writer, &bytes, CompressionType::None, None)?;
 
             let bytes_written = BlockHeader::serialized_len() as u32 + header.data_length;
 
@@ -363,6 +545,7 @@ impl Writer {
             index_blocks: None,
             filter: filter_handle,
             metadata: metadata_handle,
+            dictionary: dictionary_handle,
             /* range_filter:range_filter_ptr: rf:rf_ptr,
             range_tombstones:range_tombstones_ptr,
             pfx:pfx_ptr, */
@@ -24,6 +24,9 @@ impl<'a> Ingestion<'a> {
             "can only perform bulk_ingest on empty trees",
         );
 
+        // NOTE: Ingested segments are moved into the last level
+        let last_level = tree.config.level_count - 1;
+
         let folder = tree.config.path.join(SEGMENTS_FOLDER);
         log::debug!("Ingesting into disk segments in {folder:?}");
 
@@ -38,19 +41,12 @@ impl<'a> Ingestion<'a> {
                 segment_id: 0, /* TODO: unused */
             }, */
         )?
-        .use_compression(tree.config.compression);
-
-        /* {
-            use crate::segment::writer::BloomConstructionPolicy;
-
-            if tree.config.bloom_bits_per_key >= 0 {
-                writer = writer.use_bloom_policy(BloomConstructionPolicy::BitsPerKey(
-                    tree.config.bloom_bits_per_key.unsigned_abs(),
-                ));
-            } else {
-                writer = writer.use_bloom_policy(BloomConstructionPolicy::BitsPerKey(0));
-            }
-        } */
+        .use_compression(tree.config.level_compression(last_level))
+        .use_dictionary_size(tree.config.zstd_dictionary_size)
+        .use_data_block_size(tree.config.level_data_block_size(last_level))
+        .use_index_block_size(tree.config.level_index_block_size(last_level))
+        .use_bloom_policy(tree.config.level_bloom_policy(last_level))
+        .use_partitioner(tree.config.segment_partitioner.clone());
 
         Ok(Self {
             folder,
@@ -85,6 +81,7 @@ impl<'a> Ingestion<'a> {
                     self.tree.id,
                     self.tree.config.cache.clone(),
                     self.tree.config.descriptor_table.clone(),
+                    self.tree.config.verify_cache_hits,
                 )
 
                 // todo!()
@@ -3,10 +3,16 @@
 // (found in the LICENSE-* files in the repository)
 
 use crate::{
-    config::Config, file::LEVELS_MANIFEST_FILE, level_manifest::LevelManifest, memtable::Memtable,
-    stop_signal::StopSignal, SegmentId,
+    blob_tree::relocation::ValueLogHandle,
+    compaction::{progress::ActiveCompactions, stats::StatsRecorder},
+    config::Config,
+    file::LEVELS_MANIFEST_FILE,
+    level_manifest::LevelManifest,
+    memtable::Memtable,
+    stop_signal::StopSignal,
+    SegmentId,
 };
-use std::sync::{atomic::AtomicU64, Arc, RwLock};
+use std::sync::{atomic::AtomicU64, Arc, OnceLock, RwLock};
 
 /// Unique tree ID
 ///
@@ -30,8 +36,9 @@ impl SealedMemtables {
         self.0.push((id, memtable));
     }
 
-    pub fn remove(&mut self, id_to_remove: MemtableId) {
-        self.0.retain(|(id, _)| *id != id_to_remove);
+    pub fn remove(&mut self, id_to_remove: MemtableId) -> Option<Arc<Memtable>> {
+        let idx = self.0.iter().position(|(id, _)| *id == id_to_remove)?;
+        Some(self.0.remove(idx).1)
     }
 
     pub fn iter(&self) -> impl DoubleEndedIterator<Item = &(MemtableId, Arc<Memtable>)> {
@@ -75,7 +82,16 @@ pub struct TreeInner {
     /// will interrupt the compaction and kill the worker.
     pub(crate) stop_signal: StopSignal,
 
+    /// Compactions that are currently running
+    pub(crate) active_compactions: ActiveCompactions,
+
+    /// Cumulative compaction statistics
+    pub(crate) compaction_stats: StatsRecorder,
+
     pub(crate) major_compaction_lock: RwLock<()>,
+
+    /// Value log, if this is the index tree of a blob tree
+    pub(crate) value_log: OnceLock<ValueLogHandle>,
 }
 
 impl TreeInner {
@@ -86,12 +102,15 @@ impl TreeInner {
         Ok(Self {
             id: get_next_tree_id(),
             segment_id_counter: Arc::new(AtomicU64::default()),
+            active_memtable: Arc::new(RwLock::new(Arc::new(Memtable::new(config.memtable_type)))),
             config,
-            active_memtable: Arc::default(),
             sealed_memtables: Arc::default(),
             levels: Arc::new(RwLock::new(levels)),
             stop_signal: StopSignal::default(),
+            active_compactions: ActiveCompactions::default(),
+            compaction_stats: StatsRecorder::default(),
             major_compaction_lock: RwLock::default(),
+            value_log: OnceLock::new(),
         })
     }
 
@@ -107,5 +126,23 @@ impl Drop for TreeInner {
 
         log::trace!("Sending stop signal to compactors");
         self.stop_signal.send();
+
+        // NOTE: Give back the memory of our memtables,
+        // otherwise they would count against the shared budget forever
+        if let Some(manager) = &self.config.write_buffer_manager {
+            let active_size = self
+                .active_memtable
+                .read()
+                .map(|memtable| memtable.size())
+                .unwrap_or_default();
+
+            let sealed_size: u64 = self
+                .sealed_memtables
+                .read()
+                .map(|sealed| sealed.iter().map(|(_, memtable)| memtable.size()).sum())
+                .unwrap_or_default();
+
+            manager.free(active_size + sealed_size);
+        }
     }
 }
@@ -7,7 +7,10 @@ pub mod inner;
 
 use crate::{
     coding::{Decode, Encode},
-    compaction::CompactionStrategy,
+    compaction::{
+        progress::ActiveCompactions, stats::StatsRecorder, CompactionHandle, CompactionStats,
+        CompactionStrategy,
+    },
     config::Config,
     level_manifest::LevelManifest,
     manifest::Manifest,
@@ -15,25 +18,37 @@ use crate::{
     segment::Segment,
     value::InternalValue,
     version::Version,
-    AbstractTree, Cache, DescriptorTable, KvPair, SegmentId, SeqNo, Snapshot, UserKey, UserValue,
-    ValueType,
+    AbstractTree, Cache, DescriptorTable, IoPriority, KvPair, SegmentId, SeqNo, Snapshot, UserKey,
+    UserValue, ValueType,
 };
 use inner::{MemtableId, SealedMemtables, TreeId, TreeInner};
 use std::{
     io::Cursor,
     ops::RangeBounds,
     path::Path,
-    sync::{atomic::AtomicU64, Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    sync::{atomic::AtomicU64, Arc, OnceLock, RwLock, RwLockReadGuard, RwLockWriteGuard},
 };
 
+/// Hides tombstones and expired values, and strips the expiry timestamp of live values
 fn ignore_tombstone_value(item: InternalValue) -> Option<InternalValue> {
-    if item.is_tombstone() {
+    ignore_dead_value(item).map(InternalValue::into_user_value)
+}
+
+/// Hides tombstones and expired values, but keeps the expiry timestamp of live values
+fn ignore_dead_value(item: InternalValue) -> Option<InternalValue> {
+    if item.is_tombstone() || is_expired(&item) {
         None
     } else {
         Some(item)
     }
 }
 
+fn is_expired(item: &InternalValue) -> bool {
+    // NOTE: Only get the current time if the value can actually expire
+    item.expires_at()
+        .is_some_and(|expires_at| expires_at <= crate::time::unix_timestamp().as_secs())
+}
+
 /// A log-structured merge tree (LSM-tree/LSMT)
 #[derive(Clone)]
 pub struct Tree(#[doc(hidden)] pub Arc<TreeInner>);
@@ -89,6 +104,15 @@ impl AbstractTree for Tree {
     fn major_compact(&self, target_size: u64, seqno_threshold: SeqNo) -> crate::Result<()> {
         let strategy = Arc::new(crate::compaction::major::Strategy::new(target_size));
 
+        log::info!("Starting major compaction");
+        self.inner_compact(strategy, seqno_threshold, None, true)
+    }
+
+    fn drop_range(&self, key_range: crate::KeyRange) -> crate::Result<()> {
+        use crate::compaction::worker::{do_drop_range, Options};
+
+        let strategy = Arc::new(crate::compaction::drop_range::Strategy::new(key_range));
+
         // IMPORTANT: Write lock so we can be the only compaction going on
         let _lock = self
             .0
@@ -96,8 +120,47 @@ impl AbstractTree for Tree {
             .write()
             .expect("lock is poisoned");
 
-        log::info!("Starting major compaction");
-        self.inner_compact(strategy, seqno_threshold)
+        log::info!("Starting drop_range compaction");
+        do_drop_range(&Options::from_tree(self, strategy.clone()), &strategy)
+    }
+
+    fn compact_range(
+        &self,
+        key_range: crate::KeyRange,
+        target_level: u8,
+        seqno_threshold: SeqNo,
+    ) -> crate::Result<()> {
+        use crate::compaction::worker::{do_compact_range, Options};
+
+        let last_level_index = self
+            .levels
+            .read()
+            .expect("lock is poisoned")
+            .last_level_index();
+
+        if target_level > last_level_index {
+            return Err(crate::Error::InvalidLevel(target_level));
+        }
+
+        let strategy = Arc::new(crate::compaction::compact_range::Strategy::new(
+            key_range,
+            target_level,
+        ));
+
+        // NOTE: Read lock major compaction lock
+        // That way, if a major compaction is running, we cannot proceed
+        // But other (non-major) compactions can run in parallel
+        let _lock = self
+            .0
+            .major_compaction_lock
+            .read()
+            .expect("lock is poisoned");
+
+        let mut opts = Options::from_tree(self, strategy.clone());
+        opts.eviction_seqno = seqno_threshold;
+
+        log::info!("Starting compact_range compaction");
+        do_compact_range(&opts, &strategy)
     }
 
     fn l0_run_count(&self) -> usize {
@@ -118,6 +181,18 @@ impl AbstractTree for Tree {
         }
     }
 
+    fn active_compactions(&self) -> Vec<CompactionHandle> {
+        self.active_compactions.list()
+    }
+
+    fn compaction_stats(&self) -> CompactionStats {
+        self.compaction_stats.get()
+    }
+
+    fn set_compaction_stats(&self, stats: CompactionStats) {
+        self.compaction_stats.set(stats);
+    }
+
     fn size_of<K: AsRef<[u8]>>(&self, key: K, seqno: Option<SeqNo>) -> crate::Result<Option<u32>> {
         Ok(self.get(key, seqno)?.map(|x| x.len() as u32))
     }
@@ -138,22 +213,17 @@ impl AbstractTree for Tree {
             .len()
     }
 
-    /* fn verify(&self) -> crate::Result<usize> {
-        // NOTE: Lock memtable to prevent any tampering with disk segments
-        let _lock = self.lock_active_memtable();
-
-        let mut sum = 0;
+    fn verify(&self) -> crate::Result<usize> {
+        let mut corrupted = vec![];
 
-        let level_manifest = self.levels.read().expect("lock is poisoned");
+        let item_count = self.verify_segments(&mut corrupted)?;
 
-        for level in &level_manifest.levels {
-            for segment in &level.segments {
-                sum += segment.verify()?;
-            }
+        if corrupted.is_empty() {
+            Ok(item_count)
+        } else {
+            Err(crate::Error::Corrupted(corrupted))
         }
-
-        Ok(sum)
-    } */
+    }
 
     fn keys(
         &self,
@@ -187,17 +257,12 @@ impl AbstractTree for Tree {
         log::debug!("writing segment to {segment_file_path:?}");
 
         let mut segment_writer = Writer::new(segment_file_path, segment_id)?
-            .use_compression(self.config.compression)
-            .use_data_block_size(self.config.data_block_size)
-            .use_bloom_policy({
-                use crate::segment::filter::BloomConstructionPolicy;
-
-                if self.config.bloom_bits_per_key >= 0 {
-                    BloomConstructionPolicy::FpRate(0.00001)
-                } else {
-                    BloomConstructionPolicy::BitsPerKey(0)
-                }
-            });
+            .use_compression(self.config.level_compression(0))
+            .use_dictionary_size(self.config.zstd_dictionary_size)
+            .use_data_block_size(self.config.level_data_block_size(0))
+            .use_index_block_size(self.config.level_index_block_size(0))
+            .use_rate_limiter(self.config.rate_limiter.clone(), IoPriority::High)
+            .use_bloom_policy(self.config.level_bloom_policy(0));
 
         let iter = memtable.iter().map(Ok);
         let compaction_filter = CompactionStream::new(iter, seqno_threshold);
@@ -208,6 +273,10 @@ impl AbstractTree for Tree {
 
         let result = self.consume_writer(segment_id, segment_writer)?;
 
+        if let Some(segment) = &result {
+            self.compaction_stats.record_flush(segment.metadata.file_size);
+        }
+
         log::debug!("Flushed memtable {segment_id:?} in {:?}", start.elapsed());
 
         Ok(result)
@@ -237,7 +306,12 @@ impl AbstractTree for Tree {
 
         for segment in segments {
             log::trace!("releasing sealed memtable {}", segment.id());
-            sealed_memtables.remove(segment.id());
+
+            if let Some(memtable) = sealed_memtables.remove(segment.id()) {
+                if let Some(manager) = &self.config.write_buffer_manager {
+                    manager.free(memtable.size());
+                }
+            }
         }
 
         Ok(())
@@ -248,16 +322,27 @@ impl AbstractTree for Tree {
     }
 
     fn clear_active_memtable(&self) {
-        *self.active_memtable.write().expect("lock is poisoned") = Arc::new(Memtable::default());
+        self.set_active_memtable(Memtable::new(self.config.memtable_type));
     }
 
     fn set_active_memtable(&self, memtable: Memtable) {
         let mut memtable_lock = self.active_memtable.write().expect("lock is poisoned");
+
+        if let Some(manager) = &self.config.write_buffer_manager {
+            manager.free(memtable_lock.size());
+            manager.allocate(memtable.size());
+        }
+
         *memtable_lock = Arc::new(memtable);
     }
 
     fn add_sealed_memtable(&self, id: MemtableId, memtable: Arc<Memtable>) {
         let mut memtable_lock = self.sealed_memtables.write().expect("lock is poisoned");
+
+        if let Some(manager) = &self.config.write_buffer_manager {
+            manager.allocate(memtable.size());
+        }
+
         memtable_lock.add(id, memtable);
     }
 
@@ -266,16 +351,16 @@ impl AbstractTree for Tree {
         strategy: Arc<dyn CompactionStrategy>,
         seqno_threshold: SeqNo,
     ) -> crate::Result<()> {
-        // NOTE: Read lock major compaction lock
-        // That way, if a major compaction is running, we cannot proceed
-        // But in general, parallel (non-major) compactions can occur
-        let _lock = self
-            .0
-            .major_compaction_lock
-            .read()
-            .expect("lock is poisoned");
+        self.inner_compact(strategy, seqno_threshold, None, false)
+    }
 
-        self.inner_compact(strategy, seqno_threshold)
+    fn compact_with_snapshots(
+        &self,
+        strategy: Arc<dyn CompactionStrategy>,
+        seqno_threshold: SeqNo,
+        snapshots: &[SeqNo],
+    ) -> crate::Result<()> {
+        self.inner_compact(strategy, seqno_threshold, Some(snapshots), false)
     }
 
     fn get_next_segment_id(&self) -> SegmentId {
@@ -311,8 +396,10 @@ impl AbstractTree for Tree {
             return None;
         }
 
-        let yanked_memtable = std::mem::take(&mut *active_memtable);
-        let yanked_memtable = yanked_memtable;
+        let yanked_memtable = std::mem::replace(
+            &mut *active_memtable,
+            Arc::new(Memtable::new(self.config.memtable_type)),
+        );
 
         let tmp_memtable_id = self.get_next_segment_id();
         sealed_memtables.add(tmp_memtable_id, yanked_memtable.clone());
@@ -419,17 +506,28 @@ impl AbstractTree for Tree {
         key: K,
         value: V,
         seqno: SeqNo,
-    ) -> (u64, u64) {
+    ) -> (u64, u64, bool) {
         let value = InternalValue::from_components(key, value, seqno, ValueType::Value);
         self.append_entry(value)
     }
 
-    fn remove<K: Into<UserKey>>(&self, key: K, seqno: SeqNo) -> (u64, u64) {
+    fn insert_with_expiry<K: Into<UserKey>, V: Into<UserValue>>(
+        &self,
+        key: K,
+        value: V,
+        expires_at: u64,
+        seqno: SeqNo,
+    ) -> (u64, u64, bool) {
+        let value = InternalValue::new_expiring(key, value.into(), expires_at, seqno);
+        self.append_entry(value)
+    }
+
+    fn remove<K: Into<UserKey>>(&self, key: K, seqno: SeqNo) -> (u64, u64, bool) {
         let value = InternalValue::new_tombstone(key, seqno);
         self.append_entry(value)
     }
 
-    fn remove_weak<K: Into<UserKey>>(&self, key: K, seqno: SeqNo) -> (u64, u64) {
+    fn remove_weak<K: Into<UserKey>>(&self, key: K, seqno: SeqNo) -> (u64, u64, bool) {
         let value = InternalValue::new_weak_tombstone(key, seqno);
         self.append_entry(value)
     }
@@ -464,6 +562,13 @@ impl Tree {
             Self::create_new(config)
         }?;
 
+        // NOTE: Blob trees register themselves (including their value log)
+        if tree.config.tree_type == crate::TreeType::Standard {
+            if let Some(manager) = &tree.config.write_buffer_manager {
+                manager.register_tree(&tree);
+            }
+        }
+
         Ok(tree)
     }
 
@@ -515,6 +620,7 @@ impl Tree {
             self.id,
             self.config.cache.clone(),
             self.config.descriptor_table.clone(),
+            self.config.verify_cache_hits,
         )?;
 
         log::debug!("Flushed segment to {segment_file_path:?}");
@@ -542,6 +648,7 @@ impl Tree {
 
         let Some(segment) = self.flush_memtable(segment_id, &yanked_memtable, seqno_threshold)?
         else {
+            self.release_sealed_memtable(segment_id);
             return Ok(None);
         };
         self.register_segments(&[segment.clone()])?;
@@ -549,6 +656,19 @@ impl Tree {
         Ok(Some(segment))
     }
 
+    /// Removes a sealed memtable that did not produce a segment when flushed.
+    pub(crate) fn release_sealed_memtable(&self, id: MemtableId) {
+        let memtable = self
+            .sealed_memtables
+            .write()
+            .expect("lock is poisoned")
+            .remove(id);
+
+        if let (Some(memtable), Some(manager)) = (memtable, &self.config.write_buffer_manager) {
+            manager.free(memtable.size());
+        }
+    }
+
     /// Returns `true` if there are some segments that are being compacted.
     #[doc(hidden)]
     #[must_use]
@@ -557,13 +677,42 @@ impl Tree {
         levels.is_compacting()
     }
 
+    /// Verifies all disk segments, adding corrupted blocks to `corrupted`.
+    ///
+    /// Returns the amount of items in the intact data blocks.
+    pub(crate) fn verify_segments(
+        &self,
+        corrupted: &mut Vec<crate::CorruptedBlock>,
+    ) -> crate::Result<usize> {
+        // NOTE: Segments are only deleted once dropped, so there
+        // is no need to hold the lock while reading them
+        let segments = self
+            .levels
+            .read()
+            .expect("lock is poisoned")
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut item_count = 0;
+
+        for segment in segments {
+            item_count += segment.verify(corrupted)?;
+        }
+
+        Ok(item_count)
+    }
+
     /// Write-locks the sealed memtables for exclusive access
     fn lock_sealed_memtables(&self) -> RwLockWriteGuard<'_, SealedMemtables> {
         self.sealed_memtables.write().expect("lock is poisoned")
     }
 
-    // TODO: maybe not needed anyway
-    /// Used for [`BlobTree`] lookup
+    /// Used for [`BlobTree`] garbage collection
+    ///
+    /// Unlike [`Tree::get_internal_entry`], the expiry timestamp
+    /// of expiring values is kept, so it can be carried over
+    /// when the value is rewritten.
     pub(crate) fn get_internal_entry_with_memtable(
         &self,
         memtable_lock: &Memtable,
@@ -571,15 +720,17 @@ impl Tree {
         seqno: Option<SeqNo>,
     ) -> crate::Result<Option<InternalValue>> {
         if let Some(entry) = memtable_lock.get(key, seqno) {
-            return Ok(ignore_tombstone_value(entry));
+            return Ok(ignore_dead_value(entry));
         };
 
         // Now look in sealed memtables
         if let Some(entry) = self.get_internal_entry_from_sealed_memtables(key, seqno) {
-            return Ok(ignore_tombstone_value(entry));
+            return Ok(ignore_dead_value(entry));
         }
 
-        self.get_internal_entry_from_segments(key, seqno)
+        Ok(self
+            .get_internal_entry_from_segments(key, seqno)?
+            .and_then(ignore_dead_value))
     }
 
     fn get_internal_entry_from_sealed_memtables(
@@ -598,6 +749,7 @@ impl Tree {
         None
     }
 
+    /// Returns the newest version of the key in the segments, including tombstones
     fn get_internal_entry_from_segments(
         &self,
         key: &[u8],
@@ -615,7 +767,7 @@ impl Tree {
                 if let Some(level) = level.as_disjoint() {
                     if let Some(segment) = level.get_segment_containing_key(key) {
                         if let Some(item) = segment.get(key, seqno, key_hash)? {
-                            return Ok(ignore_tombstone_value(item));
+                            return Ok(Some(item));
                         }
                     }
 
@@ -631,7 +783,7 @@ impl Tree {
                 }
 
                 if let Some(item) = segment.get(key, seqno, key_hash)? {
-                    return Ok(ignore_tombstone_value(item));
+                    return Ok(Some(item));
                 }
             }
         }
@@ -661,20 +813,65 @@ impl Tree {
         }
 
         // Now look in segments... this may involve disk I/O
-        self.get_internal_entry_from_segments(key, seqno)
+        Ok(self
+            .get_internal_entry_from_segments(key, seqno)?
+            .and_then(ignore_tombstone_value))
     }
 
+    /// Runs a compaction, retrying it if it fails.
+    ///
+    /// If `exclusive` is set, no other compaction can run at the same time.
     fn inner_compact(
         &self,
         strategy: Arc<dyn CompactionStrategy>,
         seqno_threshold: SeqNo,
+        snapshots: Option<&[SeqNo]>,
+        exclusive: bool,
     ) -> crate::Result<()> {
         use crate::compaction::worker::{do_compaction, Options};
 
         let mut opts = Options::from_tree(self, strategy);
         opts.eviction_seqno = seqno_threshold;
+        opts.snapshots = snapshots.map(<[SeqNo]>::to_vec);
+
+        let max_retries = self.config.compaction_max_retries;
+        let mut backoff = self.config.compaction_retry_backoff;
+        let mut retries = 0;
+
+        loop {
+            // NOTE: The major compaction lock is released before backing off,
+            // so other compactions are not blocked while we wait to retry
+            let result = if exclusive {
+                // IMPORTANT: Write lock so we can be the only compaction going on
+                let _lock = self
+                    .major_compaction_lock
+                    .write()
+                    .expect("lock is poisoned");
+
+                do_compaction(&opts)
+            } else {
+                // NOTE: Read lock major compaction lock
+                // That way, if a major compaction is running, we cannot proceed
+                // But in general, parallel (non-major) compactions can occur
+                let _lock = self.major_compaction_lock.read().expect("lock is poisoned");
+
+                do_compaction(&opts)
+            };
 
-        do_compaction(&opts)?;
+            match result {
+                Err(crate::Error::Compaction(e)) if retries < max_retries => {
+                    retries += 1;
+
+                    log::warn!(
+                        "Compaction failed, retrying in {backoff:?} ({retries}/{max_retries}): {e}"
+                    );
+
+                    std::thread::sleep(backoff);
+                    backoff = backoff.saturating_mul(2);
+                }
+                result => break result?,
+            }
+        }
 
         log::debug!("Compaction run over");
 
@@ -738,7 +935,13 @@ impl Tree {
             levels: level_manifest.levels.clone(),
         };
 
-        TreeIter::create_range(iter_state, bounds, seqno, level_manifest)
+        TreeIter::create_range(iter_state, bounds, seqno, level_manifest).filter_map(
+            |item| match item {
+                // NOTE: Expired values act like tombstones, and thus cover older versions
+                Ok(item) => ignore_tombstone_value(item).map(Ok),
+                Err(e) => Some(Err(e)),
+            },
+        )
     }
 
     #[doc(hidden)]
@@ -770,12 +973,21 @@ impl Tree {
 
     /// Adds an item to the active memtable.
     ///
-    /// Returns the added item's size and new size of the memtable.
+    /// Returns the added item's size, the new size of the memtable, and `true` if
+    /// the write buffer manager is over budget.
     #[doc(hidden)]
     #[must_use]
-    pub fn append_entry(&self, value: InternalValue) -> (u64, u64) {
+    pub fn append_entry(&self, value: InternalValue) -> (u64, u64, bool) {
         let memtable_lock = self.active_memtable.read().expect("lock is poisoned");
-        memtable_lock.insert(value)
+        let (item_size, memtable_size) = memtable_lock.insert(value);
+
+        let is_over_budget = self
+            .config
+            .write_buffer_manager
+            .as_ref()
+            .is_some_and(|manager| manager.allocate(item_size));
+
+        (item_size, memtable_size, is_over_budget)
     }
 
     /// Recovers previous state, by loading the level manifest and segments.
@@ -809,6 +1021,7 @@ impl Tree {
             tree_id,
             &config.cache,
             &config.descriptor_table,
+            config.verify_cache_hits,
         )?;
         levels.update_metadata();
 
@@ -817,12 +1030,17 @@ impl Tree {
         let inner = TreeInner {
             id: tree_id,
             segment_id_counter: Arc::new(AtomicU64::new(highest_segment_id + 1)),
-            active_memtable: Arc::default(),
+            active_memtable: Arc::new(RwLock::new(Arc::new(Memtable::new(
+                config.memtable_type,
+            )))),
             sealed_memtables: Arc::default(),
             levels: Arc::new(RwLock::new(levels)),
             stop_signal: StopSignal::default(),
+            active_compactions: ActiveCompactions::default(),
+            compaction_stats: StatsRecorder::default(),
             config,
             major_compaction_lock: RwLock::default(),
+            value_log: OnceLock::new(),
         };
 
         Ok(Self(Arc::new(inner)))
@@ -870,6 +1088,7 @@ impl Tree {
         tree_id: TreeId,
         cache: &Arc<Cache>,
         descriptor_table: &Arc<DescriptorTable>,
+        verify_cache_hits: bool,
     ) -> crate::Result<LevelManifest> {
         use crate::{
             file::fsync_directory,
@@ -938,6 +1157,7 @@ impl Tree {
                     tree_id,
                     cache.clone(),
                     descriptor_table.clone(),
+                    verify_cache_hits,
                     // level_idx == 0 || level_idx == 1,
                 )?;
 
@@ -39,6 +39,11 @@ pub enum ValueType {
 
     /// "Weak" deletion (a.k.a. `SingleDelete` in `RocksDB`)
     WeakTombstone,
+
+    /// Existing value that expires at some point in time
+    ///
+    /// The value is prefixed with the expiry timestamp, see [`InternalValue::new_expiring`].
+    ExpiringValue,
 }
 
 impl TryFrom<u8> for ValueType {
@@ -49,6 +54,7 @@ impl TryFrom<u8> for ValueType {
             0 => Ok(Self::Value),
             1 => Ok(Self::Tombstone),
             2 => Ok(Self::WeakTombstone),
+            3 => Ok(Self::ExpiringValue),
             _ => Err(()),
         }
     }
@@ -60,6 +66,7 @@ impl From<ValueType> for u8 {
             ValueType::Value => 0,
             ValueType::Tombstone => 1,
             ValueType::WeakTombstone => 2,
+            ValueType::ExpiringValue => 3,
         }
     }
 }
@@ -130,13 +137,76 @@ impl InternalValue {
         Self::new(key, vec![])
     }
 
+    /// Creates a new value that expires at the given unix timestamp (in seconds).
+    ///
+    /// The expiry timestamp is stored (big-endian) in front of the user value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key length is empty or greater than 2^16, or the value length is greater than 2^32.
+    pub fn new_expiring<K: Into<UserKey>, V: AsRef<[u8]>>(
+        key: K,
+        value: V,
+        expires_at: u64,
+        seqno: SeqNo,
+    ) -> Self {
+        let value = value.as_ref();
+
+        let mut bytes = Vec::with_capacity(EXPIRY_PREFIX_LEN + value.len());
+        bytes.extend_from_slice(&expires_at.to_be_bytes());
+        bytes.extend_from_slice(value);
+
+        let key = InternalKey::new(key, seqno, ValueType::ExpiringValue);
+        Self::new(key, bytes)
+    }
+
     #[doc(hidden)]
     #[must_use]
     pub fn is_tombstone(&self) -> bool {
         self.key.is_tombstone()
     }
+
+    /// Returns the expiry unix timestamp (in seconds), if the value expires.
+    #[doc(hidden)]
+    #[must_use]
+    pub fn expires_at(&self) -> Option<u64> {
+        if self.key.value_type != ValueType::ExpiringValue {
+            return None;
+        }
+
+        let bytes = self.value.get(..EXPIRY_PREFIX_LEN)?;
+        let mut buf = [0; EXPIRY_PREFIX_LEN];
+        buf.copy_from_slice(bytes);
+        Some(u64::from_be_bytes(buf))
+    }
+
+    /// Returns `true` if the value has expired at the given unix timestamp (in seconds).
+    #[doc(hidden)]
+    #[must_use]
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at().is_some_and(|expires_at| expires_at <= now)
+    }
+
+    /// Strips the expiry timestamp of an expiring value, so only the user value remains.
+    ///
+    /// Other value types are returned as-is.
+    #[doc(hidden)]
+    #[must_use]
+    pub fn into_user_value(mut self) -> Self {
+        if self.key.value_type == ValueType::ExpiringValue {
+            self.value = if self.value.len() >= EXPIRY_PREFIX_LEN {
+                self.value.slice(EXPIRY_PREFIX_LEN..)
+            } else {
+                UserValue::empty()
+            };
+        }
+        self
+    }
 }
 
+/// Length of the expiry timestamp that prefixes expiring values
+const EXPIRY_PREFIX_LEN: usize = std::mem::size_of::<u64>();
+
 impl PartialEq for InternalValue {
     fn eq(&self, other: &Self) -> bool {
         self.key == other.key
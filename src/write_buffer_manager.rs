@@ -0,0 +1,355 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use crate::{
+    blob_tree::{cache::MyBlobCache, compression::MyCompressor},
+    tree::inner::{TreeId, TreeInner},
+    AbstractTree, BlobTree, SeqNo, Tree,
+};
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc, Mutex, Weak,
+};
+use value_log::ValueLog;
+
+/// Weak reference to a registered tree
+///
+/// The write buffer manager should never keep a tree alive.
+enum WeakTree {
+    Standard(Weak<TreeInner>),
+
+    Blob {
+        index: Weak<TreeInner>,
+        blobs: ValueLog<MyBlobCache, MyCompressor>,
+        pending_segments: Arc<AtomicUsize>,
+    },
+}
+
+impl WeakTree {
+    fn upgrade(&self) -> Option<crate::AnyTree> {
+        match self {
+            Self::Standard(inner) => inner
+                .upgrade()
+                .map(|inner| crate::AnyTree::Standard(Tree(inner))),
+            Self::Blob {
+                index,
+                blobs,
+                pending_segments,
+            } => index.upgrade().map(|inner| {
+                crate::AnyTree::Blob(BlobTree {
+                    index: Tree(inner).into(),
+                    blobs: blobs.clone(),
+                    pending_segments: pending_segments.clone(),
+                })
+            }),
+        }
+    }
+}
+
+/// Write buffer manager, which keeps track of the memory used by
+/// memtables (active and sealed) of all trees sharing it
+///
+/// This is the write memory equivalent of [`crate::Cache`] and [`crate::DescriptorTable`]:
+/// share a single manager between multiple trees to cap global write buffer memory usage.
+///
+/// The manager does not flush in the background; instead, writes to a tree
+/// (e.g. [`crate::AbstractTree::insert`]) report when the budget is exceeded,
+/// and the caller then needs to call [`WriteBufferManager::enforce`].
+///
+/// # Examples
+///
+/// ```
+/// # use lsm_tree::{AbstractTree, Config, WriteBufferManager};
+/// # use std::sync::Arc;
+/// #
+/// // Allow 64 MB of memtables across all trees
+/// let write_buffer_manager = Arc::new(WriteBufferManager::new(64 * 1_000 * 1_000));
+///
+/// # let folder = tempfile::tempdir()?;
+/// let tree1 = Config::new(folder)
+///     .use_write_buffer_manager(write_buffer_manager.clone())
+///     .open()?;
+/// # let folder = tempfile::tempdir()?;
+/// let tree2 = Config::new(folder)
+///     .use_write_buffer_manager(write_buffer_manager.clone())
+///     .open()?;
+///
+/// tree1.insert("a", "a", 0);
+///
+/// let (_, _, is_over_budget) = tree2.insert("b", "b", 1);
+///
+/// if is_over_budget {
+///     write_buffer_manager.enforce(0)?;
+/// }
+/// #
+/// # Ok::<(), lsm_tree::Error>(())
+/// ```
+pub struct WriteBufferManager {
+    /// Budget in bytes
+    capacity: AtomicU64,
+
+    /// Approximate size of all memtables in bytes
+    used: AtomicU64,
+
+    /// Trees that share this manager
+    trees: Mutex<Vec<(TreeId, WeakTree)>>,
+}
+
+impl WriteBufferManager {
+    /// Creates a new write buffer manager with the given budget in bytes.
+    #[must_use]
+    pub fn new(capacity: u64) -> Self {
+        Self {
+            capacity: AtomicU64::new(capacity),
+            used: AtomicU64::default(),
+            trees: Mutex::default(),
+        }
+    }
+
+    /// Returns the budget in bytes.
+    #[must_use]
+    pub fn capacity(&self) -> u64 {
+        self.capacity.load(Ordering::Acquire)
+    }
+
+    /// Sets the budget in bytes.
+    pub fn set_capacity(&self, bytes: u64) {
+        self.capacity.store(bytes, Ordering::Release);
+    }
+
+    /// Returns the approximate size of all memtables in bytes.
+    #[must_use]
+    pub fn usage(&self) -> u64 {
+        self.used.load(Ordering::Acquire)
+    }
+
+    /// Returns `true` if the memtables use more memory than the budget allows.
+    #[must_use]
+    pub fn is_over_budget(&self) -> bool {
+        self.usage() > self.capacity()
+    }
+
+    /// Returns the amount of registered trees that are still alive.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lock is poisoned.
+    #[must_use]
+    pub fn tree_count(&self) -> usize {
+        let mut trees = self.trees.lock().expect("lock is poisoned");
+        Self::prune(&mut trees);
+        trees.len()
+    }
+
+    /// Returns `true` if the memtables use more memory than the budget allows afterwards.
+    pub(crate) fn allocate(&self, bytes: u64) -> bool {
+        let used = self.used.fetch_add(bytes, Ordering::AcqRel) + bytes;
+        used > self.capacity()
+    }
+
+    pub(crate) fn free(&self, bytes: u64) {
+        // NOTE: Saturate, so a missed allocation can never underflow the counter
+        let _ = self
+            .used
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                Some(used.saturating_sub(bytes))
+            });
+    }
+
+    pub(crate) fn register_tree(&self, tree: &Tree) {
+        let mut trees = self.trees.lock().expect("lock is poisoned");
+        Self::prune(&mut trees);
+        trees.push((tree.id, WeakTree::Standard(Arc::downgrade(&tree.0))));
+    }
+
+    pub(crate) fn register_blob_tree(&self, tree: &BlobTree) {
+        let mut trees = self.trees.lock().expect("lock is poisoned");
+        Self::prune(&mut trees);
+        trees.push((
+            tree.index.id,
+            WeakTree::Blob {
+                index: Arc::downgrade(&tree.index.0 .0),
+                blobs: tree.blobs.clone(),
+                pending_segments: tree.pending_segments.clone(),
+            },
+        ));
+    }
+
+    /// Removes trees that have been dropped.
+    fn prune(trees: &mut Vec<(TreeId, WeakTree)>) {
+        trees.retain(|(_, tree)| match tree {
+            WeakTree::Standard(inner) | WeakTree::Blob { index: inner, .. } => {
+                inner.strong_count() > 0
+            }
+        });
+    }
+
+    /// Flushes the largest active memtables (across all trees) to disk,
+    /// until the memtables fit into the budget again.
+    ///
+    /// Returns the amount of flushed memtables.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lock is poisoned.
+    pub fn enforce(&self, eviction_seqno: SeqNo) -> crate::Result<usize> {
+        let mut flushed = 0;
+
+        while self.is_over_budget() {
+            let trees = {
+                let mut trees = self.trees.lock().expect("lock is poisoned");
+                Self::prune(&mut trees);
+                trees
+                    .iter()
+                    .filter_map(|(_, tree)| tree.upgrade())
+                    .collect::<Vec<_>>()
+            };
+
+            let Some(largest) = trees
+                .into_iter()
+                .filter(|tree| tree.active_memtable_size() > 0)
+                .max_by_key(AbstractTree::active_memtable_size)
+            else {
+                // NOTE: Only sealed memtables remain, which are owned by some other flusher
+                break;
+            };
+
+            log::debug!(
+                "Write buffer usage ({}B) exceeds budget ({}B), flushing largest memtable ({}B)",
+                self.usage(),
+                self.capacity(),
+                largest.active_memtable_size(),
+            );
+
+            let result = match &largest {
+                crate::AnyTree::Standard(tree) => tree.flush_active_memtable(eviction_seqno)?,
+                crate::AnyTree::Blob(tree) => tree.flush_active_memtable(eviction_seqno)?,
+            };
+
+            if result.is_some() {
+                flushed += 1;
+            }
+        }
+
+        Ok(flushed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use test_log::test;
+
+    #[test]
+    fn write_buffer_manager_accounting() {
+        let wbm = WriteBufferManager::new(100);
+
+        wbm.allocate(50);
+        assert!(!wbm.is_over_budget());
+
+        wbm.allocate(51);
+        assert!(wbm.is_over_budget());
+
+        wbm.free(60);
+        assert_eq!(41, wbm.usage());
+
+        wbm.free(1_000);
+        assert_eq!(0, wbm.usage());
+    }
+
+    #[test]
+    fn write_buffer_manager_report_over_budget() -> crate::Result<()> {
+        let wbm = Arc::new(WriteBufferManager::new(100));
+
+        let folder = tempfile::tempdir()?;
+        let tree = Config::new(&folder)
+            .use_write_buffer_manager(wbm.clone())
+            .open()?;
+
+        let (_, _, is_over_budget) = tree.insert("a", "a", 0);
+        assert!(!is_over_budget);
+
+        let (_, _, is_over_budget) = tree.insert("b", "b".repeat(100), 1);
+        assert!(is_over_budget);
+
+        assert_eq!(1, wbm.enforce(0)?);
+        assert!(!wbm.is_over_budget());
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_buffer_manager_free_on_empty_flush() -> crate::Result<()> {
+        let wbm = Arc::new(WriteBufferManager::new(1_000_000));
+
+        let folder = tempfile::tempdir()?;
+        let tree = Config::new(&folder)
+            .use_write_buffer_manager(wbm.clone())
+            .open()?;
+
+        // NOTE: The weak tombstone cancels out the value, so no segment is written
+        tree.insert("a", "a", 0);
+        tree.remove_weak("a", 1);
+        assert!(wbm.usage() > 0);
+
+        assert!(tree.flush_active_memtable(2)?.is_none());
+        assert_eq!(0, tree.sealed_memtable_count());
+        assert_eq!(0, wbm.usage());
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_buffer_manager_prune_dropped_trees() -> crate::Result<()> {
+        let wbm = Arc::new(WriteBufferManager::new(100));
+
+        let folder = tempfile::tempdir()?;
+        let tree = Config::new(&folder)
+            .use_write_buffer_manager(wbm.clone())
+            .open()?;
+        assert_eq!(1, wbm.tree_count());
+
+        drop(tree);
+        assert_eq!(0, wbm.tree_count());
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_buffer_manager_free_on_drop() -> crate::Result<()> {
+        let wbm = Arc::new(WriteBufferManager::new(1_000_000));
+
+        let folder = tempfile::tempdir()?;
+        let tree = Config::new(&folder)
+            .use_write_buffer_manager(wbm.clone())
+            .open()?;
+
+        tree.insert("a", "a", 0);
+        tree.insert("b", "b", 1);
+        assert!(wbm.usage() > 0);
+
+        drop(tree);
+        assert_eq!(0, wbm.usage());
+
+        let folder = tempfile::tempdir()?;
+        let tree = Config::new(&folder)
+            .use_write_buffer_manager(wbm.clone())
+            .open_as_blob_tree()?;
+        assert_eq!(1, wbm.tree_count());
+
+        tree.insert("a", "a", 0);
+        assert!(wbm.usage() > 0);
+
+        drop(tree);
+        assert_eq!(0, wbm.usage());
+        assert_eq!(0, wbm.tree_count());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,178 @@
+use lsm_tree::{AbstractTree, Config, Error, SeqNo, SequenceNumberCounter};
+use std::sync::atomic::Ordering;
+use test_log::test;
+
+#[test]
+fn blob_compaction_relocation() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let big_value = b"neptune!".repeat(128_000);
+    let new_value = b"saturn!".repeat(128_000);
+
+    {
+        let tree = Config::new(&folder)
+            .blob_file_relocation_threshold(0.5)
+            .open_as_blob_tree()?;
+
+        let seqno = SequenceNumberCounter::default();
+
+        for key in ["a", "b", "c", "d"] {
+            tree.insert(key, &big_value, seqno.next());
+        }
+        tree.flush_active_memtable(0)?;
+
+        for key in ["a", "b"] {
+            tree.insert(key, &new_value, seqno.next());
+        }
+        tree.flush_active_memtable(0)?;
+        assert_eq!(2, tree.blob_file_count());
+
+        // NOTE: Dropping the old versions of "a" and "b" makes half of the first blob file stale
+        tree.major_compact(u64::MAX, SeqNo::MAX)?;
+        assert_eq!(2, tree.blob_file_count());
+        assert_eq!(0, tree.gc_drop_stale()?);
+
+        // NOTE: Now "c" and "d" are moved out of the first blob file, so it is fully stale
+        tree.major_compact(u64::MAX, SeqNo::MAX)?;
+        assert_eq!(3, tree.blob_file_count());
+        assert!(tree.gc_drop_stale()? > 0);
+        assert_eq!(2, tree.blob_file_count());
+
+        assert_eq!(&*tree.get("a", None)?.unwrap(), new_value);
+        assert_eq!(&*tree.get("b", None)?.unwrap(), new_value);
+        assert_eq!(&*tree.get("c", None)?.unwrap(), big_value);
+        assert_eq!(&*tree.get("d", None)?.unwrap(), big_value);
+    }
+
+    {
+        let tree = Config::new(&folder).open_as_blob_tree()?;
+        assert_eq!(2, tree.blob_file_count());
+        assert_eq!(&*tree.get("c", None)?.unwrap(), big_value);
+        assert_eq!(&*tree.get("d", None)?.unwrap(), big_value);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn blob_compaction_relocation_disabled() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let tree = Config::new(&folder).open_as_blob_tree()?;
+
+    let seqno = SequenceNumberCounter::default();
+
+    for key in ["a", "b"] {
+        tree.insert(key, b"neptune!".repeat(128_000), seqno.next());
+    }
+    tree.flush_active_memtable(0)?;
+
+    tree.insert("a", b"saturn!".repeat(128_000), seqno.next());
+    tree.flush_active_memtable(0)?;
+
+    tree.major_compact(u64::MAX, SeqNo::MAX)?;
+    tree.major_compact(u64::MAX, SeqNo::MAX)?;
+
+    // NOTE: Without relocation, blob files are only touched by blob GC
+    assert_eq!(2, tree.blob_file_count());
+    assert_eq!(0, tree.gc_drop_stale()?);
+
+    Ok(())
+}
+
+#[test]
+#[ignore = "gc_scan_stats needs range iteration, which is not implemented yet"]
+fn blob_compaction_relocation_after_gc_scan() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let big_value = b"neptune!".repeat(128_000);
+    let new_value = b"saturn!".repeat(128_000);
+
+    let tree = Config::new(&folder)
+        .blob_file_relocation_threshold(0.9)
+        .open_as_blob_tree()?;
+
+    let seqno = SequenceNumberCounter::default();
+
+    for key in ["a", "b", "c", "d"] {
+        tree.insert(key, &big_value, seqno.next());
+    }
+    tree.flush_active_memtable(0)?;
+
+    for key in ["a", "b"] {
+        tree.insert(key, &new_value, seqno.next());
+    }
+    tree.flush_active_memtable(0)?;
+
+    // NOTE: The scan already counts the old versions of "a" and "b" as stale
+    tree.gc_scan_stats(seqno.get(), 0)?;
+
+    // NOTE: Dropping the old versions must not count them again
+    tree.major_compact(u64::MAX, SeqNo::MAX)?;
+    assert_eq!(0, tree.gc_drop_stale()?);
+    assert_eq!(2, tree.blob_file_count());
+
+    assert_eq!(&*tree.get("a", None)?.unwrap(), new_value);
+    assert_eq!(&*tree.get("b", None)?.unwrap(), new_value);
+    assert_eq!(&*tree.get("c", None)?.unwrap(), big_value);
+    assert_eq!(&*tree.get("d", None)?.unwrap(), big_value);
+
+    Ok(())
+}
+
+#[test]
+fn blob_compaction_relocation_failure_cleanup() -> lsm_tree::Result<()> {
+    const ITEM_COUNT: usize = 1_000;
+
+    let folder = tempfile::tempdir()?;
+
+    let tree = Config::new(&folder)
+        .data_block_size(1_024)
+        .blob_file_separation_threshold(1)
+        .blob_file_relocation_threshold(0.5)
+        .open_as_blob_tree()?;
+
+    let seqno = SequenceNumberCounter::default();
+
+    for idx in 0..ITEM_COUNT {
+        tree.insert(format!("{idx:0>6}"), b"neptune!".repeat(10), seqno.next());
+    }
+    tree.flush_active_memtable(0)?;
+
+    for idx in ITEM_COUNT / 2..ITEM_COUNT {
+        tree.insert(format!("{idx:0>6}"), b"saturn!".repeat(10), seqno.next());
+    }
+    tree.flush_active_memtable(0)?;
+
+    // NOTE: Dropping the old versions makes half of the first blob file stale
+    tree.major_compact(u64::MAX, SeqNo::MAX)?;
+    assert_eq!(2, tree.blob_file_count());
+
+    let blob_files_folder = folder.path().join("blobs").join("segments");
+    assert_eq!(2, std::fs::read_dir(&blob_files_folder)?.count());
+
+    // NOTE: Block the second output segment, so some blobs have already been relocated
+    let next_segment_id = tree.index.segment_id_counter.load(Ordering::Relaxed) + 1;
+    let blocked_path = folder
+        .path()
+        .join("segments")
+        .join(next_segment_id.to_string());
+    std::fs::create_dir(&blocked_path)?;
+
+    assert!(matches!(
+        tree.major_compact(2_048, SeqNo::MAX),
+        Err(Error::Compaction(_)),
+    ));
+
+    std::fs::remove_dir(&blocked_path)?;
+
+    // NOTE: The blob files of relocated blobs are deleted as well
+    assert_eq!(2, tree.blob_file_count());
+    assert_eq!(2, std::fs::read_dir(&blob_files_folder)?.count());
+
+    for idx in 0..ITEM_COUNT {
+        assert!(tree.contains_key(format!("{idx:0>6}"), None)?);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use lsm_tree::{AbstractTree, Config, RateLimiter, SeqNo};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use test_log::test;
+
+const ITEM_COUNT: usize = 10_000;
+
+#[test]
+fn compaction_progress_and_cancel() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let rate_limiter = Arc::new(RateLimiter::new(u64::MAX));
+
+    let tree = Config::new(&folder)
+        .use_rate_limiter(rate_limiter.clone())
+        .open()?;
+
+    for batch in 0..2 {
+        for idx in 0..ITEM_COUNT {
+            tree.insert(format!("{idx:0>6}"), "a".repeat(100), batch);
+        }
+        tree.flush_active_memtable(0)?;
+    }
+    assert_eq!(2, tree.segment_count());
+    assert!(tree.active_compactions().is_empty());
+
+    // NOTE: Throttle compaction, so it runs for a long time
+    rate_limiter.set_rate(500_000);
+
+    let compaction = std::thread::spawn({
+        let tree = tree.clone();
+        move || tree.major_compact(u64::MAX, SeqNo::MAX)
+    });
+
+    let deadline = Instant::now() + Duration::from_secs(30);
+
+    let handle = loop {
+        if let Some(handle) = tree.active_compactions().pop() {
+            if handle.bytes_processed() > 0 {
+                break handle;
+            }
+        }
+
+        assert!(
+            !compaction.is_finished(),
+            "compaction finished before it could be observed",
+        );
+        assert!(
+            Instant::now() < deadline,
+            "compaction did not make progress"
+        );
+
+        std::thread::sleep(Duration::from_millis(10));
+    };
+
+    assert_eq!(2, handle.segment_ids().len());
+    assert!(handle.input_bytes() > 0);
+    assert!(handle.bytes_processed() < handle.input_bytes());
+    assert!(handle.eta().is_some());
+
+    handle.cancel();
+    compaction.join().expect("should join")?;
+
+    assert!(handle.is_cancelled());
+    assert!(tree.active_compactions().is_empty());
+
+    // NOTE: Input segments are kept, and nothing was left behind
+    assert_eq!(2, tree.segment_count());
+    assert_eq!(
+        2,
+        std::fs::read_dir(folder.path().join("segments"))?.count()
+    );
+
+    for idx in 0..ITEM_COUNT {
+        assert!(tree.contains_key(format!("{idx:0>6}"), None)?);
+    }
+
+    // NOTE: Segments can be compacted again
+    rate_limiter.set_rate(u64::MAX);
+    tree.major_compact(u64::MAX, SeqNo::MAX)?;
+    assert_eq!(1, tree.segment_count());
+    assert_eq!(ITEM_COUNT, tree.approximate_len());
+
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use lsm_tree::{AbstractTree, CompactionPhase, Config, Error, SeqNo, Tree};
+use std::{sync::atomic::Ordering, time::Duration};
+use test_log::test;
+
+fn setup(tree: &Tree) -> lsm_tree::Result<()> {
+    for seqno in 0..2 {
+        tree.insert("a", "a", seqno);
+        tree.insert("b", "b", seqno);
+        tree.flush_active_memtable(0)?;
+    }
+    assert_eq!(2, tree.segment_count());
+    Ok(())
+}
+
+/// Blocks the next segment file by creating a folder at its path.
+fn block_next_segment(tree: &Tree, folder: &std::path::Path) -> lsm_tree::Result<()> {
+    let next_segment_id = tree.segment_id_counter.load(Ordering::Relaxed);
+    std::fs::create_dir(folder.join("segments").join(next_segment_id.to_string()))?;
+    Ok(())
+}
+
+#[test]
+fn compaction_failure_is_returned() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder).open()?;
+    setup(&tree)?;
+
+    let segment_ids = {
+        let levels = tree.levels.read().expect("lock is poisoned");
+        let mut ids = levels.iter().map(|x| x.id()).collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids
+    };
+
+    block_next_segment(&tree, folder.path())?;
+
+    match tree.major_compact(u64::MAX, SeqNo::MAX) {
+        Err(Error::Compaction(e)) => {
+            assert_eq!(CompactionPhase::Write, e.phase);
+            assert_eq!(segment_ids, e.segment_ids);
+        }
+        result => panic!("expected compaction error, got {result:?}"),
+    }
+
+    // NOTE: Input segments are not hidden anymore and can be compacted again
+    assert_eq!(2, tree.segment_count());
+    assert!(tree.active_compactions().is_empty());
+    assert_eq!(Some("a".as_bytes().into()), tree.get("a", None)?);
+
+    tree.major_compact(u64::MAX, SeqNo::MAX)?;
+    assert_eq!(1, tree.segment_count());
+
+    Ok(())
+}
+
+#[test]
+fn compaction_failure_retry() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder)
+        .compaction_retries(1, Duration::from_millis(1))
+        .open()?;
+    setup(&tree)?;
+
+    block_next_segment(&tree, folder.path())?;
+
+    // NOTE: The retry writes into the next segment ID, which is not blocked
+    tree.major_compact(u64::MAX, SeqNo::MAX)?;
+    assert_eq!(1, tree.segment_count());
+    assert_eq!(2, tree.approximate_len());
+
+    Ok(())
+}
+
+#[test]
+fn compaction_failure_cleanup() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder).data_block_size(1_024).open()?;
+
+    for seqno in 0..2 {
+        for idx in 0..1_000 {
+            tree.insert(format!("{idx:0>6}"), "a".repeat(100), seqno);
+        }
+        tree.flush_active_memtable(0)?;
+    }
+
+    // NOTE: Block the second output segment, so the first one has already been written
+    let next_segment_id = tree.segment_id_counter.load(Ordering::Relaxed) + 1;
+    let blocked_path = folder
+        .path()
+        .join("segments")
+        .join(next_segment_id.to_string());
+    std::fs::create_dir(&blocked_path)?;
+
+    assert!(matches!(
+        tree.major_compact(10_000, SeqNo::MAX),
+        Err(Error::Compaction(_)),
+    ));
+
+    std::fs::remove_dir(&blocked_path)?;
+
+    // NOTE: Only the input segments are left
+    assert_eq!(2, tree.segment_count());
+    assert_eq!(
+        2,
+        std::fs::read_dir(folder.path().join("segments"))?.count()
+    );
+
+    Ok(())
+}
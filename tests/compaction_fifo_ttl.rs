@@ -0,0 +1,70 @@
+use lsm_tree::{compaction::Fifo, AbstractTree, Config, SeqNo};
+use std::{sync::Arc, time::Duration};
+use test_log::test;
+
+#[test]
+fn fifo_ttl_drops_old_segments() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder).open()?;
+
+    tree.insert("a", "a", 0);
+    tree.flush_active_memtable(0)?;
+    assert_eq!(1, tree.segment_count());
+
+    tree.compact(Arc::new(Fifo::new(u64::MAX, Some(60))), SeqNo::MAX)?;
+    assert_eq!(1, tree.segment_count());
+
+    std::thread::sleep(Duration::from_millis(1_100));
+
+    tree.insert("b", "b", 1);
+    tree.flush_active_memtable(0)?;
+    assert_eq!(2, tree.segment_count());
+
+    tree.compact(Arc::new(Fifo::new(u64::MAX, Some(1))), SeqNo::MAX)?;
+    assert_eq!(1, tree.segment_count());
+    assert!(!tree.contains_key("a", None)?);
+    assert!(tree.contains_key("b", None)?);
+
+    Ok(())
+}
+
+#[test]
+fn fifo_ttl_no_fresh_lease_after_compaction() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder).open()?;
+
+    tree.insert("a", "a", 0);
+    tree.flush_active_memtable(0)?;
+
+    std::thread::sleep(Duration::from_millis(1_100));
+
+    // NOTE: Rewrites the segment, but the data is still as old as before
+    tree.major_compact(u64::MAX, SeqNo::MAX)?;
+    assert_eq!(1, tree.segment_count());
+
+    tree.compact(Arc::new(Fifo::new(u64::MAX, Some(1))), SeqNo::MAX)?;
+    assert_eq!(0, tree.segment_count());
+
+    Ok(())
+}
+
+#[test]
+fn fifo_ttl_recover() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    {
+        let tree = Config::new(&folder).open()?;
+        tree.insert("a", "a", 0);
+        tree.flush_active_memtable(0)?;
+    }
+
+    std::thread::sleep(Duration::from_millis(1_100));
+
+    let tree = Config::new(&folder).open()?;
+    assert_eq!(1, tree.segment_count());
+
+    tree.compact(Arc::new(Fifo::new(u64::MAX, Some(1))), SeqNo::MAX)?;
+    assert_eq!(0, tree.segment_count());
+
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use lsm_tree::{
+    compaction::{CompactionFilter, FilterVerdict},
+    AbstractTree, Config, SeqNo,
+};
+use std::sync::Arc;
+use test_log::test;
+
+struct DropTenant;
+
+impl CompactionFilter for DropTenant {
+    fn filter(&self, key: &[u8], value: &[u8]) -> FilterVerdict {
+        if key.starts_with(b"tenant1/") {
+            FilterVerdict::Remove
+        } else if key.starts_with(b"tenant2/") {
+            FilterVerdict::ChangeValue([value, b"_v2"].concat().into())
+        } else {
+            FilterVerdict::Keep
+        }
+    }
+}
+
+#[test]
+fn compaction_filter_drop_and_rewrite() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder)
+        .compaction_filter(Arc::new(DropTenant))
+        .open()?;
+
+    tree.insert("tenant1/a", "a", 0);
+    tree.insert("tenant2/a", "a", 1);
+    tree.insert("tenant3/a", "a", 2);
+    tree.flush_active_memtable(0)?;
+
+    // NOTE: Flushing does not invoke the filter
+    assert_eq!(Some("a".as_bytes().into()), tree.get("tenant1/a", None)?);
+
+    tree.major_compact(u64::MAX, SeqNo::MAX)?;
+
+    assert_eq!(None, tree.get("tenant1/a", None)?);
+    assert_eq!(Some("a_v2".as_bytes().into()), tree.get("tenant2/a", None)?);
+    assert_eq!(Some("a".as_bytes().into()), tree.get("tenant3/a", None)?);
+
+    // NOTE: The removed item's tombstone is dropped in the last level
+    assert_eq!(2, tree.approximate_len());
+
+    Ok(())
+}
+
+#[test]
+fn compaction_filter_respects_snapshot() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder)
+        .compaction_filter(Arc::new(DropTenant))
+        .open()?;
+
+    tree.insert("tenant1/a", "a", 0);
+    tree.insert("tenant2/a", "a", 1);
+    tree.flush_active_memtable(0)?;
+
+    // NOTE: A snapshot at seqno=2 sees the item written at seqno=1,
+    // so only the item written at seqno=0 (below the threshold) is filtered
+    tree.major_compact(u64::MAX, 1)?;
+
+    assert_eq!(None, tree.get("tenant1/a", None)?);
+    assert_eq!(None, tree.get("tenant1/a", Some(1))?);
+    assert_eq!(Some("a".as_bytes().into()), tree.get("tenant2/a", None)?);
+    assert_eq!(Some("a".as_bytes().into()), tree.get("tenant2/a", Some(2))?);
+
+    Ok(())
+}
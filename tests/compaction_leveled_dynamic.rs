@@ -0,0 +1,84 @@
+use lsm_tree::{compaction::Leveled, AbstractTree, Config, SeqNo, Tree};
+use std::sync::Arc;
+use test_log::test;
+
+const ITEM_COUNT: usize = 100;
+
+fn flush_batches(tree: &Tree, batches: std::ops::Range<usize>) -> lsm_tree::Result<()> {
+    for batch in batches {
+        for idx in 0..ITEM_COUNT {
+            tree.insert(
+                format!("{batch:0>3}{idx:0>6}"),
+                "a".repeat(100),
+                batch as SeqNo,
+            );
+        }
+        tree.flush_active_memtable(0)?;
+    }
+    Ok(())
+}
+
+fn non_empty_levels(tree: &Tree) -> Vec<usize> {
+    tree.levels
+        .read()
+        .expect("lock is poisoned")
+        .levels
+        .iter()
+        .enumerate()
+        .filter(|(_, level)| !level.is_empty())
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+#[test]
+fn leveled_dynamic_level_bytes() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder).data_block_size(1_024).open()?;
+
+    let strategy = Arc::new(Leveled {
+        target_size: 4_096,
+        dynamic_level_bytes: true,
+        ..Default::default()
+    });
+
+    // NOTE: Small tree, so L0 is merged directly into the last level
+    flush_batches(&tree, 0..4)?;
+    tree.compact(strategy.clone(), SeqNo::MAX)?;
+    assert_eq!(vec![6], non_empty_levels(&tree));
+
+    // NOTE: Last level is larger than the base size, so the base level moves up
+    flush_batches(&tree, 4..8)?;
+    tree.compact(strategy.clone(), SeqNo::MAX)?;
+    assert_eq!(vec![5, 6], non_empty_levels(&tree));
+
+    // NOTE: L5 is over its target size (1/10th of L6)
+    tree.compact(strategy, SeqNo::MAX)?;
+    assert_eq!(vec![6], non_empty_levels(&tree));
+
+    assert_eq!(8 * ITEM_COUNT, tree.approximate_len());
+
+    for batch in 0..8 {
+        for idx in 0..ITEM_COUNT {
+            assert!(tree.contains_key(format!("{batch:0>3}{idx:0>6}"), None)?);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn leveled_static_level_bytes() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder).data_block_size(1_024).open()?;
+
+    let strategy = Arc::new(Leveled {
+        target_size: 4_096,
+        ..Default::default()
+    });
+
+    flush_batches(&tree, 0..4)?;
+    tree.compact(strategy, SeqNo::MAX)?;
+    assert_eq!(vec![1], non_empty_levels(&tree));
+
+    Ok(())
+}
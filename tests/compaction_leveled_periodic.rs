@@ -0,0 +1,41 @@
+use lsm_tree::{compaction::Leveled, AbstractTree, Config, SeqNo};
+use std::{sync::Arc, time::Duration};
+use test_log::test;
+
+#[test]
+fn leveled_periodic_compaction() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder).open()?;
+
+    tree.insert("a", "old", 0);
+    tree.flush_active_memtable(0)?;
+    tree.insert("a", "new", 1);
+    tree.flush_active_memtable(0)?;
+
+    // NOTE: Keep both versions around
+    tree.major_compact(u64::MAX, 0)?;
+    assert_eq!(1, tree.segment_count());
+    assert_eq!(2, tree.approximate_len());
+
+    let strategy = Arc::new(Leveled {
+        periodic_compaction_seconds: Some(1),
+        ..Default::default()
+    });
+
+    // NOTE: Segment is not old enough yet
+    tree.compact(strategy.clone(), SeqNo::MAX)?;
+    assert_eq!(2, tree.approximate_len());
+
+    std::thread::sleep(Duration::from_millis(1_100));
+
+    tree.compact(strategy.clone(), SeqNo::MAX)?;
+    assert_eq!(1, tree.segment_count());
+    assert_eq!(1, tree.approximate_len());
+    assert_eq!(Some("new".as_bytes().into()), tree.get("a", None)?);
+
+    // NOTE: Rewritten segment is fresh again
+    tree.compact(strategy, SeqNo::MAX)?;
+    assert_eq!(1, tree.segment_count());
+
+    Ok(())
+}
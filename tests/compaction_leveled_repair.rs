@@ -0,0 +1,51 @@
+use lsm_tree::{
+    compaction::{Leveled, MoveDown},
+    AbstractTree, Config, SeqNo,
+};
+use std::sync::Arc;
+use test_log::test;
+
+#[test]
+fn leveled_repair_non_disjoint_level() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder).open()?;
+
+    tree.insert("a", "a", 0);
+    tree.insert("c", "c", 0);
+    tree.flush_active_memtable(0)?;
+
+    tree.insert("b", "b", 1);
+    tree.insert("d", "d", 1);
+    tree.flush_active_memtable(0)?;
+
+    tree.insert("x", "x", 2);
+    tree.insert("z", "z", 2);
+    tree.flush_active_memtable(0)?;
+
+    // NOTE: Force overlapping segments into L1
+    tree.compact(Arc::new(MoveDown(0, 1)), SeqNo::MAX)?;
+
+    {
+        let levels = tree.levels.read().expect("lock is poisoned");
+        let level = levels.levels.get(1).expect("level should exist");
+        assert_eq!(3, level.len());
+        assert!(!level.is_disjoint);
+    }
+
+    tree.compact(Arc::new(Leveled::default()), SeqNo::MAX)?;
+
+    {
+        let levels = tree.levels.read().expect("lock is poisoned");
+        let level = levels.levels.get(1).expect("level should exist");
+        assert!(level.is_disjoint);
+
+        // NOTE: Only the overlapping segments were rewritten
+        assert_eq!(2, level.len());
+    }
+
+    for key in ["a", "b", "c", "d", "x", "z"] {
+        assert_eq!(Some(key.as_bytes().into()), tree.get(key, None)?);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use lsm_tree::{compaction::SegmentPartitioner, AbstractTree, Config, KeyRange, SeqNo};
+use std::sync::Arc;
+use test_log::test;
+
+struct TenantPartitioner;
+
+fn tenant(key: &[u8]) -> &[u8] {
+    let end = key.iter().position(|&x| x == b'/').unwrap_or(key.len());
+    key.get(..end).unwrap_or_default()
+}
+
+impl SegmentPartitioner for TenantPartitioner {
+    fn should_partition(&self, prev: &[u8], next: &[u8]) -> bool {
+        tenant(prev) != tenant(next)
+    }
+}
+
+#[test]
+fn compaction_partitioner_tenants() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let tree = Config::new(&folder)
+        .segment_partitioner(Arc::new(TenantPartitioner))
+        .open()?;
+
+    for tenant in ["a", "b", "c"] {
+        for x in 0..10 {
+            tree.insert(format!("{tenant}/{x}"), "abc", 0);
+        }
+    }
+    tree.flush_active_memtable(0)?;
+    assert_eq!(1, tree.segment_count());
+
+    tree.major_compact(u64::MAX, SeqNo::MAX)?;
+    assert_eq!(3, tree.segment_count());
+
+    {
+        let levels = tree.levels.read().expect("lock is poisoned");
+
+        for segment in levels.iter() {
+            let key_range = &segment.metadata.key_range;
+            assert_eq!(tenant(key_range.min()), tenant(key_range.max()));
+        }
+    }
+
+    // NOTE: Dropping a tenant only touches its own segment
+    tree.drop_range(KeyRange::new(("b/".into(), "b/\u{ff}".into())))?;
+    assert_eq!(2, tree.segment_count());
+
+    assert!(tree.contains_key("a/0", None)?);
+    assert!(!tree.contains_key("b/0", None)?);
+    assert!(tree.contains_key("c/0", None)?);
+
+    Ok(())
+}
@@ -0,0 +1,40 @@
+use lsm_tree::{compaction::Leveled, AbstractTree, Config, SequenceNumberCounter};
+use std::sync::Arc;
+use test_log::test;
+
+#[test]
+fn compaction_snapshot_stripes() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let tree = Config::new(&folder).open()?;
+
+    let seqno = SequenceNumberCounter::default();
+
+    for _ in 0..4 {
+        for _ in 0..3 {
+            let seqno = seqno.next();
+            tree.insert("a", format!("v{seqno}"), seqno);
+        }
+        tree.flush_active_memtable(0)?;
+    }
+    assert_eq!(12, tree.approximate_len());
+
+    let old_snapshot = tree.snapshot(3);
+    let new_snapshot = tree.snapshot(7);
+
+    tree.compact_with_snapshots(
+        Arc::new(Leveled::default()),
+        0,
+        &[old_snapshot.seqno, new_snapshot.seqno],
+    )?;
+    assert_eq!(1, tree.segment_count());
+
+    // NOTE: Only the newest version of every snapshot stripe is kept
+    assert_eq!(3, tree.approximate_len());
+
+    assert_eq!(Some("v2".as_bytes().into()), old_snapshot.get("a")?);
+    assert_eq!(Some("v6".as_bytes().into()), new_snapshot.get("a")?);
+    assert_eq!(Some("v11".as_bytes().into()), tree.get("a", None)?);
+
+    Ok(())
+}
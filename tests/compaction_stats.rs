@@ -0,0 +1,79 @@
+use lsm_tree::{compaction::CompactionStats, AbstractTree, Config, SeqNo};
+use test_log::test;
+
+#[test]
+fn compaction_stats() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder).open()?;
+
+    assert_eq!(CompactionStats::default(), tree.compaction_stats());
+    assert_eq!(None, tree.compaction_stats().write_amplification());
+
+    tree.insert("a", "a", 0);
+    tree.insert("b", "b", 0);
+    tree.insert("c", "c", 0);
+    tree.flush_active_memtable(0)?;
+
+    tree.insert("a", "a", 1);
+    tree.remove("b", 1);
+    tree.flush_active_memtable(0)?;
+
+    let flushed_bytes = tree.disk_space();
+
+    let stats = tree.compaction_stats();
+    assert_eq!(flushed_bytes, stats.bytes_flushed);
+    assert_eq!(Some(1.0), stats.write_amplification());
+
+    tree.major_compact(u64::MAX, SeqNo::MAX)?;
+
+    let stats = tree.compaction_stats();
+    let last_level = stats.levels.last().expect("should exist");
+    assert_eq!(6, stats.levels.len() - 1);
+    assert_eq!(1, last_level.compactions);
+    assert_eq!(flushed_bytes, last_level.bytes_read);
+    assert_eq!(tree.disk_space(), last_level.bytes_written);
+    assert_eq!(2, last_level.versions_dropped);
+    assert_eq!(1, last_level.tombstones_dropped);
+    assert_eq!(last_level, &stats.total());
+
+    let write_amp = stats.write_amplification().expect("should exist");
+    assert!(write_amp > 1.0);
+    let level_write_amp = stats.level_write_amplification(6).expect("should exist");
+    assert!((write_amp - 1.0 - level_write_amp).abs() < f64::EPSILON);
+
+    tree.set_compaction_stats(CompactionStats::default());
+    assert_eq!(CompactionStats::default(), tree.compaction_stats());
+
+    Ok(())
+}
+
+#[test]
+fn compaction_stats_ingest_not_flushed() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder).open()?;
+
+    tree.ingest(
+        ["a", "b", "c"]
+            .into_iter()
+            .map(|key| (key.into(), key.into())),
+    )?;
+    assert_eq!(1, tree.segment_count());
+    assert_eq!(0, tree.compaction_stats().bytes_flushed);
+
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder).open_as_blob_tree()?;
+
+    tree.ingest(
+        ["a", "b", "c"]
+            .into_iter()
+            .map(|key| (key.into(), key.into())),
+    )?;
+    assert_eq!(1, tree.segment_count());
+    assert_eq!(0, tree.compaction_stats().bytes_flushed);
+
+    tree.insert("d", "d", 0);
+    tree.flush_active_memtable(0)?;
+    assert!(tree.compaction_stats().bytes_flushed > 0);
+
+    Ok(())
+}
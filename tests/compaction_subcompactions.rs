@@ -0,0 +1,124 @@
+use lsm_tree::{AbstractTree, Config, SeqNo};
+use test_log::test;
+
+const ITEM_COUNT: usize = 10_000;
+
+#[test]
+fn compaction_subcompactions() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let tree = Config::new(&folder)
+        .data_block_size(1_024)
+        .max_subcompactions(4)
+        .open()?;
+
+    for batch in 0..4 {
+        for idx in (batch..ITEM_COUNT).step_by(4) {
+            tree.insert(format!("{idx:0>6}"), "old", idx as SeqNo);
+        }
+        tree.flush_active_memtable(0)?;
+    }
+
+    for idx in (0..ITEM_COUNT).step_by(2) {
+        tree.insert(format!("{idx:0>6}"), "new", (ITEM_COUNT + idx) as SeqNo);
+    }
+    for idx in (0..ITEM_COUNT).step_by(3) {
+        tree.remove(format!("{idx:0>6}"), (2 * ITEM_COUNT + idx) as SeqNo);
+    }
+    tree.flush_active_memtable(0)?;
+    assert_eq!(5, tree.segment_count());
+
+    tree.major_compact(16 * 1_024, SeqNo::MAX)?;
+    assert!(tree.segment_count() > 1);
+
+    let mut expected_len = 0;
+
+    for idx in 0..ITEM_COUNT {
+        let key = format!("{idx:0>6}");
+        let value = tree.get(&key, None)?;
+
+        if idx % 3 == 0 {
+            assert_eq!(None, value, "{key} should be deleted");
+        } else if idx % 2 == 0 {
+            assert_eq!(Some("new".as_bytes().into()), value);
+            expected_len += 1;
+        } else {
+            assert_eq!(Some("old".as_bytes().into()), value);
+            expected_len += 1;
+        }
+    }
+
+    // NOTE: Old versions and tombstones are evicted
+    assert_eq!(expected_len, tree.approximate_len());
+
+    Ok(())
+}
+
+#[test]
+fn compaction_subcompactions_recover() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    {
+        let tree = Config::new(&folder)
+            .data_block_size(1_024)
+            .max_subcompactions(8)
+            .open()?;
+
+        for idx in 0..ITEM_COUNT {
+            tree.insert(format!("{idx:0>6}"), "a", idx as SeqNo);
+        }
+        tree.flush_active_memtable(0)?;
+
+        tree.major_compact(8 * 1_024, SeqNo::MAX)?;
+        assert!(tree.segment_count() > 1);
+    }
+
+    let tree = Config::new(&folder).open()?;
+
+    for idx in 0..ITEM_COUNT {
+        assert!(tree.contains_key(format!("{idx:0>6}"), None)?);
+    }
+    assert_eq!(ITEM_COUNT, tree.approximate_len());
+
+    Ok(())
+}
+
+#[test]
+fn compaction_subcompactions_versions_span_blocks() -> lsm_tree::Result<()> {
+    const KEY_COUNT: usize = 20;
+    const VERSION_COUNT: usize = 100;
+
+    let folder = tempfile::tempdir()?;
+
+    let tree = Config::new(&folder)
+        .data_block_size(1_024)
+        .max_subcompactions(4)
+        .open()?;
+
+    for version in 0..VERSION_COUNT {
+        for idx in 0..KEY_COUNT {
+            let seqno = (version * KEY_COUNT + idx) as SeqNo;
+            tree.insert(format!("{idx:0>6}"), format!("{version:0>40}"), seqno);
+        }
+    }
+    tree.flush_active_memtable(0)?;
+
+    // NOTE: The versions of a single key are spread over multiple blocks,
+    // so subranges are split at keys whose versions continue in the next block
+    tree.major_compact(4 * 1_024, 0)?;
+    assert!(tree.segment_count() > 1);
+
+    for version in 0..VERSION_COUNT {
+        for idx in 0..KEY_COUNT {
+            let seqno = (version * KEY_COUNT + idx) as SeqNo;
+
+            assert_eq!(
+                Some(format!("{version:0>40}").as_bytes().into()),
+                tree.get(format!("{idx:0>6}"), Some(seqno + 1))?,
+                "version {version} of key {idx} should be visible",
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,158 @@
+use lsm_tree::{
+    compaction::{TimeWindow, TimestampExtractor},
+    AbstractTree, Config, SeqNo, Tree,
+};
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use test_log::test;
+
+struct SeriesTimestamp;
+
+impl TimestampExtractor for SeriesTimestamp {
+    fn extract(&self, key: &[u8]) -> Option<u64> {
+        let pos = key.iter().rposition(|&x| x == b'/')?;
+        let bytes = key.get(pos + 1..)?;
+        std::str::from_utf8(bytes).ok()?.parse().ok()
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("time went backwards")
+        .as_secs()
+}
+
+fn flush_series(tree: &Tree, timestamps: &[u64], seqno: SeqNo) -> lsm_tree::Result<()> {
+    for series in ["cpu", "mem"] {
+        for ts in timestamps {
+            tree.insert(format!("{series}/{ts:0>20}"), "a", seqno);
+        }
+    }
+    tree.flush_active_memtable(0)?;
+    Ok(())
+}
+
+#[test]
+fn time_window_merges_within_windows() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder).open()?;
+
+    let strategy = Arc::new(TimeWindow {
+        window_size_seconds: 100,
+        timestamp_extractor: Some(Arc::new(SeriesTimestamp)),
+        ..Default::default()
+    });
+
+    flush_series(&tree, &[0, 10], 0)?;
+    flush_series(&tree, &[20, 30], 1)?;
+    flush_series(&tree, &[100, 110], 2)?;
+    flush_series(&tree, &[120, 130], 3)?;
+    assert_eq!(4, tree.segment_count());
+
+    // NOTE: Old window is merged into a single segment
+    tree.compact(strategy.clone(), SeqNo::MAX)?;
+    assert_eq!(3, tree.segment_count());
+
+    // NOTE: Newest window has not reached `min_merge_width` yet
+    tree.compact(strategy.clone(), SeqNo::MAX)?;
+    assert_eq!(3, tree.segment_count());
+
+    flush_series(&tree, &[140], 4)?;
+    flush_series(&tree, &[150], 5)?;
+
+    tree.compact(strategy.clone(), SeqNo::MAX)?;
+    assert_eq!(2, tree.segment_count());
+
+    // NOTE: Windows are never merged with each other
+    tree.compact(strategy, SeqNo::MAX)?;
+    assert_eq!(2, tree.segment_count());
+
+    assert_eq!(20, tree.approximate_len());
+
+    Ok(())
+}
+
+#[test]
+fn time_window_interleaved_windows() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder).open()?;
+
+    let strategy = Arc::new(TimeWindow {
+        window_size_seconds: 100,
+        timestamp_extractor: Some(Arc::new(SeriesTimestamp)),
+        ..Default::default()
+    });
+
+    tree.insert("cpu/00000000000000000005", "old", 0);
+    tree.flush_active_memtable(0)?;
+
+    // NOTE: Belongs to the newer window, but updates a key of the older window
+    tree.insert("cpu/00000000000000000005", "new", 1);
+    tree.insert("cpu/00000000000000000150", "a", 1);
+    tree.flush_active_memtable(0)?;
+
+    tree.insert("cpu/00000000000000000010", "a", 2);
+    tree.flush_active_memtable(0)?;
+    assert_eq!(3, tree.segment_count());
+
+    // NOTE: The segments of the older window are not adjacent, so they are not merged
+    tree.compact(strategy, SeqNo::MAX)?;
+    assert_eq!(3, tree.segment_count());
+
+    assert_eq!(
+        Some("new".as_bytes().into()),
+        tree.get("cpu/00000000000000000005", None)?,
+    );
+
+    Ok(())
+}
+
+#[test]
+fn time_window_drops_expired_windows() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder).open()?;
+
+    let strategy = Arc::new(TimeWindow {
+        window_size_seconds: 100,
+        retention_seconds: Some(1_000),
+        timestamp_extractor: Some(Arc::new(SeriesTimestamp)),
+        ..Default::default()
+    });
+
+    let now = now();
+
+    flush_series(&tree, &[now - 10_000], 0)?;
+    flush_series(&tree, &[now - 9_990], 1)?;
+    flush_series(&tree, &[now], 2)?;
+    assert_eq!(3, tree.segment_count());
+
+    tree.compact(strategy.clone(), SeqNo::MAX)?;
+    assert_eq!(1, tree.segment_count());
+    assert_eq!(2, tree.approximate_len());
+
+    tree.compact(strategy, SeqNo::MAX)?;
+    assert_eq!(1, tree.segment_count());
+
+    Ok(())
+}
+
+#[test]
+fn time_window_created_at() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder).open()?;
+
+    for seqno in 0..4 {
+        tree.insert(format!("{seqno}"), "a", seqno);
+        tree.flush_active_memtable(0)?;
+    }
+    assert_eq!(4, tree.segment_count());
+
+    tree.compact(Arc::new(TimeWindow::default()), SeqNo::MAX)?;
+    assert_eq!(1, tree.segment_count());
+    assert_eq!(4, tree.approximate_len());
+
+    Ok(())
+}
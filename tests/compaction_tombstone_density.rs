@@ -0,0 +1,42 @@
+use lsm_tree::{compaction::TombstoneDensity, AbstractTree, Config, SeqNo};
+use std::sync::Arc;
+use test_log::test;
+
+#[test]
+fn tombstone_density_evicts_tombstones() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder).open()?;
+
+    for key in ["a", "b", "c", "d"] {
+        tree.insert(key, "old", 0);
+    }
+    tree.flush_active_memtable(0)?;
+
+    tree.insert("e", "new", 1);
+    tree.flush_active_memtable(0)?;
+
+    for key in ["a", "b", "c"] {
+        tree.remove(key, 2);
+    }
+    tree.flush_active_memtable(0)?;
+    assert_eq!(3, tree.segment_count());
+    assert_eq!(8, tree.approximate_len());
+
+    tree.compact(Arc::new(TombstoneDensity::new(0.5)), SeqNo::MAX)?;
+
+    // NOTE: The segment that only contains "e" is not touched
+    assert_eq!(2, tree.segment_count());
+    assert_eq!(2, tree.approximate_len());
+
+    for key in ["a", "b", "c"] {
+        assert!(!tree.contains_key(key, None)?);
+    }
+    assert!(tree.contains_key("d", None)?);
+    assert!(tree.contains_key("e", None)?);
+
+    // NOTE: No tombstones left
+    tree.compact(Arc::new(TombstoneDensity::new(0.5)), SeqNo::MAX)?;
+    assert_eq!(2, tree.segment_count());
+
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use lsm_tree::{compaction::Universal, AbstractTree, Config, SeqNo};
+use std::sync::Arc;
+use test_log::test;
+
+#[test]
+fn universal_compaction_keeps_data() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder).open()?;
+
+    let strategy = Arc::new(Universal {
+        max_run_count: 4,
+        ..Default::default()
+    });
+
+    let mut seqno = 0;
+
+    for batch in 0..20 {
+        for idx in 0..(batch + 1) * 10 {
+            tree.insert(format!("{idx:0>6}"), format!("{batch}"), seqno);
+            seqno += 1;
+        }
+        tree.flush_active_memtable(0)?;
+
+        tree.compact(strategy.clone(), SeqNo::MAX)?;
+        tree.compact(strategy.clone(), SeqNo::MAX)?;
+    }
+
+    assert!(tree.segment_count() <= 4);
+
+    for idx in 0..200 {
+        assert_eq!(
+            Some("19".as_bytes().into()),
+            tree.get(format!("{idx:0>6}"), None)?,
+        );
+    }
+
+    Ok(())
+}
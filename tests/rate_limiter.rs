@@ -0,0 +1,52 @@
+use lsm_tree::{AbstractTree, Config, RateLimiter, SeqNo};
+use std::{sync::Arc, time::Instant};
+use test_log::test;
+
+const ITEM_COUNT: usize = 1_000;
+
+#[test]
+fn rate_limiter_flush_and_compaction() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let rate_limiter = Arc::new(RateLimiter::new(1_000_000));
+
+    let tree = Config::new(&folder)
+        .data_block_size(1_024)
+        .use_rate_limiter(rate_limiter.clone())
+        .open()?;
+
+    let start = Instant::now();
+
+    for batch in 0..2 {
+        for idx in 0..ITEM_COUNT {
+            tree.insert(format!("{idx:0>6}"), "a".repeat(100), batch);
+        }
+        tree.flush_active_memtable(0)?;
+    }
+    assert_eq!(2, tree.segment_count());
+
+    // NOTE: 2 segments of ~100 KB are written, then read & written again
+    // minus the initial burst of 100 KB
+    tree.major_compact(u64::MAX, SeqNo::MAX)?;
+    assert!(start.elapsed().as_millis() >= 300, "{:?}", start.elapsed());
+
+    assert_eq!(1, tree.segment_count());
+    assert_eq!(ITEM_COUNT, tree.approximate_len());
+
+    // NOTE: Lift the limit, so the next flush is not throttled
+    rate_limiter.set_rate(u64::MAX);
+
+    for idx in 0..ITEM_COUNT {
+        tree.insert(format!("{idx:0>6}"), "b", 2);
+    }
+    tree.flush_active_memtable(0)?;
+
+    for idx in 0..ITEM_COUNT {
+        assert_eq!(
+            Some("b".as_bytes().into()),
+            tree.get(format!("{idx:0>6}"), None)?,
+        );
+    }
+
+    Ok(())
+}
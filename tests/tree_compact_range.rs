@@ -0,0 +1,98 @@
+use lsm_tree::{AbstractTree, Config, Error, KeyRange, SeqNo, Tree};
+use test_log::test;
+
+const ITEM_COUNT: usize = 100;
+
+fn key_range(min: &str, max: &str) -> KeyRange {
+    KeyRange::new((min.as_bytes().into(), max.as_bytes().into()))
+}
+
+fn level_segment_counts(tree: &Tree) -> Vec<usize> {
+    tree.levels
+        .read()
+        .expect("lock is poisoned")
+        .levels
+        .iter()
+        .map(|level| level.len())
+        .collect()
+}
+
+#[test]
+fn tree_compact_range() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder).open()?;
+
+    for (seqno, prefix) in ["a", "b", "c"].into_iter().enumerate() {
+        for idx in 0..ITEM_COUNT {
+            tree.insert(format!("{prefix}{idx:0>5}"), "old", seqno as SeqNo);
+        }
+        tree.flush_active_memtable(0)?;
+    }
+    assert_eq!(vec![3, 0, 0, 0, 0, 0, 0], level_segment_counts(&tree));
+
+    tree.compact_range(key_range("b", "b~"), 3, SeqNo::MAX)?;
+    assert_eq!(vec![2, 0, 0, 1, 0, 0, 0], level_segment_counts(&tree));
+
+    // NOTE: Newer versions are pushed down on top of the older ones
+    for idx in 0..ITEM_COUNT {
+        tree.insert(format!("b{idx:0>5}"), "new", 3);
+    }
+    tree.flush_active_memtable(0)?;
+
+    tree.compact_range(key_range("b", "b~"), 3, SeqNo::MAX)?;
+    assert_eq!(vec![2, 0, 0, 1, 0, 0, 0], level_segment_counts(&tree));
+
+    for idx in 0..ITEM_COUNT {
+        assert_eq!(
+            Some("new".as_bytes().into()),
+            tree.get(format!("b{idx:0>5}"), None)?,
+        );
+        assert!(tree.contains_key(format!("a{idx:0>5}"), None)?);
+        assert!(tree.contains_key(format!("c{idx:0>5}"), None)?);
+    }
+    assert_eq!(3 * ITEM_COUNT, tree.approximate_len());
+
+    Ok(())
+}
+
+#[test]
+fn tree_compact_range_evict_tombstones() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder).open()?;
+
+    for idx in 0..ITEM_COUNT {
+        tree.insert(format!("a{idx:0>5}"), "a", 0);
+        tree.insert(format!("b{idx:0>5}"), "b", 0);
+    }
+    tree.flush_active_memtable(0)?;
+
+    for idx in 0..ITEM_COUNT {
+        tree.remove(format!("b{idx:0>5}"), 1);
+    }
+    tree.flush_active_memtable(0)?;
+    assert_eq!(3 * ITEM_COUNT, tree.approximate_len());
+
+    tree.compact_range(key_range("b", "b~"), 6, SeqNo::MAX)?;
+    assert_eq!(vec![0, 0, 0, 0, 0, 0, 1], level_segment_counts(&tree));
+    assert_eq!(ITEM_COUNT, tree.approximate_len());
+
+    for idx in 0..ITEM_COUNT {
+        assert!(tree.contains_key(format!("a{idx:0>5}"), None)?);
+        assert!(!tree.contains_key(format!("b{idx:0>5}"), None)?);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn tree_compact_range_invalid_level() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder).open()?;
+
+    assert!(matches!(
+        tree.compact_range(key_range("a", "z"), 7, SeqNo::MAX),
+        Err(Error::InvalidLevel(7)),
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use lsm_tree::{AbstractTree, Config, KeyRange, SeqNo};
+use test_log::test;
+
+fn key_range(min: &str, max: &str) -> KeyRange {
+    KeyRange::new((min.as_bytes().into(), max.as_bytes().into()))
+}
+
+#[test]
+fn tree_drop_range_contained() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder).open()?;
+
+    for key in ["a", "b", "c"] {
+        tree.insert(key, "old", 0);
+    }
+    tree.flush_active_memtable(0)?;
+
+    for key in ["d", "e", "f"] {
+        tree.insert(key, "old", 1);
+    }
+    tree.flush_active_memtable(0)?;
+    assert_eq!(2, tree.segment_count());
+
+    tree.drop_range(key_range("a", "c"))?;
+    assert_eq!(1, tree.segment_count());
+
+    for key in ["a", "b", "c"] {
+        assert!(!tree.contains_key(key, None)?);
+    }
+    for key in ["d", "e", "f"] {
+        assert!(tree.contains_key(key, None)?);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn tree_drop_range_boundary() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder).open()?;
+
+    for key in ["a", "b", "c", "d"] {
+        tree.insert(key, "old", 0);
+    }
+    tree.flush_active_memtable(0)?;
+    tree.major_compact(u64::MAX, SeqNo::MAX)?;
+
+    // NOTE: Tombstone in a higher level that covers an item in the lower level
+    tree.remove("b", 1);
+    tree.insert("x", "new", 1);
+    tree.flush_active_memtable(0)?;
+
+    for key in ["c", "y"] {
+        tree.insert(key, "new", 2);
+    }
+    tree.flush_active_memtable(0)?;
+    assert_eq!(3, tree.segment_count());
+
+    tree.drop_range(key_range("b", "c"))?;
+
+    assert!(tree.contains_key("a", None)?);
+    assert!(!tree.contains_key("b", None)?);
+    assert!(!tree.contains_key("c", None)?);
+    assert!(tree.contains_key("d", None)?);
+    assert!(tree.contains_key("x", None)?);
+    assert!(tree.contains_key("y", None)?);
+
+    Ok(())
+}
+
+#[test]
+fn tree_drop_range_keeps_memtable() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder).open()?;
+
+    tree.insert("a", "old", 0);
+    tree.flush_active_memtable(0)?;
+
+    tree.insert("b", "new", 1);
+
+    tree.drop_range(key_range("a", "z"))?;
+    assert_eq!(0, tree.segment_count());
+
+    assert!(!tree.contains_key("a", None)?);
+    assert!(tree.contains_key("b", None)?);
+
+    Ok(())
+}
+
+#[test]
+fn tree_drop_range_recover() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    {
+        let tree = Config::new(&folder).open()?;
+
+        tree.insert("a", "a", 0);
+        tree.flush_active_memtable(0)?;
+        tree.insert("b", "b", 1);
+        tree.flush_active_memtable(0)?;
+
+        tree.drop_range(key_range("a", "a"))?;
+        assert_eq!(1, tree.segment_count());
+    }
+
+    let tree = Config::new(&folder).open()?;
+    assert_eq!(1, tree.segment_count());
+    assert!(!tree.contains_key("a", None)?);
+    assert!(tree.contains_key("b", None)?);
+
+    Ok(())
+}
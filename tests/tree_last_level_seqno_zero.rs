@@ -0,0 +1,145 @@
+use lsm_tree::{
+    compaction::{Choice, CompactionStrategy, Input},
+    level_manifest::LevelManifest,
+    AbstractTree, Config, SeqNo, SequenceNumberCounter,
+};
+use std::sync::Arc;
+use test_log::test;
+
+const ITEM_COUNT: usize = 100;
+
+fn last_level_seqnos(tree: &lsm_tree::Tree) -> (SeqNo, SeqNo) {
+    let levels = tree.levels.read().expect("lock is poisoned");
+    let level = levels.levels.last().expect("level should exist");
+    let segment = level.segments.first().expect("segment should exist");
+    segment.metadata.seqnos
+}
+
+#[test]
+fn tree_last_level_seqno_zero() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let tree = Config::new(&folder).open()?;
+
+    let seqno = SequenceNumberCounter::default();
+
+    for x in 0..ITEM_COUNT as u64 {
+        tree.insert(x.to_be_bytes(), "old", seqno.next());
+    }
+    tree.flush_active_memtable(0)?;
+
+    let watermark = seqno.get();
+
+    tree.major_compact(u64::MAX, watermark)?;
+    assert_eq!((0, 0), last_level_seqnos(&tree));
+
+    // NOTE: Newer versions in upper levels still shadow the zeroed versions
+    for x in 0..ITEM_COUNT as u64 {
+        tree.insert(x.to_be_bytes(), "new", seqno.next());
+    }
+    tree.flush_active_memtable(0)?;
+
+    let snapshot = seqno.get();
+
+    for x in 0..ITEM_COUNT as u64 {
+        tree.insert(x.to_be_bytes(), "newest", seqno.next());
+    }
+
+    // NOTE: Reads below the eviction watermark are not supported anymore,
+    // so only check reads at or above it
+    for x in 0..ITEM_COUNT as u64 {
+        let key = x.to_be_bytes();
+        assert_eq!(&*tree.get(key, None)?.unwrap(), b"newest");
+        assert_eq!(&*tree.get(key, Some(snapshot))?.unwrap(), b"new");
+        assert_eq!(&*tree.get(key, Some(watermark))?.unwrap(), b"old");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn tree_last_level_seqno_zero_watermark() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let tree = Config::new(&folder).open()?;
+
+    let seqno = SequenceNumberCounter::default();
+
+    for x in 0..ITEM_COUNT as u64 {
+        tree.insert(x.to_be_bytes(), "abc", seqno.next());
+    }
+    tree.flush_active_memtable(0)?;
+
+    // NOTE: Versions that are not older than the watermark keep their seqno
+    tree.major_compact(u64::MAX, 50)?;
+    assert_eq!((0, ITEM_COUNT as SeqNo - 1), last_level_seqnos(&tree));
+
+    for x in 0..ITEM_COUNT as u64 {
+        let key = x.to_be_bytes();
+        assert!(tree.contains_key(key, None)?);
+        assert_eq!(x <= 50, tree.contains_key(key, Some(51))?);
+    }
+
+    Ok(())
+}
+
+/// Merges the first level into the last level, leaving the last level's segments alone
+struct MergeIntoLastLevel;
+
+impl CompactionStrategy for MergeIntoLastLevel {
+    fn get_name(&self) -> &'static str {
+        "MergeIntoLastLevel"
+    }
+
+    fn choose(&self, levels: &LevelManifest, _: &Config) -> Choice {
+        let first_level = levels.levels.first().expect("level should exist");
+
+        Choice::Merge(Input {
+            segment_ids: first_level.segments.iter().map(|x| x.id()).collect(),
+            dest_level: levels.last_level_index(),
+            target_size: u64::MAX,
+        })
+    }
+}
+
+#[test]
+fn tree_last_level_seqno_zero_overlapping_segment() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let tree = Config::new(&folder).open()?;
+
+    let seqno = SequenceNumberCounter::default();
+
+    for x in 0..ITEM_COUNT as u64 {
+        tree.insert(x.to_be_bytes(), "old", seqno.next());
+    }
+    tree.flush_active_memtable(0)?;
+
+    // NOTE: Nothing is below the watermark, so the old versions keep their seqnos
+    tree.major_compact(u64::MAX, 0)?;
+    assert_eq!((0, ITEM_COUNT as SeqNo - 1), last_level_seqnos(&tree));
+
+    for x in 0..ITEM_COUNT as u64 {
+        tree.insert(x.to_be_bytes(), "new", seqno.next());
+    }
+    tree.flush_active_memtable(0)?;
+
+    // NOTE: The overlapping last level segment is not part of the compaction,
+    // so the new versions must keep their seqnos to still shadow the old ones
+    tree.compact(Arc::new(MergeIntoLastLevel), SeqNo::MAX)?;
+
+    let levels = tree.levels.read().expect("lock is poisoned");
+    let last_level = levels.levels.last().expect("level should exist");
+    assert_eq!(2, last_level.segments.len());
+    assert!(last_level
+        .segments
+        .iter()
+        .all(|segment| segment.metadata.seqnos.1 > 0));
+    drop(levels);
+
+    for x in 0..ITEM_COUNT as u64 {
+        assert_eq!(&*tree.get(x.to_be_bytes(), None)?.unwrap(), b"new");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,150 @@
+use lsm_tree::{
+    segment::filter::BloomConstructionPolicy, AbstractTree, Config, LevelOptions, SeqNo,
+    SequenceNumberCounter,
+};
+use test_log::test;
+
+const ITEM_COUNT: usize = 1_000;
+
+#[test]
+fn tree_level_options_data_block_size() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let tree = Config::new(&folder)
+        .data_block_size(1_024)
+        .level_options(6, LevelOptions::default().data_block_size(64 * 1_024))
+        .open()?;
+
+    let seqno = SequenceNumberCounter::default();
+
+    for x in 0..ITEM_COUNT as u64 {
+        let key = x.to_be_bytes();
+        let value = nanoid::nanoid!();
+        tree.insert(key, value.as_bytes(), seqno.next());
+    }
+
+    tree.flush_active_memtable(0)?;
+
+    let l0_block_count = {
+        let levels = tree.levels.read().expect("lock is poisoned");
+        let level = levels.levels.first().expect("level should exist");
+        let segment = level.segments.first().expect("segment should exist");
+        segment.metadata.data_block_count
+    };
+
+    tree.major_compact(u64::MAX, SeqNo::MAX)?;
+
+    let l6_block_count = {
+        let levels = tree.levels.read().expect("lock is poisoned");
+        let level = levels.levels.get(6).expect("level should exist");
+        let segment = level.segments.first().expect("segment should exist");
+        segment.metadata.data_block_count
+    };
+
+    assert!(l6_block_count < l0_block_count);
+    assert_eq!(ITEM_COUNT, tree.approximate_len());
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "lz4")]
+fn tree_level_options_compression() -> lsm_tree::Result<()> {
+    use lsm_tree::CompressionType;
+
+    let folder = tempfile::tempdir()?;
+
+    let tree = Config::new(&folder)
+        .compression(CompressionType::Lz4)
+        .level_options(
+            0,
+            LevelOptions::default().compression(CompressionType::None),
+        )
+        .open()?;
+
+    let seqno = SequenceNumberCounter::default();
+
+    for x in 0..ITEM_COUNT as u64 {
+        let key = x.to_be_bytes();
+        tree.insert(key, "abc".repeat(50), seqno.next());
+    }
+
+    tree.flush_active_memtable(0)?;
+
+    {
+        let levels = tree.levels.read().expect("lock is poisoned");
+        let level = levels.levels.first().expect("level should exist");
+        let segment = level.segments.first().expect("segment should exist");
+        assert_eq!(
+            CompressionType::None,
+            segment.metadata.data_block_compression
+        );
+    }
+
+    tree.major_compact(u64::MAX, SeqNo::MAX)?;
+
+    {
+        let levels = tree.levels.read().expect("lock is poisoned");
+        let level = levels.levels.get(6).expect("level should exist");
+        let segment = level.segments.first().expect("segment should exist");
+        assert_eq!(
+            CompressionType::Lz4,
+            segment.metadata.data_block_compression
+        );
+    }
+
+    for x in 0..ITEM_COUNT as u64 {
+        assert!(tree.contains_key(x.to_be_bytes(), None)?);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn tree_level_options_bloom_policy() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let tree = Config::new(&folder)
+        .level_options(
+            6,
+            LevelOptions::default().bloom_policy(BloomConstructionPolicy::BitsPerKey(0)),
+        )
+        .open()?;
+
+    // NOTE: Ingestion writes into the last level
+    tree.ingest((0..ITEM_COUNT as u64).map(|x| (x.to_be_bytes().into(), "abc".into())))?;
+
+    {
+        let levels = tree.levels.read().expect("lock is poisoned");
+        let level = levels.levels.get(6).expect("level should exist");
+        let segment = level.segments.first().expect("segment should exist");
+        assert!(segment.pinned_filter.is_none());
+    }
+
+    let folder = tempfile::tempdir()?;
+
+    let tree = Config::new(&folder)
+        .level_options(
+            0,
+            LevelOptions::default().bloom_policy(BloomConstructionPolicy::BitsPerKey(0)),
+        )
+        .open_as_blob_tree()?;
+
+    for x in 0..ITEM_COUNT as u64 {
+        tree.insert(x.to_be_bytes(), "abc", x);
+    }
+    tree.flush_active_memtable(0)?;
+
+    {
+        let levels = tree.index.levels.read().expect("lock is poisoned");
+        let level = levels.levels.first().expect("level should exist");
+        let segment = level.segments.first().expect("segment should exist");
+        assert!(segment.pinned_filter.is_none());
+    }
+
+    for x in 0..ITEM_COUNT as u64 {
+        assert!(tree.contains_key(x.to_be_bytes(), None)?);
+    }
+
+    Ok(())
+}
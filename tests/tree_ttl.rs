@@ -0,0 +1,119 @@
+use lsm_tree::{AbstractTree, Config, SeqNo, ValueType};
+use test_log::test;
+
+#[test]
+fn tree_ttl_point_read() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder).open()?;
+
+    tree.insert("a", "old", 0);
+    tree.insert_with_expiry("a", "new", /* long ago */ 1, 1);
+    tree.insert_with_expiry("b", "b", u64::MAX, 2);
+    tree.insert("c", "c", 3);
+
+    // NOTE: Expired value hides older versions, like a tombstone
+    assert_eq!(None, tree.get("a", None)?);
+    assert_eq!(Some("old".as_bytes().into()), tree.get("a", Some(1))?);
+    assert_eq!(Some("b".as_bytes().into()), tree.get("b", None)?);
+    assert_eq!(Some("c".as_bytes().into()), tree.get("c", None)?);
+
+    tree.flush_active_memtable(0)?;
+
+    assert_eq!(None, tree.get("a", None)?);
+    assert_eq!(Some("old".as_bytes().into()), tree.get("a", Some(1))?);
+    assert_eq!(Some("b".as_bytes().into()), tree.get("b", None)?);
+    assert_eq!(Some("c".as_bytes().into()), tree.get("c", None)?);
+
+    Ok(())
+}
+
+#[test]
+fn tree_ttl_flush_drops_expired() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder).open()?;
+
+    tree.insert("a", "old", 0);
+    tree.insert_with_expiry("a", "new", /* long ago */ 1, 1);
+    tree.insert_with_expiry("b", "b", u64::MAX, 2);
+    tree.insert_with_expiry("c", "c", /* long ago */ 1, 3);
+
+    // NOTE: Old version of "a" is garbage collected, the expired values
+    // are kept as tombstones, because they may cover data in older segments
+    let segment = tree
+        .flush_active_memtable(SeqNo::MAX)?
+        .expect("should flush");
+    assert_eq!(3, segment.metadata.item_count);
+
+    let item = tree.get_internal_entry(b"b", None)?.expect("should exist");
+    assert_eq!(&*item.value, b"b");
+
+    assert_eq!(None, tree.get("a", None)?);
+    assert_eq!(None, tree.get("c", None)?);
+
+    Ok(())
+}
+
+#[test]
+fn tree_ttl_blob_point_read() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder)
+        .blob_file_separation_threshold(1)
+        .open_as_blob_tree()?;
+
+    let big_value = "x".repeat(1_000);
+
+    tree.insert_with_expiry("a", &big_value, /* long ago */ 1, 0);
+    tree.insert_with_expiry("b", &big_value, u64::MAX, 1);
+
+    assert_eq!(None, tree.get("a", None)?);
+    assert_eq!(Some(big_value.as_bytes().into()), tree.get("b", None)?);
+
+    tree.flush_active_memtable(0)?;
+
+    assert_eq!(None, tree.get("a", None)?);
+    assert_eq!(Some(big_value.as_bytes().into()), tree.get("b", None)?);
+
+    Ok(())
+}
+
+#[test]
+fn tree_ttl_blob_gc_keeps_expiry() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let tree = Config::new(&folder)
+        .blob_file_separation_threshold(1)
+        .open_as_blob_tree()?;
+
+    let big_value = "x".repeat(1_000);
+
+    tree.insert_with_expiry("a", &big_value, u64::MAX, 0);
+    tree.insert("b", &big_value, 1);
+    tree.insert_with_expiry("c", &big_value, /* long ago */ 1, 2);
+    tree.flush_active_memtable(0)?;
+    assert_eq!(1, tree.blobs.segment_count());
+
+    tree.insert("b", "b", 3);
+
+    // NOTE: Mark the blob of "b" as stale, so the blob file is rewritten
+    for blob_file in tree.blobs.manifest.list_segments() {
+        blob_file.gc_stats.set_stale_items(1);
+        blob_file.gc_stats.set_stale_bytes(1_000);
+    }
+
+    let strategy = value_log::StaleThresholdStrategy::new(0.0);
+    tree.apply_gc_strategy(&strategy, 4)?;
+    assert_eq!(1, tree.blobs.segment_count());
+
+    // NOTE: The relocated value of "a" still expires
+    let item = tree
+        .index
+        .get_internal_entry(b"a", None)?
+        .expect("should exist");
+    assert_eq!(4, item.key.seqno);
+    assert_eq!(ValueType::ExpiringValue, item.key.value_type);
+
+    assert_eq!(Some(big_value.as_bytes().into()), tree.get("a", None)?);
+    assert_eq!(Some("b".as_bytes().into()), tree.get("b", None)?);
+    assert_eq!(None, tree.get("c", None)?);
+
+    Ok(())
+}
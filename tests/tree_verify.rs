@@ -0,0 +1,130 @@
+use lsm_tree::{AbstractTree, Config, Error};
+use std::{
+    io::{Seek, SeekFrom, Write},
+    path::Path,
+};
+use test_log::test;
+
+const ITEM_COUNT: u64 = 1_000;
+
+fn corrupt(path: &Path, offset: u64) -> std::io::Result<()> {
+    let mut file = std::fs::OpenOptions::new().write(true).open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    file.write_all(b"!")?;
+    file.sync_all()
+}
+
+#[test]
+fn tree_verify() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let segment_path = {
+        let tree = Config::new(&folder).open()?;
+
+        for x in 0..ITEM_COUNT {
+            tree.insert(x.to_be_bytes(), "neptune", x);
+        }
+        let segment = tree.flush_active_memtable(0)?.unwrap();
+
+        for x in 0..ITEM_COUNT {
+            tree.insert(x.to_be_bytes(), "saturn", ITEM_COUNT + x);
+        }
+        tree.flush_active_memtable(0)?;
+
+        assert_eq!(2 * ITEM_COUNT as usize, tree.verify()?);
+
+        segment.path.clone()
+    };
+
+    // NOTE: The first data block starts at offset 0
+    corrupt(&segment_path, 100)?;
+
+    let tree = Config::new(&folder).open()?;
+
+    match tree.verify() {
+        Err(Error::Corrupted(blocks)) => {
+            assert_eq!(1, blocks.len());
+
+            let block = blocks.first().unwrap();
+            assert_eq!(segment_path, block.path);
+            assert_eq!(0, block.offset);
+            assert!(matches!(*block.source, Error::InvalidChecksum(_)));
+        }
+        other => panic!("expected corruption, got {other:?}"),
+    }
+
+    // NOTE: Newer versions are stored in the intact segment
+    assert_eq!(&*tree.get(0u64.to_be_bytes(), None)?.unwrap(), b"saturn");
+
+    assert!(matches!(
+        tree.get(0u64.to_be_bytes(), Some(ITEM_COUNT)),
+        Err(Error::InvalidChecksum(_)),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn tree_verify_cache_hits() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let tree = Config::new(&folder).verify_cache_hits(true).open()?;
+
+    for x in 0..ITEM_COUNT {
+        tree.insert(x.to_be_bytes(), "neptune", x);
+    }
+    tree.flush_active_memtable(0)?;
+
+    for _ in 0..2 {
+        for x in 0..ITEM_COUNT {
+            assert_eq!(&*tree.get(x.to_be_bytes(), None)?.unwrap(), b"neptune");
+        }
+    }
+
+    assert_eq!(ITEM_COUNT as usize, tree.verify()?);
+
+    Ok(())
+}
+
+#[test]
+fn blob_tree_verify() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let big_value = b"neptune!".repeat(128_000);
+
+    let blob_file_path = {
+        let tree = Config::new(&folder).open_as_blob_tree()?;
+
+        tree.insert("a", &big_value, 0);
+        tree.insert("b", &big_value, 0);
+        tree.insert("c", "smol", 0);
+        tree.flush_active_memtable(0)?;
+
+        // NOTE: 3 index tree items + 2 blobs
+        assert_eq!(5, tree.verify()?);
+
+        let blob_files = tree.blobs.manifest.list_segments();
+        blob_files.first().unwrap().path.clone()
+    };
+
+    // NOTE: Corrupt the value of the second blob
+    let second_blob_offset = 8 + 8 + 2 + 1 + 4 + big_value.len() as u64;
+    corrupt(&blob_file_path, second_blob_offset + 100)?;
+
+    let tree = Config::new(&folder).open_as_blob_tree()?;
+
+    match tree.verify() {
+        Err(Error::Corrupted(blocks)) => {
+            assert_eq!(1, blocks.len());
+
+            let block = blocks.first().unwrap();
+            assert_eq!(blob_file_path, block.path);
+            assert_eq!(second_blob_offset, block.offset);
+        }
+        other => panic!("expected corruption, got {other:?}"),
+    }
+
+    assert_eq!(&*tree.get("a", None)?.unwrap(), big_value);
+
+    Ok(())
+}
@@ -0,0 +1,136 @@
+#![cfg(feature = "zstd")]
+
+use lsm_tree::{AbstractTree, CompressionType, Config, SeqNo, SequenceNumberCounter};
+use test_log::test;
+
+const ITEM_COUNT: u64 = 50_000;
+
+/// Values are diverse within a block, but share their patterns across blocks
+fn value(x: u64) -> Vec<u8> {
+    let mut state = (x % 64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+
+    let mut value = (0..200)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            b'a' + (state % 26) as u8
+        })
+        .collect::<Vec<_>>();
+
+    value.extend_from_slice(&x.to_be_bytes());
+    value
+}
+
+fn fill(tree: &lsm_tree::Tree) -> lsm_tree::Result<()> {
+    let seqno = SequenceNumberCounter::default();
+
+    for x in 0..ITEM_COUNT {
+        tree.insert(x.to_be_bytes(), value(x), seqno.next());
+    }
+    tree.flush_active_memtable(0)?;
+
+    Ok(())
+}
+
+#[test]
+fn tree_zstd_compression_dictionary() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let folder_no_dict = tempfile::tempdir()?;
+
+    let no_dict_size = {
+        let tree = Config::new(&folder_no_dict)
+            .compression(CompressionType::Zstd(3))
+            .open()?;
+        fill(&tree)?;
+        tree.disk_space()
+    };
+
+    {
+        let tree = Config::new(&folder)
+            .compression(CompressionType::Zstd(3))
+            .zstd_dictionary_size(16 * 1_024)
+            .open()?;
+        fill(&tree)?;
+
+        // NOTE: The dictionary is stored once per segment, but saves space in every block
+        assert!(tree.disk_space() < no_dict_size);
+
+        for x in (0..ITEM_COUNT).step_by(97) {
+            assert_eq!(&*tree.get(x.to_be_bytes(), None)?.unwrap(), value(x));
+        }
+    }
+
+    {
+        let tree = Config::new(&folder)
+            .compression(CompressionType::Zstd(3))
+            .zstd_dictionary_size(16 * 1_024)
+            .open()?;
+
+        for x in (0..ITEM_COUNT).step_by(97) {
+            assert_eq!(&*tree.get(x.to_be_bytes(), None)?.unwrap(), value(x));
+        }
+
+        tree.major_compact(u64::MAX, SeqNo::MAX)?;
+        assert_eq!(1, tree.segment_count());
+
+        for x in (0..ITEM_COUNT).step_by(97) {
+            assert_eq!(&*tree.get(x.to_be_bytes(), None)?.unwrap(), value(x));
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn tree_zstd_compression_too_few_samples() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let tree = Config::new(&folder)
+        .compression(CompressionType::Zstd(3))
+        .zstd_dictionary_size(16 * 1_024)
+        .open()?;
+
+    // NOTE: Not enough data to train a dictionary, so blocks are written without one
+    tree.insert("a", "abc", 0);
+    tree.insert("b", "def", 1);
+    tree.flush_active_memtable(0)?;
+
+    assert_eq!(&*tree.get("a", None)?.unwrap(), b"abc");
+    assert_eq!(&*tree.get("b", None)?.unwrap(), b"def");
+
+    Ok(())
+}
+
+#[test]
+fn blob_tree_zstd_compression() -> lsm_tree::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let big_value = b"neptune!".repeat(128_000);
+
+    {
+        let tree = Config::new(&folder)
+            .compression(CompressionType::Zstd(3))
+            .blob_compression(CompressionType::Zstd(3))
+            .open_as_blob_tree()?;
+
+        tree.insert("big", &big_value, 0);
+        tree.insert("smol", "small value", 0);
+        tree.flush_active_memtable(0)?;
+
+        // NOTE: Blobs are compressed as well
+        assert!(tree.disk_space() < big_value.len() as u64);
+    }
+
+    {
+        let tree = Config::new(&folder)
+            .compression(CompressionType::Zstd(3))
+            .blob_compression(CompressionType::Zstd(3))
+            .open_as_blob_tree()?;
+
+        assert_eq!(&*tree.get("big", None)?.unwrap(), big_value);
+        assert_eq!(&*tree.get("smol", None)?.unwrap(), b"small value");
+    }
+
+    Ok(())
+}
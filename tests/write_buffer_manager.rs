@@ -0,0 +1,48 @@
+use lsm_tree::{AbstractTree, Config, WriteBufferManager};
+use std::sync::Arc;
+use test_log::test;
+
+#[test]
+fn write_buffer_manager_flush_largest() -> lsm_tree::Result<()> {
+    let write_buffer_manager = Arc::new(WriteBufferManager::new(5_000));
+
+    let folder0 = tempfile::tempdir()?;
+    let tree0 = Config::new(&folder0)
+        .use_write_buffer_manager(write_buffer_manager.clone())
+        .open()?;
+
+    let folder1 = tempfile::tempdir()?;
+    let tree1 = Config::new(&folder1)
+        .use_write_buffer_manager(write_buffer_manager.clone())
+        .open()?;
+
+    assert_eq!(2, write_buffer_manager.tree_count());
+
+    for x in 0..10_u64 {
+        tree0.insert(x.to_be_bytes(), "a", x);
+    }
+
+    for x in 0..100_u64 {
+        tree1.insert(x.to_be_bytes(), "a".repeat(10), x);
+    }
+
+    assert_eq!(
+        write_buffer_manager.usage(),
+        tree0.active_memtable_size() + tree1.active_memtable_size(),
+    );
+    assert!(write_buffer_manager.is_over_budget());
+
+    // NOTE: Flushing the largest memtable (of tree 1) is enough to get under budget
+    assert_eq!(1, write_buffer_manager.enforce(0)?);
+    assert!(!write_buffer_manager.is_over_budget());
+    assert_eq!(write_buffer_manager.usage(), tree0.active_memtable_size());
+
+    assert_eq!(0, tree0.segment_count());
+    assert_eq!(1, tree1.segment_count());
+    assert_eq!(0, tree1.active_memtable_size());
+
+    drop(tree1);
+    assert_eq!(1, write_buffer_manager.tree_count());
+
+    Ok(())
+}